@@ -0,0 +1,75 @@
+//! Allocation counts for the `alloc_count` attribute option.
+//!
+//! Counting needs every allocation of the process to go through [`CountingAllocator`], so it
+//! only works once the application installs it as the global allocator. The generated code
+//! reads the calling thread's count before and after the body and adds the difference to
+//! `function_allocations_total`. Without the allocator installed the count never moves and
+//! nothing is added.
+//!
+//! ```ignore
+//! use std::alloc::System;
+//!
+//! use metrics_utils::alloc::CountingAllocator;
+//!
+//! #[global_allocator]
+//! static ALLOCATOR: CountingAllocator<System> = CountingAllocator::new(System);
+//! ```
+
+use std::alloc::{GlobalAlloc, Layout};
+use std::cell::Cell;
+
+thread_local! {
+    // Const-initialized without a destructor, so reading it never allocates itself.
+    static ALLOCATIONS: Cell<u64> = const { Cell::new(0) };
+}
+
+/// A global allocator that counts the allocations of each thread and forwards them to `A`.
+///
+/// `alloc`, `alloc_zeroed` and `realloc` each count as one allocation; deallocations are not
+/// counted.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CountingAllocator<A> {
+    inner: A,
+}
+
+impl<A> CountingAllocator<A> {
+    /// Counts the allocations served by `inner`, usually `std::alloc::System`.
+    pub const fn new(inner: A) -> Self {
+        CountingAllocator { inner }
+    }
+}
+
+/// Adds one allocation to the calling thread's count. Allocations made while the thread is
+/// being torn down, after its count is gone, are not counted.
+fn count() {
+    let _ = ALLOCATIONS.try_with(|allocations| allocations.set(allocations.get() + 1));
+}
+
+// SAFETY: every call is forwarded unchanged to `inner`, which upholds the `GlobalAlloc`
+// contract itself; counting touches no allocator state.
+unsafe impl<A: GlobalAlloc> GlobalAlloc for CountingAllocator<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count();
+        self.inner.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        count();
+        self.inner.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count();
+        self.inner.realloc(ptr, layout, new_size)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.inner.dealloc(ptr, layout)
+    }
+}
+
+/// The number of allocations the calling thread has made through [`CountingAllocator`] so
+/// far, `0` if it isn't installed.
+pub fn thread_allocations() -> u64 {
+    ALLOCATIONS.try_with(Cell::get).unwrap_or(0)
+}
@@ -0,0 +1,117 @@
+//! Histogram bucket boundaries requested through the `buckets = [...]` attribute option.
+//!
+//! The `metrics` facade has no way to configure buckets, so the generated code registers
+//! them here the first time an instrumented function runs. Exporters that accept buckets per
+//! metric, such as `metrics-exporter-prometheus`, can then be configured from [`registered`],
+//! and from [`default_buckets`] for the histograms without buckets of their own.
+
+use std::sync::Mutex;
+
+static REGISTRY: Mutex<Vec<(&'static str, &'static [f64])>> = Mutex::new(Vec::new());
+
+/// Records `buckets` as the bucket boundaries for the histogram `metric`.
+///
+/// The first registration for a metric name wins, so functions sharing a histogram should
+/// agree on their buckets.
+pub fn register(metric: &'static str, buckets: &'static [f64]) {
+    let mut registry = REGISTRY
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    if !registry.iter().any(|(name, _)| *name == metric) {
+        registry.push((metric, buckets));
+    }
+}
+
+/// Returns every `(metric name, bucket boundaries)` pair registered so far.
+///
+/// Buckets are registered on the first call of an instrumented function, so exporters that
+/// build their configuration at install time only see functions that already ran.
+pub fn registered() -> Vec<(&'static str, &'static [f64])> {
+    REGISTRY
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clone()
+}
+
+/// The default bucket boundaries in milliseconds, from a tenth of a millisecond to ten seconds.
+const DEFAULT_MILLISECONDS: &[f64] = &[
+    0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0, 2500.0, 5000.0,
+    10000.0,
+];
+
+/// The duration histograms the attributes emit, before the unit suffix.
+const DURATION_HISTOGRAMS: &[&str] = &[
+    "function_duration",
+    "function_duration_ok",
+    "function_duration_err",
+    "async_function_duration",
+    "async_function_duration_ok",
+    "async_function_duration_err",
+    "async_function_busy",
+    "async_function_sync_prefix",
+    "function_cpu",
+    "function_interarrival",
+];
+
+/// The duration histograms that are always recorded in milliseconds.
+const MILLISECOND_HISTOGRAMS: &[&str] = &[
+    "closure_duration_milliseconds",
+    "block_duration_milliseconds",
+];
+
+/// The units a duration can be recorded in, with the factor converting milliseconds to them as
+/// a multiplier and a divisor, which keeps the scaled boundaries exact.
+const UNITS: &[(&str, f64, f64)] = &[
+    ("nanoseconds", 1e6, 1.0),
+    ("microseconds", 1e3, 1.0),
+    ("milliseconds", 1.0, 1.0),
+    ("seconds", 1.0, 1e3),
+];
+
+/// Returns bucket boundaries for every duration histogram name the attributes generate, in
+/// every unit, for configuring an exporter up front.
+///
+/// The boundaries span a tenth of a millisecond to ten seconds, scaled to the unit in the
+/// name, so `function_duration_seconds` gets `0.0001` to `10.0`. Names carry the
+/// `METRICS_MACROS_PREFIX` this crate was built with, which is the one the macros read as long
+/// as the whole build shares its environment. The list can go straight into an exporter:
+///
+/// ```ignore
+/// let mut builder = metrics_exporter_prometheus::PrometheusBuilder::new();
+/// for (name, buckets) in metrics_utils::buckets::default_buckets() {
+///     builder = builder.set_buckets_for_metric(Matcher::Full(name.as_str().to_owned()), &buckets)?;
+/// }
+/// ```
+///
+/// Names chosen with `metric = "..."` aren't known here, and neither are the `buckets = [...]`
+/// given to single functions; those come from [`registered`].
+pub fn default_buckets() -> Vec<(metrics::KeyName, Vec<f64>)> {
+    let mut buckets: Vec<_> = DURATION_HISTOGRAMS
+        .iter()
+        .flat_map(|name| {
+            UNITS.iter().map(move |(unit, multiplier, divisor)| {
+                let bounds = DEFAULT_MILLISECONDS
+                    .iter()
+                    .map(|bound| bound * multiplier / divisor)
+                    .collect();
+                (prefixed(&format!("{name}_{unit}")), bounds)
+            })
+        })
+        .collect();
+    buckets.extend(
+        MILLISECOND_HISTOGRAMS
+            .iter()
+            .map(|name| (prefixed(name), DEFAULT_MILLISECONDS.to_vec())),
+    );
+    buckets
+}
+
+/// Prepends the `METRICS_MACROS_PREFIX` of the build to `name`, as the macros do.
+fn prefixed(name: &str) -> metrics::KeyName {
+    let name = match option_env!("METRICS_MACROS_PREFIX") {
+        Some("") | None => name.to_owned(),
+        Some(prefix) if prefix.ends_with('_') => format!("{prefix}{name}"),
+        Some(prefix) => format!("{prefix}_{name}"),
+    };
+    metrics::KeyName::from(name)
+}
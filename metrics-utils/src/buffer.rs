@@ -0,0 +1,92 @@
+//! Thread-local batching of the duration histograms, enabled by the `buffered` feature.
+//!
+//! With the feature on, the generated code records durations through [`BufferedSink`], which
+//! appends each sample to a buffer owned by the calling thread instead of dispatching to the
+//! `metrics` recorder. The samples are forwarded with `metrics::histogram!` when the thread's
+//! buffer reaches [`CAPACITY`], when [`flush`] is called on that thread and when the thread
+//! exits. Until then they are invisible to the recorder, which is the trade-off for taking
+//! the recorder off the hot path. Companion metrics such as `count` and `inflight` are
+//! recorded directly.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use metrics::Label;
+
+use crate::DurationSink;
+
+/// The number of samples a thread buffers before forwarding them on its own.
+pub const CAPACITY: usize = 1024;
+
+type Samples = HashMap<(&'static str, Vec<Label>), Vec<f64>>;
+
+/// A thread's pending samples, forwarded when the thread exits.
+#[derive(Default)]
+struct Buffer {
+    samples: Samples,
+    len: usize,
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        forward(std::mem::take(&mut self.samples));
+    }
+}
+
+thread_local! {
+    static BUFFER: RefCell<Buffer> = RefCell::new(Buffer::default());
+}
+
+/// Appends `value` to the calling thread's buffer, forwarding the buffer once it is full.
+pub fn record(metric: &'static str, labels: &[Label], value: f64) {
+    let full = BUFFER.with(|buffer| {
+        let mut buffer = buffer.borrow_mut();
+        buffer
+            .samples
+            .entry((metric, labels.to_vec()))
+            .or_default()
+            .push(value);
+        buffer.len += 1;
+        buffer.len >= CAPACITY
+    });
+    if full {
+        flush();
+    }
+}
+
+/// Forwards every sample buffered by the calling thread to the `metrics` recorder.
+///
+/// Each thread flushes its own buffer, so a periodic flush has to run on every thread that
+/// records, e.g. at the end of each batch of work. Samples of threads that exit are forwarded
+/// automatically.
+pub fn flush() {
+    // The buffer is emptied before recording, so a recorder that is itself instrumented
+    // doesn't find it borrowed.
+    let samples = BUFFER.with(|buffer| {
+        let mut buffer = buffer.borrow_mut();
+        buffer.len = 0;
+        std::mem::take(&mut buffer.samples)
+    });
+    forward(samples);
+}
+
+/// Records `samples` to the `metrics` recorder, resolving each histogram once.
+fn forward(samples: Samples) {
+    for ((metric, labels), values) in samples {
+        let histogram = metrics::histogram!(metric, labels);
+        for value in values {
+            histogram.record(value);
+        }
+    }
+}
+
+/// Buffers samples on the calling thread, which is what the generated code does with the
+/// `buffered` feature and no `sink = ...`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BufferedSink;
+
+impl DurationSink for BufferedSink {
+    fn record_duration(&self, metric: &'static str, labels: &[Label], value: f64) {
+        record(metric, labels, value);
+    }
+}
@@ -0,0 +1,63 @@
+//! The bounded sets of label values behind the `max_cardinality` attribute option.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Mutex;
+
+use metrics::Label;
+
+/// The value recorded in place of a dynamic label value once its label reached the cap.
+pub const OVERFLOW: &str = "__overflow__";
+
+/// The distinct values seen for each dynamic label of one instrumented function.
+///
+/// With `max_cardinality = N`, the generated code keeps one of these in a `static` per source
+/// of dynamic labels and passes every runtime value through [`admit`](Cardinality::admit).
+/// The first `N` distinct values of a label are recorded as they are, later new values are
+/// recorded as [`OVERFLOW`], so an unbounded label such as a user id can't grow the
+/// exporter's memory without bound. At most `N` values are stored per label.
+#[derive(Debug, Default)]
+pub struct Cardinality {
+    seen: Mutex<BTreeMap<String, BTreeSet<String>>>,
+}
+
+impl Cardinality {
+    /// An empty set, usable in a `static`.
+    pub const fn new() -> Self {
+        Cardinality {
+            seen: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Returns `value` if it was seen for `key` before or `key` has fewer than `max` values,
+    /// and [`OVERFLOW`] otherwise.
+    pub fn admit(&self, key: &str, value: String, max: usize) -> String {
+        let mut seen = self
+            .seen
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let values = seen.entry(key.to_owned()).or_default();
+        if values.contains(&value) {
+            return value;
+        }
+        if values.len() < max {
+            values.insert(value.clone());
+            return value;
+        }
+        OVERFLOW.to_owned()
+    }
+
+    /// Forgets every value seen so far, so the next `max` distinct values are admitted again.
+    pub fn clear(&self) {
+        self.seen
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clear();
+    }
+
+    /// Passes the value of `label` through [`admit`](Cardinality::admit).
+    pub fn admit_label(&self, label: Label, max: usize) -> Label {
+        let (key, value) = label.into_parts();
+        let value = self.admit(&key, value.to_string(), max);
+        Label::new(key, value)
+    }
+}
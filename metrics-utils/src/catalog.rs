@@ -0,0 +1,48 @@
+//! An inventory of the duration histograms emitted by instrumented functions, enabled by the
+//! `catalog` feature.
+//!
+//! With the feature on, every instrumented function registers a [`MetricDescriptor`] here the
+//! first time it runs, so documentation generators and dashboards can enumerate the metric
+//! names in use through [`collected_metrics`].
+
+use std::sync::Mutex;
+
+static CATALOG: Mutex<Vec<MetricDescriptor>> = Mutex::new(Vec::new());
+
+/// Describes the duration histogram of one instrumented function.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MetricDescriptor {
+    /// The histogram name, including any `METRICS_MACROS_PREFIX`.
+    pub name: &'static str,
+    /// The unit of the recorded values, e.g. `"milliseconds"`.
+    pub unit: &'static str,
+    /// The key of the label carrying the function name, `"function"` by default.
+    pub label_key: &'static str,
+    /// The value of that label, or `None` if it is taken from a parameter at runtime.
+    pub label: Option<&'static str>,
+    /// The name of the instrumented function as written.
+    pub function: &'static str,
+    /// The module the function is defined in, from `module_path!()`.
+    pub module_path: &'static str,
+}
+
+/// Adds `descriptor` to the catalog.
+pub fn register(descriptor: MetricDescriptor) {
+    CATALOG
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .push(descriptor);
+}
+
+/// Returns the descriptors of every instrumented function that ran so far, in the order of
+/// their first calls.
+///
+/// Functions register on their first call, so functions that haven't been called yet are
+/// missing. A tool generating documentation should exercise the code paths it documents, e.g.
+/// from a test, before reading the catalog.
+pub fn collected_metrics() -> Vec<MetricDescriptor> {
+    CATALOG
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clone()
+}
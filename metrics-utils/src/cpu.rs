@@ -0,0 +1,29 @@
+//! Thread CPU time for the `cpu_time` attribute option.
+//!
+//! The generated code reads the calling thread's CPU clock before and after the body and
+//! records the difference next to the wall-clock duration, so time spent blocked, sleeping or
+//! waiting for a lock shows up as the gap between the two.
+
+use std::time::Duration;
+
+/// The CPU time consumed by the calling thread so far, or `None` where the platform has no
+/// per-thread CPU clock.
+///
+/// Uses `clock_gettime(CLOCK_THREAD_CPUTIME_ID)` on Unix. Other platforms return `None` and
+/// nothing is recorded.
+pub fn thread_time() -> Option<Duration> {
+    #[cfg(unix)]
+    {
+        let mut time = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        };
+        // SAFETY: `time` is a valid, writable `timespec` for the duration of the call.
+        let result = unsafe { libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut time) };
+        (result == 0).then(|| Duration::new(time.tv_sec as u64, time.tv_nsec as u32))
+    }
+    #[cfg(not(unix))]
+    {
+        None
+    }
+}
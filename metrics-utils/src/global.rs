@@ -0,0 +1,77 @@
+//! A duration sink installed at runtime, enabled by the `global_sink` feature.
+//!
+//! With the feature on, the generated code records durations through [`GlobalSink`], which
+//! forwards them to the sink installed with [`set_global_sink`]. This is the way to plug in a
+//! backend the `metrics` crate doesn't support, chosen by the application rather than by each
+//! `sink = ...` attribute. Until a sink is installed, durations are recorded with
+//! `metrics::histogram!` as they would be without the feature. Companion metrics such as
+//! `count` and `inflight` still go through `metrics`.
+
+use std::sync::{Arc, OnceLock};
+
+use metrics::Label;
+
+use crate::{DurationSink, MetricsSink};
+
+/// A sink shared by every thread that records.
+pub type SharedSink = Arc<dyn DurationSink + Send + Sync>;
+
+static SINK: OnceLock<SharedSink> = OnceLock::new();
+
+/// Installs the sink receiving the duration histograms of every instrumented function.
+///
+/// The sink can be installed once per process, typically during startup; if one is already in
+/// place the given sink is handed back. Calls recorded before then go to `metrics`.
+///
+/// ```ignore
+/// use std::sync::Arc;
+/// use metrics::Label;
+/// use metrics_utils::DurationSink;
+///
+/// struct Tsdb(tsdb::Client);
+///
+/// impl DurationSink for Tsdb {
+///     fn record_duration(&self, metric: &'static str, labels: &[Label], value: f64) {
+///         self.0.push(metric, labels, value);
+///     }
+/// }
+///
+/// metrics_utils::global::set_global_sink(Arc::new(Tsdb(tsdb::Client::connect())))
+///     .unwrap_or_else(|_| panic!("a global sink is already installed"));
+/// ```
+pub fn set_global_sink(sink: SharedSink) -> Result<(), SharedSink> {
+    SINK.set(sink)
+}
+
+/// The installed sink, if any.
+pub fn global_sink() -> Option<&'static SharedSink> {
+    SINK.get()
+}
+
+/// Records to the sink installed with [`set_global_sink`], or with `metrics::histogram!` before
+/// one is installed, which is what the generated code does with the `global_sink` feature and
+/// no `sink = ...`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GlobalSink;
+
+impl DurationSink for GlobalSink {
+    fn record_duration(&self, metric: &'static str, labels: &[Label], value: f64) {
+        match SINK.get() {
+            Some(sink) => sink.record_duration(metric, labels, value),
+            None => MetricsSink.record_duration(metric, labels, value),
+        }
+    }
+
+    fn record_duration_with_exemplar(
+        &self,
+        metric: &'static str,
+        labels: &[Label],
+        value: f64,
+        exemplar: Option<&str>,
+    ) {
+        match SINK.get() {
+            Some(sink) => sink.record_duration_with_exemplar(metric, labels, value, exemplar),
+            None => MetricsSink.record_duration(metric, labels, value),
+        }
+    }
+}
@@ -0,0 +1,87 @@
+//! A drop guard for timing code that doesn't fit an attribute or `measure!`.
+
+use std::time::{Duration, Instant};
+
+use metrics::Label;
+
+use crate::timing::sink;
+use crate::DurationSink;
+
+/// Records the time between its creation and its drop to a histogram.
+///
+/// The duration is recorded in milliseconds to `metric`, with a `function` label holding
+/// the label value and a `status` label, matching what the attribute macros emit: `"panic"`
+/// when the guard is dropped by a panic unwinding past it and `"ok"` otherwise. Drop the guard
+/// (or call [`finish`](MeasureGuard::finish)) at the point the measured work ends:
+///
+/// ```ignore
+/// use metrics_utils::MeasureGuard;
+///
+/// fn handle(request: Request) -> Response {
+///     let guard = MeasureGuard::new("function_duration_milliseconds", "handle");
+///     if let Some(cached) = cache_lookup(&request) {
+///         return cached; // recorded when `guard` goes out of scope
+///     }
+///     let response = render(&request);
+///     guard.finish();
+///     log_response(&response); // not part of the recorded duration
+///     response
+/// }
+/// ```
+///
+/// The guard only holds an [`Instant`], `&'static str`s and a flag, so it is `Send` and `Sync`
+/// and may be moved to or shared with other threads; the sample is recorded on whichever thread
+/// drops it. A guard created while its thread was already unwinding, e.g. in a `Drop` impl,
+/// is recorded as `"ok"` when that unwinding drops it.
+///
+/// The histogram goes to the same destination as with [`measure_fn`](crate::measure_fn).
+#[must_use = "the duration is recorded when the guard is dropped, so binding it to `_` records immediately"]
+#[derive(Debug)]
+pub struct MeasureGuard {
+    metric: &'static str,
+    label: &'static str,
+    start: Instant,
+    /// Whether the thread was unwinding when the guard was created.
+    unwinding: bool,
+}
+
+impl MeasureGuard {
+    /// Starts timing, to be recorded to `metric` with `function="<label>"`.
+    pub fn new(metric: &'static str, label: &'static str) -> Self {
+        MeasureGuard {
+            metric,
+            label,
+            start: Instant::now(),
+            unwinding: std::thread::panicking(),
+        }
+    }
+
+    /// The time elapsed since the guard was created.
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// Records the elapsed time now and consumes the guard, returning the recorded duration.
+    pub fn finish(self) -> Duration {
+        let elapsed = self.record("ok");
+        std::mem::forget(self);
+        elapsed
+    }
+
+    fn record(&self, status: &'static str) -> Duration {
+        let elapsed = self.elapsed();
+        let labels = [
+            Label::new("function", self.label),
+            Label::new("status", status),
+        ];
+        sink().record_duration(self.metric, &labels, elapsed.as_secs_f64() * 1000.0);
+        elapsed
+    }
+}
+
+impl Drop for MeasureGuard {
+    fn drop(&mut self) {
+        let panicked = std::thread::panicking() && !self.unwinding;
+        self.record(if panicked { "panic" } else { "ok" });
+    }
+}
@@ -0,0 +1,59 @@
+//! Pre-resolved histograms for functions instrumented with `handle = ...`.
+
+use std::sync::{LazyLock, OnceLock};
+
+use metrics::Histogram;
+
+/// A histogram resolved ahead of time, which `#[measured_function(handle = HANDLE)]` records
+/// the durations into instead of looking the histogram up with `metrics::histogram!` on every
+/// call.
+///
+/// The handle carries its own name and labels, fixed when it was resolved. It is implemented
+/// for a [`Histogram`], for a [`LazyLock`] resolving one on first use and for a [`OnceLock`]
+/// set during startup:
+///
+/// ```ignore
+/// use std::sync::LazyLock;
+/// use metrics::Histogram;
+/// use metrics_utils::measured_function;
+///
+/// static ENCODE: LazyLock<Histogram> =
+///     LazyLock::new(|| metrics::histogram!("encode_duration_milliseconds"));
+///
+/// #[measured_function(handle = ENCODE)]
+/// fn encode(frame: &[u8]) -> Vec<u8> {
+///     frame.to_vec()
+/// }
+/// ```
+///
+/// A `OnceLock` that hasn't been set yet returns `None`, and the call is recorded with
+/// `metrics::histogram!` as it would be without `handle`. That covers calls made before the
+/// recorder, and with it the handle, was installed.
+pub trait HistogramHandle {
+    /// Returns the histogram to record into, or `None` to fall back to `metrics::histogram!`.
+    fn histogram(&self) -> Option<&Histogram>;
+}
+
+impl HistogramHandle for Histogram {
+    fn histogram(&self) -> Option<&Histogram> {
+        Some(self)
+    }
+}
+
+impl<T: HistogramHandle + ?Sized> HistogramHandle for &T {
+    fn histogram(&self) -> Option<&Histogram> {
+        (**self).histogram()
+    }
+}
+
+impl HistogramHandle for OnceLock<Histogram> {
+    fn histogram(&self) -> Option<&Histogram> {
+        self.get()
+    }
+}
+
+impl<F: FnOnce() -> Histogram> HistogramHandle for LazyLock<Histogram, F> {
+    fn histogram(&self) -> Option<&Histogram> {
+        Some(LazyLock::force(self))
+    }
+}
@@ -0,0 +1,48 @@
+//! The hour of day for the `time_bucket` attribute option.
+//!
+//! With `time_bucket`, the generated code labels each call with the hour it was recorded in,
+//! `"0"` to `"23"`, so latency can be segmented by time of day without a query over the whole
+//! series. The labels are borrowed from a static table and cost no allocation.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const HOURS: [&str; 24] = [
+    "0", "1", "2", "3", "4", "5", "6", "7", "8", "9", "10", "11", "12", "13", "14", "15", "16",
+    "17", "18", "19", "20", "21", "22", "23",
+];
+
+/// The current hour of day in UTC, as a label value.
+pub fn utc() -> &'static str {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+    HOURS[(seconds / 3600 % 24) as usize]
+}
+
+/// The current hour of day in the local time zone, as a label value, with the `local_time`
+/// feature.
+///
+/// Uses `localtime_r` on Unix. Other platforms, and a failing conversion, fall back to
+/// [`utc`].
+#[cfg(feature = "local_time")]
+pub fn local() -> &'static str {
+    #[cfg(unix)]
+    {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs()) as libc::time_t;
+        // SAFETY: an all-zero `tm` is a valid value for `localtime_r` to overwrite.
+        let mut time: libc::tm = unsafe { std::mem::zeroed() };
+        // SAFETY: both pointers are valid for the duration of the call, and `localtime_r`
+        // doesn't keep them.
+        let converted = unsafe { libc::localtime_r(&seconds, &mut time) };
+        match usize::try_from(time.tm_hour) {
+            Ok(hour) if !converted.is_null() && hour < 24 => HOURS[hour],
+            _ => utc(),
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        utc()
+    }
+}
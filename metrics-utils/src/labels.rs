@@ -0,0 +1,36 @@
+//! The runtime labels returned by the provider of the `labels_from` attribute option.
+
+use metrics::{Label, SharedString};
+
+/// A set of labels that `labels_from` adds to the samples of a call.
+///
+/// Implemented for a `Vec` of [`Label`]s and for a `Vec` of key-value pairs such as
+/// `Vec<(&'static str, String)>`.
+#[diagnostic::on_unimplemented(
+    message = "`labels_from` cannot use `{Self}` as labels",
+    label = "`{Self}` does not implement `LabelSet`",
+    note = "the `labels_from` function should return `Vec<(&'static str, String)>` or \
+            `Vec<metrics::Label>`"
+)]
+pub trait LabelSet {
+    /// Converts the set into the labels to record.
+    fn into_labels(self) -> Vec<Label>;
+}
+
+impl LabelSet for Vec<Label> {
+    fn into_labels(self) -> Vec<Label> {
+        self
+    }
+}
+
+impl<K, V> LabelSet for Vec<(K, V)>
+where
+    K: Into<SharedString>,
+    V: Into<SharedString>,
+{
+    fn into_labels(self) -> Vec<Label> {
+        self.into_iter()
+            .map(|(key, value)| Label::new(key, value))
+            .collect()
+    }
+}
@@ -0,0 +1,81 @@
+//! The collection sizes recorded by the `record_len` attribute option.
+
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet, LinkedList, VecDeque};
+
+/// A returned value whose size `record_len` records to `function_result_size`.
+///
+/// Implemented for the standard collections, strings and slices, and for `Option` and
+/// `Result` wrapping one of them. `None` and `Err` values have no size, so nothing is recorded
+/// for them. Implement it for your own collection types to record them too.
+#[diagnostic::on_unimplemented(
+    message = "`record_len` cannot measure the size of `{Self}`",
+    label = "`{Self}` does not implement `MeasuredLen`",
+    note = "`record_len` supports standard collections, strings and slices, possibly wrapped in \
+            `Option` or `Result`; implement `metrics_utils::MeasuredLen` for other types"
+)]
+pub trait MeasuredLen {
+    /// The number of elements to record, or `None` to record nothing for this value.
+    fn measured_len(&self) -> Option<usize>;
+}
+
+macro_rules! impl_len {
+    ($($ty:ty => [$($generics:tt)*]),* $(,)?) => {
+        $(
+            impl<$($generics)*> MeasuredLen for $ty {
+                fn measured_len(&self) -> Option<usize> {
+                    Some(self.len())
+                }
+            }
+        )*
+    };
+}
+
+impl_len! {
+    Vec<T> => [T],
+    VecDeque<T> => [T],
+    LinkedList<T> => [T],
+    BinaryHeap<T> => [T],
+    HashMap<K, V, S> => [K, V, S],
+    HashSet<T, S> => [T, S],
+    BTreeMap<K, V> => [K, V],
+    BTreeSet<T> => [T],
+    [T] => [T],
+    String => [],
+    str => [],
+}
+
+impl<T, const N: usize> MeasuredLen for [T; N] {
+    fn measured_len(&self) -> Option<usize> {
+        Some(N)
+    }
+}
+
+impl<T: MeasuredLen + ?Sized> MeasuredLen for &T {
+    fn measured_len(&self) -> Option<usize> {
+        (**self).measured_len()
+    }
+}
+
+impl<T: MeasuredLen + ?Sized> MeasuredLen for &mut T {
+    fn measured_len(&self) -> Option<usize> {
+        (**self).measured_len()
+    }
+}
+
+impl<T: MeasuredLen + ?Sized> MeasuredLen for Box<T> {
+    fn measured_len(&self) -> Option<usize> {
+        (**self).measured_len()
+    }
+}
+
+impl<T: MeasuredLen> MeasuredLen for Option<T> {
+    fn measured_len(&self) -> Option<usize> {
+        self.as_ref().and_then(MeasuredLen::measured_len)
+    }
+}
+
+impl<T: MeasuredLen, E> MeasuredLen for Result<T, E> {
+    fn measured_len(&self) -> Option<usize> {
+        self.as_ref().ok().and_then(MeasuredLen::measured_len)
+    }
+}
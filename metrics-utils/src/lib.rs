@@ -0,0 +1,47 @@
+//! Runtime support for the `metrics-utils-macros` attributes.
+//!
+//! The attribute macros are re-exported from here, so depending on this crate alone is enough.
+//! Options whose generated code needs shared state, such as `buckets`, call into this crate and
+//! therefore require it as a dependency.
+
+pub use metrics_utils_macros::*;
+
+#[cfg(feature = "alloc_count")]
+pub mod alloc;
+pub mod buckets;
+#[cfg(feature = "buffered")]
+pub mod buffer;
+pub mod cardinality;
+#[cfg(feature = "catalog")]
+pub mod catalog;
+#[cfg(feature = "cpu_time")]
+pub mod cpu;
+#[cfg(feature = "global_sink")]
+pub mod global;
+pub mod guard;
+pub mod handle;
+pub mod hour;
+pub mod labels;
+pub mod len;
+#[cfg(feature = "otel")]
+pub mod otel;
+#[cfg(feature = "warn_no_recorder")]
+pub mod recorder;
+#[cfg(feature = "test_util")]
+pub mod reset;
+pub mod ring;
+pub mod sink;
+pub mod summary;
+#[cfg(feature = "runtime_switch")]
+pub mod switch;
+pub mod timing;
+pub mod variant;
+pub mod window;
+
+pub use guard::MeasureGuard;
+pub use handle::HistogramHandle;
+pub use labels::LabelSet;
+pub use len::MeasuredLen;
+pub use sink::{DurationSink, MetricsSink};
+pub use timing::{measure_fn, measure_future};
+pub use variant::VariantName;
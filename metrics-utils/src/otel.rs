@@ -0,0 +1,59 @@
+//! OpenTelemetry backend for the duration histograms, enabled by the `otel` feature.
+//!
+//! With the feature on, the generated code records durations through [`OtelSink`] instead of
+//! `metrics::histogram!`. Each metric name gets one `f64` histogram instrument, created on
+//! first use from the configured [`Meter`] and cached for later calls. Labels become
+//! attributes of the same name. Companion metrics such as `count` and `inflight` still go
+//! through `metrics`.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+
+use metrics::Label;
+use opentelemetry::metrics::{Histogram, Meter};
+use opentelemetry::KeyValue;
+
+use crate::DurationSink;
+
+static METER: OnceLock<Meter> = OnceLock::new();
+static HISTOGRAMS: Mutex<Option<HashMap<&'static str, Histogram<f64>>>> = Mutex::new(None);
+
+/// Sets the meter the histogram instruments are created from.
+///
+/// Without it, `opentelemetry::global::meter("metrics-utils")` is used. The meter is fixed by
+/// the first instrumented call, so set it before then; if one is already in place the given
+/// meter is handed back.
+pub fn set_meter(meter: Meter) -> Result<(), Meter> {
+    METER.set(meter)
+}
+
+fn meter() -> &'static Meter {
+    METER.get_or_init(|| opentelemetry::global::meter("metrics-utils"))
+}
+
+/// The cached histogram instrument for `metric`, created on first use.
+fn histogram(metric: &'static str) -> Histogram<f64> {
+    let mut histograms = HISTOGRAMS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    histograms
+        .get_or_insert_with(HashMap::new)
+        .entry(metric)
+        .or_insert_with(|| meter().f64_histogram(metric).build())
+        .clone()
+}
+
+/// Records to OpenTelemetry histograms, which is what the generated code does with the `otel`
+/// feature and no `sink = ...`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct OtelSink;
+
+impl DurationSink for OtelSink {
+    fn record_duration(&self, metric: &'static str, labels: &[Label], value: f64) {
+        let attributes: Vec<KeyValue> = labels
+            .iter()
+            .map(|label| KeyValue::new(label.key().to_owned(), label.value().to_owned()))
+            .collect();
+        histogram(metric).record(value, &attributes);
+    }
+}
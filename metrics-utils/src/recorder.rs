@@ -0,0 +1,36 @@
+//! A one-time hint that no `metrics` recorder is installed, enabled by the `warn_no_recorder`
+//! feature.
+//!
+//! Without a recorder every metric call is silently dropped. With the feature on, the first
+//! instrumented call of the process checks for one and reports its absence once, through
+//! `tracing::warn!` with the `tracing` feature and on stderr otherwise.
+
+use std::sync::Once;
+
+/// The hint reported when no recorder is installed.
+pub const MESSAGE: &str = "no `metrics` recorder is installed, so the metrics of instrumented \
+                           functions are dropped; install one, e.g. with \
+                           `metrics::set_global_recorder`, before the first call";
+
+static CHECKED: Once = Once::new();
+
+/// Whether the calling thread records to the `metrics` facade's no-op recorder, because
+/// neither a global nor a local recorder is set.
+///
+/// The facade doesn't expose which recorder is in use, but falls back to a zero-sized no-op
+/// recorder, so this checks for a zero-sized one. A recorder of your own that is zero-sized is
+/// therefore reported as missing as well.
+pub fn missing() -> bool {
+    metrics::with_recorder(|recorder| std::mem::size_of_val(recorder) == 0)
+}
+
+/// Calls `report` with [`MESSAGE`] if no recorder is installed, on the first call of the
+/// process only. Later calls return right away.
+#[inline]
+pub fn check(report: fn(&str)) {
+    CHECKED.call_once(|| {
+        if missing() {
+            report(MESSAGE);
+        }
+    });
+}
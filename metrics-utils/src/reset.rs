@@ -0,0 +1,52 @@
+//! Resetting the per-function state of the attribute options between tests, enabled by the
+//! `test_util` feature.
+//!
+//! Options such as `sample`, `warmup`, `tail_sample`, `ewma`, `interarrival` and
+//! `max_cardinality` keep their state in a `static` inside the instrumented function, which
+//! lives as long as the process. Tests sharing a process therefore see the counters and
+//! averages left behind by earlier tests. With the feature on, each of those statics registers
+//! a reset here on the function's first call, and [`reset_all_state`] runs them all.
+//!
+//! The cached answer of `enabled_if` is not reset, since it is decided once per process.
+
+use std::sync::Mutex;
+
+static RESETS: Mutex<Vec<fn()>> = Mutex::new(Vec::new());
+
+/// Adds `reset` to the functions run by [`reset_all_state`].
+pub fn register(reset: fn()) {
+    RESETS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .push(reset);
+}
+
+/// Returns the state of every instrumented function that ran so far to the state it had
+/// before its first call, and empties the windows of `summary` and the buffers of `ringbuffer`.
+///
+/// Calls made concurrently with the reset may still see the old state, so reset between
+/// tests rather than during one. Tests running in parallel share the state too; a test
+/// relying on a fresh counter should run on its own, e.g. with `--test-threads=1`.
+///
+/// ```ignore
+/// #[measured_function(sample = 10)]
+/// fn lookup() {}
+///
+/// #[test]
+/// fn first_call_is_sampled() {
+///     metrics_utils::reset::reset_all_state();
+///     lookup();
+///     // the first call after a reset is recorded again
+/// }
+/// ```
+pub fn reset_all_state() {
+    let resets = RESETS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clone();
+    for reset in resets {
+        reset();
+    }
+    crate::summary::clear();
+    crate::ring::clear();
+}
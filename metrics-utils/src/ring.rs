@@ -0,0 +1,55 @@
+//! In-memory buffers of recent durations recorded through the `ringbuffer` attribute option.
+//!
+//! For debugging without a metrics stack, the generated code with `ringbuffer = N` also keeps
+//! the last `N` recorded durations of the function here, keyed by its `function` label, and
+//! [`recent_durations`] reads them back, e.g. from a test or a `dbg!`.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+static BUFFERS: Mutex<Option<HashMap<&'static str, VecDeque<f64>>>> = Mutex::new(None);
+
+/// Records `value` to the buffer of `function`, evicting the oldest durations once it holds
+/// `capacity` of them.
+pub fn record(function: &'static str, capacity: usize, value: f64) {
+    let mut buffers = BUFFERS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let buffer = buffers
+        .get_or_insert_with(HashMap::new)
+        .entry(function)
+        .or_default();
+    while buffer.len() >= capacity {
+        buffer.pop_front();
+    }
+    buffer.push_back(value);
+}
+
+/// The most recent durations recorded for `function`, oldest first, in the unit of its
+/// histogram. Empty if the function wasn't called yet or doesn't use `ringbuffer`.
+///
+/// ```ignore
+/// #[measured_function(ringbuffer = 8)]
+/// fn lookup() {}
+///
+/// lookup();
+/// assert_eq!(metrics_utils::ring::recent_durations("lookup").len(), 1);
+/// ```
+pub fn recent_durations(function: &str) -> Vec<f64> {
+    let buffers = BUFFERS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    buffers
+        .as_ref()
+        .and_then(|buffers| buffers.get(function))
+        .map(|buffer| buffer.iter().copied().collect())
+        .unwrap_or_default()
+}
+
+/// Drops every buffer, for `reset::reset_all_state`.
+#[cfg(feature = "test_util")]
+pub(crate) fn clear() {
+    *BUFFERS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = None;
+}
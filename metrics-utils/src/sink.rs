@@ -0,0 +1,90 @@
+//! Pluggable destinations for the durations recorded by the attribute macros.
+
+use metrics::Label;
+
+/// Receives the duration histograms of functions instrumented with `sink = ...`.
+///
+/// By default the generated code records straight to the global `metrics` recorder. With
+/// `#[measured_function(sink = MY_SINK)]` it instead calls [`record_duration`] on the given
+/// value, which can be a unit struct, a `const` or a `static`. The call is made on that
+/// concrete value, so it is monomorphized rather than dispatched through a vtable.
+///
+/// This makes instrumented code testable without installing a global recorder, for example
+/// with a sink that collects samples into a thread-local:
+///
+/// ```ignore
+/// use std::cell::RefCell;
+/// use metrics::Label;
+/// use metrics_utils::{measured_function, DurationSink};
+///
+/// thread_local! {
+///     static SAMPLES: RefCell<Vec<(&'static str, Vec<Label>, f64)>> = RefCell::new(Vec::new());
+/// }
+///
+/// struct Capture;
+///
+/// impl DurationSink for Capture {
+///     fn record_duration(&self, metric: &'static str, labels: &[Label], value: f64) {
+///         SAMPLES.with(|samples| samples.borrow_mut().push((metric, labels.to_vec(), value)));
+///     }
+/// }
+///
+/// #[measured_function(sink = Capture)]
+/// fn work() {}
+/// ```
+///
+/// Only the duration histograms go through the sink; companion metrics such as `count` and
+/// `inflight` are still recorded with `metrics`.
+///
+/// To pick the sink at runtime instead, for every instrumented function at once, enable the
+/// `global_sink` feature and install it with `metrics_utils::global::set_global_sink`.
+///
+/// [`record_duration`]: DurationSink::record_duration
+pub trait DurationSink {
+    /// Records `value`, in the unit selected by the attribute, to the histogram `metric`.
+    fn record_duration(&self, metric: &'static str, labels: &[Label], value: f64);
+
+    /// Records `value` like [`record_duration`], along with the trace id from `exemplar_from`
+    /// when the provider returned one.
+    ///
+    /// Sinks backed by a recorder that supports exemplars can attach `exemplar` to the sample.
+    /// The default implementation drops it and records the plain value.
+    ///
+    /// [`record_duration`]: DurationSink::record_duration
+    fn record_duration_with_exemplar(
+        &self,
+        metric: &'static str,
+        labels: &[Label],
+        value: f64,
+        exemplar: Option<&str>,
+    ) {
+        let _ = exemplar;
+        self.record_duration(metric, labels, value);
+    }
+}
+
+/// Forwards to `metrics::histogram!`, which is what the generated code does without `sink = ...`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MetricsSink;
+
+impl DurationSink for MetricsSink {
+    fn record_duration(&self, metric: &'static str, labels: &[Label], value: f64) {
+        metrics::histogram!(metric, labels.to_vec()).record(value);
+    }
+}
+
+impl<S: DurationSink + ?Sized> DurationSink for &S {
+    fn record_duration(&self, metric: &'static str, labels: &[Label], value: f64) {
+        (**self).record_duration(metric, labels, value);
+    }
+
+    fn record_duration_with_exemplar(
+        &self,
+        metric: &'static str,
+        labels: &[Label],
+        value: f64,
+        exemplar: Option<&str>,
+    ) {
+        (**self).record_duration_with_exemplar(metric, labels, value, exemplar);
+    }
+}
@@ -0,0 +1,105 @@
+//! Client-side quantile summaries recorded through the `summary` attribute option.
+//!
+//! The `metrics` facade only has histograms, and whether those end up as buckets or quantiles
+//! is up to the exporter. For backends that want quantiles computed in the process, the
+//! generated code with `summary` also records each duration here, keyed by the histogram's
+//! name and labels. Every key keeps a rolling window of its most recent [`WINDOW`] samples, and
+//! [`snapshot`] computes quantiles over it for exporting.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+use metrics::{Key, Label};
+
+/// The number of most recent samples each summary computes its quantiles over.
+pub const WINDOW: usize = 1024;
+
+static SUMMARIES: Mutex<Option<HashMap<Key, Window>>> = Mutex::new(None);
+
+#[derive(Default)]
+struct Window {
+    samples: VecDeque<f64>,
+    count: u64,
+    sum: f64,
+}
+
+/// Records `value` to the summary for `metric` with `labels`, evicting the oldest sample once
+/// the window is full.
+pub fn record(metric: &'static str, labels: &[Label], value: f64) {
+    let key = Key::from_parts(metric, labels.to_vec());
+    let mut summaries = SUMMARIES
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let window = summaries
+        .get_or_insert_with(HashMap::new)
+        .entry(key)
+        .or_default();
+    if window.samples.len() == WINDOW {
+        window.samples.pop_front();
+    }
+    window.samples.push_back(value);
+    window.count += 1;
+    window.sum += value;
+}
+
+/// The state of one summary at the time of a [`snapshot`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Summary {
+    sorted: Vec<f64>,
+    count: u64,
+    sum: f64,
+}
+
+impl Summary {
+    /// The `q` quantile, between `0.0` and `1.0`, of the samples in the window, or `None` if
+    /// nothing was recorded.
+    ///
+    /// Uses the nearest-rank method, so the result is always one of the recorded samples.
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        let last = self.sorted.len().checked_sub(1)?;
+        let rank = (q.clamp(0.0, 1.0) * last as f64).round() as usize;
+        Some(self.sorted[rank])
+    }
+
+    /// The number of samples recorded since the first one, including those that have left the
+    /// window.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// The sum of every sample recorded since the first one.
+    pub fn sum(&self) -> f64 {
+        self.sum
+    }
+}
+
+/// Returns every summary recorded so far, keyed by metric name and labels.
+pub fn snapshot() -> Vec<(Key, Summary)> {
+    let summaries = SUMMARIES
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    let Some(summaries) = summaries.as_ref() else {
+        return Vec::new();
+    };
+    summaries
+        .iter()
+        .map(|(key, window)| {
+            let mut sorted: Vec<f64> = window.samples.iter().copied().collect();
+            sorted.sort_by(f64::total_cmp);
+            let summary = Summary {
+                sorted,
+                count: window.count,
+                sum: window.sum,
+            };
+            (key.clone(), summary)
+        })
+        .collect()
+}
+
+/// Drops every summary, for `reset::reset_all_state`.
+#[cfg(feature = "test_util")]
+pub(crate) fn clear() {
+    *SUMMARIES
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = None;
+}
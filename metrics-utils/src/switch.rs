@@ -0,0 +1,29 @@
+//! A process-wide switch turning the instrumentation on and off at runtime, enabled by the
+//! `runtime_switch` feature.
+//!
+//! With the feature on, every instrumented function checks [`is_enabled`] on each call, a
+//! single relaxed atomic load. While the switch is off, calls run the body as written without
+//! reading the clock or recording anything, like calls left out by `enabled_if`, and
+//! `measure!` and `measured_closure!` record nothing. The switch starts on.
+//!
+//! Flipping it takes effect for calls that start afterwards; calls already running record as
+//! they were started.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Turns the instrumentation back on.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Turns the instrumentation off, e.g. from an admin endpoint during an incident.
+pub fn disable() {
+    ENABLED.store(false, Ordering::Relaxed);
+}
+
+/// Whether instrumented calls are currently recorded.
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
@@ -0,0 +1,128 @@
+//! Functions that time a closure or a future the way the attribute macros do, for code that
+//! can't carry an attribute, such as closures and futures built at runtime or returned by trait
+//! objects.
+
+use std::future::Future;
+use std::time::Instant;
+
+use metrics::Label;
+
+use crate::DurationSink;
+
+/// Awaits `future` and records its duration to the histogram `metric`, in milliseconds, with
+/// `function="<label>"`.
+///
+/// The samples match those of `#[measured_async_function]`: a future that completes is
+/// recorded with `status="ok"` and `completion="completed"`, one dropped before completing with
+/// `status="cancelled"` and `completion="cancelled"`, and one that panics with
+/// `status="panic"`. The time runs from the first poll, since nothing happens before it.
+///
+/// ```ignore
+/// use metrics_utils::timing::measure_future;
+///
+/// let response = measure_future(
+///     "async_function_duration_milliseconds",
+///     "fetch_user",
+///     client.fetch_user(id),
+/// )
+/// .await;
+/// ```
+///
+/// The histogram goes wherever the generated code sends it with the same features: through
+/// [`GlobalSink`](crate::global::GlobalSink) with `global_sink`,
+/// [`OtelSink`](crate::otel::OtelSink) with `otel`, [`BufferedSink`](crate::buffer::BufferedSink)
+/// with `buffered` and `metrics::histogram!` otherwise. Like [`MeasureGuard`](crate::MeasureGuard),
+/// it is not affected by the `disabled` feature.
+pub async fn measure_future<F: Future>(
+    metric: &'static str,
+    label: &'static str,
+    future: F,
+) -> F::Output {
+    let call = Call::start(metric, label, true);
+    let output = future.await;
+    call.finish(&[("status", "ok"), ("completion", "completed")]);
+    output
+}
+
+/// Runs `f` and records its duration to the histogram `metric`, in milliseconds, with
+/// `function="<label>"`, returning its value.
+///
+/// The samples match those of `#[measured_function]`: `status="ok"` when `f` returns and
+/// `status="panic"` when it panics, recorded while the panic unwinds through this function.
+///
+/// ```ignore
+/// use metrics_utils::timing::measure_fn;
+///
+/// let handler = handlers.get(route).unwrap();
+/// let response = measure_fn("function_duration_milliseconds", "dispatch", || handler(request));
+/// ```
+///
+/// The histogram goes to the same destination as with [`measure_future`].
+pub fn measure_fn<T>(metric: &'static str, label: &'static str, f: impl FnOnce() -> T) -> T {
+    let call = Call::start(metric, label, false);
+    let output = f();
+    call.finish(&[("status", "ok")]);
+    output
+}
+
+/// A running call, recorded as a panic or, for a future, a cancellation if it is dropped
+/// before [`finish`](Call::finish).
+struct Call {
+    metric: &'static str,
+    label: &'static str,
+    start: Instant,
+    future: bool,
+}
+
+impl Call {
+    fn start(metric: &'static str, label: &'static str, future: bool) -> Self {
+        Call {
+            metric,
+            label,
+            start: Instant::now(),
+            future,
+        }
+    }
+
+    /// Records the call with `labels` after the function label.
+    fn finish(self, labels: &[(&'static str, &'static str)]) {
+        self.record(labels);
+        std::mem::forget(self);
+    }
+
+    fn record(&self, labels: &[(&'static str, &'static str)]) {
+        let value = self.start.elapsed().as_secs_f64() * 1000.0;
+        let labels: Vec<Label> = std::iter::once(("function", self.label))
+            .chain(labels.iter().copied())
+            .map(|(key, value)| Label::new(key, value))
+            .collect();
+        sink().record_duration(self.metric, &labels, value);
+    }
+}
+
+impl Drop for Call {
+    fn drop(&mut self) {
+        let labels: &[_] = match (std::thread::panicking(), self.future) {
+            (true, true) => &[("status", "panic"), ("completion", "completed")],
+            (true, false) => &[("status", "panic")],
+            (false, _) => &[("status", "cancelled"), ("completion", "cancelled")],
+        };
+        self.record(labels);
+    }
+}
+
+/// The sink the generated code records duration histograms to without `sink = ...`, picked in
+/// the same order: `global_sink`, then `otel`, then `buffered`, then `metrics`.
+pub(crate) fn sink() -> impl DurationSink {
+    #[cfg(feature = "global_sink")]
+    return crate::global::GlobalSink;
+    #[cfg(all(feature = "otel", not(feature = "global_sink")))]
+    return crate::otel::OtelSink;
+    #[cfg(all(
+        feature = "buffered",
+        not(any(feature = "global_sink", feature = "otel"))
+    ))]
+    return crate::buffer::BufferedSink;
+    #[cfg(not(any(feature = "global_sink", feature = "otel", feature = "buffered")))]
+    return crate::MetricsSink;
+}
@@ -0,0 +1,55 @@
+//! The variant names recorded by the `label_variant` attribute option.
+
+/// A returned value whose variant `label_variant` records as the `variant` label.
+///
+/// Derive it for your own enums with `#[derive(VariantName)]`, which names each variant as
+/// written. It is implemented for `Option`, `Result` and `bool` as well.
+#[diagnostic::on_unimplemented(
+    message = "`label_variant` cannot name the variant of `{Self}`",
+    label = "`{Self}` does not implement `VariantName`",
+    note = "add `#[derive(metrics_utils::VariantName)]` to the returned enum, or implement \
+            `metrics_utils::VariantName` for it"
+)]
+pub trait VariantName {
+    /// The name of this value's variant, e.g. `"Pending"`.
+    fn variant_name(&self) -> &'static str;
+}
+
+impl<T> VariantName for Option<T> {
+    fn variant_name(&self) -> &'static str {
+        match self {
+            Some(_) => "Some",
+            None => "None",
+        }
+    }
+}
+
+impl<T, E> VariantName for Result<T, E> {
+    fn variant_name(&self) -> &'static str {
+        match self {
+            Ok(_) => "Ok",
+            Err(_) => "Err",
+        }
+    }
+}
+
+impl VariantName for bool {
+    fn variant_name(&self) -> &'static str {
+        match self {
+            true => "true",
+            false => "false",
+        }
+    }
+}
+
+impl<T: VariantName + ?Sized> VariantName for &T {
+    fn variant_name(&self) -> &'static str {
+        (**self).variant_name()
+    }
+}
+
+impl<T: VariantName + ?Sized> VariantName for Box<T> {
+    fn variant_name(&self) -> &'static str {
+        (**self).variant_name()
+    }
+}
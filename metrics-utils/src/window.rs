@@ -0,0 +1,75 @@
+//! Sliding-window error rates for the `windowed_error_rate` attribute option.
+//!
+//! Counters of errors and calls need a `rate()` at query time to give a recent error rate.
+//! With `windowed_error_rate = "10s"`, the generated code also counts each returned call in an
+//! [`ErrorWindow`] and publishes the share of errors among the calls of the last window as a
+//! gauge. The window is split into [`BUCKETS`] coarse time buckets, so its memory stays fixed
+//! however many calls it sees, and calls leave it a bucket at a time.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// The number of time buckets a window is split into.
+pub const BUCKETS: usize = 10;
+
+/// The errors and calls counted in one bucket, and the bucket's index since the first call.
+#[derive(Clone, Copy, Default)]
+struct Bucket {
+    index: u64,
+    errors: u64,
+    total: u64,
+}
+
+/// The error rate of one function over a sliding window, held in a `static`.
+pub struct ErrorWindow {
+    bucket_nanos: u64,
+    state: Mutex<Option<(Instant, [Bucket; BUCKETS])>>,
+}
+
+impl ErrorWindow {
+    /// A window covering the last `window`, which is at least one nanosecond per bucket.
+    pub const fn new(window: Duration) -> Self {
+        let bucket_nanos = window.as_nanos() as u64 / BUCKETS as u64;
+        ErrorWindow {
+            bucket_nanos: if bucket_nanos == 0 { 1 } else { bucket_nanos },
+            state: Mutex::new(None),
+        }
+    }
+
+    /// Counts a call, an error if `is_err`, and returns the share of errors among the calls of
+    /// the window so far, between `0.0` and `1.0`.
+    pub fn record(&self, is_err: bool) -> f64 {
+        let mut state = self
+            .state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let (start, buckets) =
+            state.get_or_insert_with(|| (Instant::now(), [Bucket::default(); BUCKETS]));
+        let index = (start.elapsed().as_nanos() / u128::from(self.bucket_nanos)) as u64;
+        let bucket = &mut buckets[(index % BUCKETS as u64) as usize];
+        if bucket.index != index {
+            *bucket = Bucket {
+                index,
+                ..Bucket::default()
+            };
+        }
+        bucket.errors += u64::from(is_err);
+        bucket.total += 1;
+
+        let (errors, total) = buckets
+            .iter()
+            .filter(|bucket| index - bucket.index < BUCKETS as u64)
+            .fold((0, 0), |(errors, total), bucket| {
+                (errors + bucket.errors, total + bucket.total)
+            });
+        errors as f64 / total as f64
+    }
+
+    /// Forgets every call counted so far, for `reset::reset_all_state`.
+    pub fn clear(&self) {
+        *self
+            .state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = None;
+    }
+}
@@ -0,0 +1,1647 @@
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{quote, quote_spanned, ToTokens};
+use syn::ext::IdentExt;
+use syn::spanned::Spanned;
+use syn::{
+    parse::Parse, parse::ParseStream, Attribute, Block, Ident, Lit, LitFloat, LitInt, LitStr, Path,
+    Token,
+};
+
+use crate::{defaults, prefix};
+
+/// The time unit a duration is recorded in.
+///
+/// The unit decides both the conversion applied to the measured `Duration` and the
+/// suffix of the emitted metric name.
+#[derive(Clone, Copy)]
+pub(crate) enum Unit {
+    Nanoseconds,
+    Microseconds,
+    Milliseconds,
+    Seconds,
+}
+
+impl Unit {
+    pub(crate) fn from_lit(lit: &LitStr) -> syn::Result<Self> {
+        match lit.value().as_str() {
+            "nanoseconds" => Ok(Unit::Nanoseconds),
+            "microseconds" => Ok(Unit::Microseconds),
+            "milliseconds" => Ok(Unit::Milliseconds),
+            "seconds" => Ok(Unit::Seconds),
+            other => Err(syn::Error::new(
+                lit.span(),
+                format!(
+                    "unsupported unit `{other}`, expected \"nanoseconds\", \"microseconds\", \
+                     \"milliseconds\" or \"seconds\""
+                ),
+            )),
+        }
+    }
+
+    /// The matching `metrics::Unit`, used when describing the histogram.
+    pub(crate) fn metrics_unit(self) -> TokenStream2 {
+        match self {
+            Unit::Nanoseconds => quote! { metrics::Unit::Nanoseconds },
+            Unit::Microseconds => quote! { metrics::Unit::Microseconds },
+            Unit::Milliseconds => quote! { metrics::Unit::Milliseconds },
+            Unit::Seconds => quote! { metrics::Unit::Seconds },
+        }
+    }
+
+    /// The field the duration is recorded to with `record = "span"`, e.g. `duration_ms`.
+    pub(crate) fn span_field(self) -> &'static str {
+        match self {
+            Unit::Nanoseconds => "duration_ns",
+            Unit::Microseconds => "duration_us",
+            Unit::Milliseconds => "duration_ms",
+            Unit::Seconds => "duration_s",
+        }
+    }
+
+    /// The other unit `dual_unit` records to, for milliseconds and seconds.
+    pub(crate) fn dual(self) -> Option<Unit> {
+        match self {
+            Unit::Milliseconds => Some(Unit::Seconds),
+            Unit::Seconds => Some(Unit::Milliseconds),
+            Unit::Nanoseconds | Unit::Microseconds => None,
+        }
+    }
+
+    pub(crate) fn suffix(self) -> &'static str {
+        match self {
+            Unit::Nanoseconds => "nanoseconds",
+            Unit::Microseconds => "microseconds",
+            Unit::Milliseconds => "milliseconds",
+            Unit::Seconds => "seconds",
+        }
+    }
+
+    /// Converts `duration` (a `std::time::Duration` expression) into an `f64` in this unit.
+    ///
+    /// Nanoseconds are exact. The coarser units keep their fractional part instead of being
+    /// truncated, so calls shorter than one unit don't all record as zero.
+    pub(crate) fn convert(self, duration: &Ident) -> TokenStream2 {
+        match self {
+            Unit::Nanoseconds => quote! { #duration.as_nanos() as f64 },
+            Unit::Microseconds => quote! { #duration.as_nanos() as f64 / 1000.0 },
+            Unit::Milliseconds => quote! { #duration.as_secs_f64() * 1000.0 },
+            Unit::Seconds => quote! { #duration.as_secs_f64() },
+        }
+    }
+}
+
+/// The `metrics` instrument the duration is recorded with, selected by `as = "..."`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Instrument {
+    /// Records every duration, the default.
+    Histogram,
+    /// Adds up the durations, in whole units.
+    Counter,
+    /// Holds the latest duration.
+    Gauge,
+}
+
+impl Instrument {
+    fn from_lit(lit: &LitStr) -> syn::Result<Self> {
+        match lit.value().as_str() {
+            "histogram" => Ok(Instrument::Histogram),
+            "counter" => Ok(Instrument::Counter),
+            "gauge" => Ok(Instrument::Gauge),
+            other => Err(syn::Error::new(
+                lit.span(),
+                format!(
+                    "unsupported instrument `{other}`, expected \"histogram\", \"counter\" or \
+                     \"gauge\""
+                ),
+            )),
+        }
+    }
+
+    /// The name of the instrument, as written in `as = "..."`.
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Instrument::Histogram => "histogram",
+            Instrument::Counter => "counter",
+            Instrument::Gauge => "gauge",
+        }
+    }
+}
+
+/// The clock the generated code reads the start time from.
+///
+/// Every clock's `Instant::elapsed()` returns a `std::time::Duration`, so only the call that
+/// takes the start time differs.
+#[derive(Clone, Copy)]
+pub(crate) enum Clock {
+    Std,
+    Tokio,
+    Quanta,
+}
+
+impl Clock {
+    fn from_lit(lit: &LitStr) -> syn::Result<Self> {
+        let (clock, feature) = match lit.value().as_str() {
+            "std" => return Ok(Clock::Std),
+            "tokio" => (Clock::Tokio, "tokio"),
+            "quanta" => (Clock::Quanta, "quanta"),
+            other => {
+                return Err(syn::Error::new(
+                    lit.span(),
+                    format!(
+                        "unsupported clock `{other}`, expected \"std\", \"tokio\" or \"quanta\""
+                    ),
+                ))
+            }
+        };
+        let enabled = match clock {
+            Clock::Tokio => cfg!(feature = "tokio"),
+            Clock::Quanta => cfg!(feature = "quanta"),
+            Clock::Std => true,
+        };
+        if !enabled {
+            return Err(syn::Error::new(
+                lit.span(),
+                format!("`clock = \"{feature}\"` requires the `{feature}` feature of metrics-utils-macros"),
+            ));
+        }
+        Ok(clock)
+    }
+
+    /// The clock's `Instant` type.
+    pub(crate) fn instant(self) -> TokenStream2 {
+        match self {
+            Clock::Std => quote! { std::time::Instant },
+            Clock::Tokio => quote! { tokio::time::Instant },
+            Clock::Quanta => quote! { quanta::Instant },
+        }
+    }
+
+    /// An expression taking the start time.
+    pub(crate) fn now(self) -> TokenStream2 {
+        let instant = self.instant();
+        quote! { #instant::now() }
+    }
+}
+
+/// How `error_kind` derives its label from the error of an `Err` return.
+#[derive(Clone)]
+pub(crate) enum ErrorKind {
+    /// The error's type name without its module path, e.g. `TimeoutError`.
+    TypeName,
+    /// The error's `Display` output, from `error_kind = display`.
+    Display(Ident),
+}
+
+/// The time zone of the `hour` label of `time_bucket`.
+#[derive(Clone, Copy)]
+pub(crate) enum TimeZone {
+    Utc,
+    /// From `time_bucket = "local"`.
+    Local,
+}
+
+/// A custom name for the function name label.
+#[derive(Clone)]
+pub(crate) enum CustomName {
+    /// A string literal, given first or as `name = "..."`.
+    Literal(LitStr),
+    /// A path to a `&'static str` constant from `name = MY_METRIC`, emitted into the generated
+    /// code and resolved where the function is defined.
+    Path(Path),
+    /// A path to a `const fn() -> &'static str` from `name_fn = metric_name`, called in a
+    /// `const` item of the generated code so the name is still fixed at compile time.
+    ConstFn(Path),
+}
+
+impl CustomName {
+    pub(crate) fn span(&self) -> Span {
+        match self {
+            CustomName::Literal(lit) => lit.span(),
+            CustomName::Path(path) | CustomName::ConstFn(path) => path.span(),
+        }
+    }
+
+    /// The name as written in the attribute, for error messages.
+    pub(crate) fn written(&self) -> String {
+        match self {
+            CustomName::Literal(lit) => lit.to_token_stream().to_string(),
+            CustomName::Path(path) => path.to_token_stream().to_string(),
+            CustomName::ConstFn(path) => format!("{}()", path.to_token_stream()),
+        }
+    }
+}
+
+impl ToTokens for CustomName {
+    fn to_tokens(&self, tokens: &mut TokenStream2) {
+        match self {
+            CustomName::Literal(lit) => lit.to_tokens(tokens),
+            CustomName::Path(path) => path.to_tokens(tokens),
+            // Spanned at the path, so a function that isn't `const` is reported there.
+            CustomName::ConstFn(path) => quote_spanned! {path.span()=>
+                {
+                    const __MEASURED_NAME: &str = #path();
+                    __MEASURED_NAME
+                }
+            }
+            .to_tokens(tokens),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct MacroArgs {
+    pub(crate) custom_name: Option<CustomName>,
+    /// Replaces the duration histogram's name, from `metric = "..."`.
+    pub(crate) metric: Option<LitStr>,
+    /// Further histogram names recording the same duration, from repeated `also_record = "..."`.
+    pub(crate) also_record: Vec<LitStr>,
+    /// The dot-separated namespace from `namespace = "db.queries"` that every metric name of the
+    /// function is placed under.
+    pub(crate) namespace: Option<LitStr>,
+    /// The separator joining the namespace components and the name, from
+    /// `namespace_separator = "."`, `_` by default.
+    pub(crate) namespace_separator: Option<LitStr>,
+    pub(crate) unit: Unit,
+    /// Set by the bare `dual_unit` flag: also record the duration in the other of milliseconds
+    /// and seconds, under the name for that unit.
+    pub(crate) dual_unit: Option<Ident>,
+    /// The instrument from `as = "..."`, a histogram by default.
+    pub(crate) instrument: Instrument,
+    /// The literal of `value_type = "u64"`, which records whole nanoseconds. `None` for
+    /// `value_type = "f64"`, the default.
+    pub(crate) integer: Option<LitStr>,
+    /// A `fn(Duration) -> f64` from `convert = path` that replaces the unit conversion of the
+    /// recorded value.
+    pub(crate) convert: Option<Path>,
+    /// The granularity from `quantize = 0.5` that recorded values are rounded to.
+    pub(crate) quantize: Option<f64>,
+    /// The clock from `clock = "..."`, `std::time::Instant` by default.
+    pub(crate) clock: Clock,
+    /// Set by the bare `result` flag: derive `status` from a returned `Result`.
+    pub(crate) result: Option<Ident>,
+    /// Set by the bare `split_result` flag: record `Ok` and `Err` calls to histograms of their
+    /// own.
+    pub(crate) split_result: Option<Ident>,
+    /// Set by `error_kind` or `error_kind = display`: label `Err` samples with the error's kind.
+    pub(crate) error_kind: Option<(Ident, ErrorKind)>,
+    /// Extra constant labels, from `labels(key = "value", ...)`.
+    pub(crate) labels: Vec<(Ident, LitStr)>,
+    /// Set by the bare `with_pkg_labels` flag: add the crate's `service` and `version` labels.
+    pub(crate) with_pkg_labels: Option<Ident>,
+    /// Set by the bare `with_profile_label` flag: add a `profile` label, `debug` or `release`.
+    pub(crate) with_profile_label: Option<Ident>,
+    /// Set by the bare `task_id_label` flag: label each call with the id of its Tokio task.
+    pub(crate) task_id_label: Option<Ident>,
+    /// The option and feature of `path_label_from_cfg = "..."`, whose state sets a `path` label
+    /// to `on` or `off`.
+    pub(crate) path_label_from_cfg: Option<(Ident, LitStr)>,
+    /// Set by the bare `arity` flag: label every metric with the number of parameters.
+    pub(crate) arity: Option<Ident>,
+    /// Replaces the `"function"` key of the label carrying the function name.
+    pub(crate) label_key: Option<LitStr>,
+    /// Set by the bare `count` flag: also increment `function_calls_total` on every call.
+    pub(crate) count: Option<Ident>,
+    /// Set by the bare `interarrival` flag: also record the time since the previous call.
+    pub(crate) interarrival: Option<Ident>,
+    /// Set by the bare `lifecycle` flag: count started, completed and cancelled calls.
+    pub(crate) lifecycle: Option<Ident>,
+    /// Set by the bare `record_len` flag: also record the length of the returned collection.
+    pub(crate) record_len: Option<Ident>,
+    /// Set by the bare `success_ratio` flag: publish the share of `Ok` returns as a gauge.
+    pub(crate) success_ratio: Option<Ident>,
+    /// Set by `windowed_error_rate = "10s"`: publish the share of `Err` returns over a sliding
+    /// window as a gauge, with the window's length in milliseconds.
+    pub(crate) windowed_error_rate: Option<(LitStr, u64)>,
+    /// A `fn(&T) -> usize` from `record_serialized_len = path`, giving the serialized size of
+    /// the returned value.
+    pub(crate) record_serialized_len: Option<Path>,
+    /// Set by the bare `label_variant` flag: label samples with the returned value's variant.
+    pub(crate) label_variant: Option<Ident>,
+    /// Set by the bare `inflight` flag: track concurrent calls in a `function_inflight` gauge.
+    pub(crate) inflight: Option<Ident>,
+    /// Set by the bare `no_histogram` flag: record none of the duration histograms, only the
+    /// opt-in metrics.
+    pub(crate) no_histogram: Option<Ident>,
+    /// Set by `describe` or `describe = "text"`: register help text and unit for the histogram.
+    pub(crate) describe: Option<(Ident, Option<LitStr>)>,
+    /// Histogram bucket boundaries from `buckets = [0.1, 1.0, ...]`.
+    pub(crate) buckets: Option<Vec<LitFloat>>,
+    /// Set by the bare `location` flag: label samples with the caller's `file:line`.
+    pub(crate) location: Option<Ident>,
+    /// Record the histogram on one call in every `N`, from `sample = N`.
+    pub(crate) sample: Option<LitInt>,
+    /// Leave the first `N` calls out of the histogram, from `warmup = N`.
+    pub(crate) warmup: Option<LitInt>,
+    /// Record every call slower than a threshold and one in every `N` faster calls, from
+    /// `tail_sample(threshold_ms = T, sample = N)`.
+    pub(crate) tail_sample: Option<TailSample>,
+    /// Set by the bare `last_duration` flag: also set a gauge to the latest call's duration.
+    pub(crate) last_duration: Option<Ident>,
+    /// Set by `ewma` or `ewma = alpha`: also publish an exponentially weighted moving average of
+    /// the durations as a gauge, with the smoothing factor.
+    pub(crate) ewma: Option<(Ident, f64)>,
+    /// Set by the bare `timestamps` flag: also set gauges to the latest call's wall-clock start
+    /// and end.
+    pub(crate) timestamps: Option<Ident>,
+    /// Set by the bare `summary` flag: also record durations to a `metrics_utils::summary`.
+    pub(crate) summary: Option<Ident>,
+    /// Set by `ringbuffer = N`: also keep the last `N` durations in memory for inspection.
+    pub(crate) ringbuffer: Option<LitInt>,
+    /// Set by the bare `cpu_time` flag: also record the thread's CPU time across the body.
+    pub(crate) cpu_time: Option<Ident>,
+    /// Set by the bare `utilization` flag: also set a gauge to the ratio of the thread's CPU
+    /// time to the wall time of each call.
+    pub(crate) utilization: Option<Ident>,
+    /// Set by the bare `alloc_count` flag: also count the allocations made during the body.
+    pub(crate) alloc_count: Option<Ident>,
+    /// Set by the bare `strict` flag: check the metric names and label keys against the naming
+    /// rules of `strict`. The `strict` feature checks every function instead.
+    pub(crate) strict: Option<Ident>,
+    /// Set by the bare `busy` flag: also record the time spent inside `poll` for async functions.
+    pub(crate) busy: Option<Ident>,
+    /// Set by the bare `poll_count` flag: also record how many times the future was polled.
+    pub(crate) poll_count: Option<Ident>,
+    /// Set by the bare `sync_prefix` flag: also record the time until the future's first `poll`
+    /// returns, i.e. the synchronous work before the first suspension.
+    pub(crate) sync_prefix: Option<Ident>,
+    /// Set by the bare `monomorphized` flag: label samples with a type parameter's name.
+    pub(crate) monomorphized: Option<Ident>,
+    /// The type parameter `monomorphized` reports, from `type_param = "T"`.
+    pub(crate) type_param: Option<LitStr>,
+    /// A `DurationSink` value from `sink = path` that receives the duration histograms.
+    pub(crate) sink: Option<Path>,
+    /// A pre-resolved `HistogramHandle` from `handle = path` that receives the duration
+    /// samples instead of `metrics::histogram!`.
+    pub(crate) handle: Option<Path>,
+    /// A `fn() -> Option<String>` from `exemplar_from = path`, providing the trace id attached to
+    /// each duration sample.
+    pub(crate) exemplar_from: Option<Path>,
+    /// A `fn(&str, f64)` from `on_record = path`, called with the label and each recorded
+    /// duration.
+    pub(crate) on_record: Option<Path>,
+    /// The variable the recorded duration is bound to for the `after` block, from
+    /// `expose_as = ident`.
+    pub(crate) expose_as: Option<Ident>,
+    /// A block from `after = { ... }` run after each recording, with the duration bound to the
+    /// `expose_as` variable.
+    pub(crate) after: Option<(Ident, Block)>,
+    /// Calls slower than this many milliseconds are counted, and logged with the `tracing`
+    /// feature, from `slow_threshold_ms = N`.
+    pub(crate) slow_threshold_ms: Option<LitInt>,
+    /// Calls slower than this many milliseconds panic in tests, from `max_duration_ms = N`.
+    pub(crate) max_duration_ms: Option<LitInt>,
+    /// Calls faster than this many milliseconds are not recorded, from `min_duration_ms = N`.
+    pub(crate) min_duration_ms: Option<LitInt>,
+    /// The parameter whose runtime value replaces the function name label, from
+    /// `label_from = param`.
+    pub(crate) label_from: Option<Ident>,
+    /// The parameter whose runtime value goes into a `correlation` label next to the function
+    /// name, from `correlation_from = param`.
+    pub(crate) correlation_from: Option<Ident>,
+    /// Set when the `label_from` parameter was given as `label_from_present = param`: label
+    /// whether the `Option` holds a value rather than the value itself.
+    pub(crate) label_from_present: Option<Ident>,
+    /// A `fn() -> Option<String>` from `label_from_context = path`, whose value labels the call
+    /// when present, with the option's key.
+    pub(crate) label_from_context: Option<(Ident, Path)>,
+    /// A function from `labels_from = path` returning further labels for each call, called on
+    /// entry.
+    pub(crate) labels_from: Option<Path>,
+    /// The number of distinct values each runtime label keeps before further values are recorded
+    /// as `__overflow__`, from `max_cardinality = N`.
+    pub(crate) max_cardinality: Option<LitInt>,
+    /// The key of the `label_from_context` label, from `context_key = "..."`.
+    pub(crate) context_key: Option<LitStr>,
+    /// The `tracing::Level` constant from `log_level = "info"`, e.g. `INFO`, at which every call
+    /// is logged.
+    pub(crate) log_level: Option<Ident>,
+    /// Set by the bare `span` flag: run the call inside a `tracing` span.
+    pub(crate) span: Option<Ident>,
+    /// The literal of `record = "span"`, which records the duration as a field of a `tracing`
+    /// span instead of a histogram. `None` for `record = "metrics"`, the default.
+    pub(crate) record_span: Option<LitStr>,
+    /// Set by the bare `strip_const` flag: instrument a `const fn` as a non-`const` function.
+    pub(crate) strip_const: Option<Ident>,
+    /// Set by the bare `named_inner` flag: move the body into a nested
+    /// `__measured_inner_<name>` function so it shows up under its own symbol in backtraces.
+    pub(crate) named_inner: Option<Ident>,
+    /// A `fn() -> bool` from `enabled_if = path`, checked once to decide whether calls are
+    /// instrumented.
+    pub(crate) enabled_if: Option<Path>,
+    /// The `bool` parameter from `skip_if = param`; calls passing `true` aren't instrumented.
+    pub(crate) skip_if: Option<Ident>,
+    /// Set by the bare `top_level_only` flag: instrument only the outermost of nested calls.
+    pub(crate) top_level_only: Option<Ident>,
+    /// Set by the bare `depth_label` flag: label samples with the call's recursion depth.
+    pub(crate) depth_label: Option<Ident>,
+    /// Set by `time_bucket` or `time_bucket = "utc" | "local"`: label samples with the hour of
+    /// day they are recorded in, in that time zone.
+    pub(crate) time_bucket: Option<(Ident, TimeZone)>,
+    /// Set by the bare `skip_in_test` flag: don't instrument calls in builds with `cfg(test)`.
+    pub(crate) skip_in_test: Option<Ident>,
+    /// The type prefixed to the default label as `Type::method`, from `type_name = "Type"`.
+    pub(crate) type_name: Option<LitStr>,
+    /// Set by the bare `qualified` flag: prefix the default label with `module_path!()`.
+    pub(crate) qualified: Option<Ident>,
+    /// Set by `#[measured_try_function]`: record only `Ok` returns and count `Err`s. Not parsed.
+    pub(crate) success_only: bool,
+    /// The label used instead of the function name when no custom name is given. Not parsed;
+    /// set by `#[measured_impl]` to `Type::method` and by `#[measured_mod]` to `module::function`.
+    pub(crate) default_name: Option<String>,
+}
+
+/// Label keys the generated code sets itself and that other labels may not override.
+pub(crate) const RESERVED_LABELS: &[&str] = &["status", "completion", "location", "error_kind"];
+
+impl MacroArgs {
+    /// The key of the label carrying the function name, `"function"` unless overridden.
+    pub(crate) fn label_key(&self) -> String {
+        self.label_key
+            .as_ref()
+            .map_or_else(|| "function".to_owned(), LitStr::value)
+    }
+
+    /// The key of the `label_from_context` label, `"context"` unless overridden.
+    pub(crate) fn context_key(&self) -> String {
+        self.context_key
+            .as_ref()
+            .map_or_else(|| "context".to_owned(), LitStr::value)
+    }
+
+    /// The full name of the metric `name`: placed under the `namespace`, joined with its
+    /// separator, then prefixed with `METRICS_MACROS_PREFIX`.
+    pub(crate) fn metric_name(&self, name: &str) -> String {
+        let name = match &self.namespace {
+            Some(namespace) => {
+                let separator = self
+                    .namespace_separator
+                    .as_ref()
+                    .map_or_else(|| "_".to_owned(), LitStr::value);
+                let namespace = namespace.value().replace('.', &separator);
+                format!("{namespace}{separator}{name}")
+            }
+            None => name.to_owned(),
+        };
+        prefix::metric_name(&name)
+    }
+
+    /// The recorded value for `duration`: the whole nanoseconds with `value_type = "u64"`,
+    /// which `f64` holds exactly up to about 104 days; otherwise the `convert` function's
+    /// result if one was given, the duration in `unit` otherwise, rounded to the nearest
+    /// multiple of `quantize` if set.
+    pub(crate) fn duration_value(&self, duration: &Ident) -> TokenStream2 {
+        if self.integer.is_some() {
+            let nanos = self.integer_value(duration);
+            return quote! { (#nanos) as f64 };
+        }
+        let value = match &self.convert {
+            Some(convert) => quote! { #convert(#duration) },
+            None => self.unit.convert(duration),
+        };
+        match self.quantize {
+            Some(step) => quote! { ((#value) / #step).round() * #step },
+            None => value,
+        }
+    }
+
+    /// The whole nanoseconds of `duration` as a `u64`, saturating after about 584 years.
+    pub(crate) fn integer_value(&self, duration: &Ident) -> TokenStream2 {
+        quote! { u64::try_from(#duration.as_nanos()).unwrap_or(u64::MAX) }
+    }
+
+    /// Applies and removes the `#[metric(...)]` helper attributes among `attrs`.
+    ///
+    /// The helper carries `name = "..."` and `metric = "..."` next to the function's other
+    /// attributes. It is consumed here, so it never reaches the compiler, which requires it to
+    /// come after the instrumenting attribute.
+    pub(crate) fn take_marker(&mut self, attrs: &mut Vec<Attribute>) -> syn::Result<()> {
+        let mut index = 0;
+        while index < attrs.len() {
+            if attrs[index].path().is_ident("metric") {
+                attrs
+                    .remove(index)
+                    .parse_args_with(|input: ParseStream| self.parse_marker(input))?;
+            } else {
+                index += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Parses the contents of a `#[metric(...)]` helper attribute.
+    fn parse_marker(&mut self, input: ParseStream) -> syn::Result<()> {
+        if input.is_empty() {
+            return Err(input.error("expected `name = \"...\"` or `metric = \"...\"`"));
+        }
+        while !input.is_empty() {
+            let key: Ident = input.parse()?;
+            match key.to_string().as_str() {
+                "name" => {}
+                "metric" => {
+                    if let Some(existing) = &self.metric {
+                        return Err(syn::Error::new(
+                            key.span(),
+                            format!("the metric is already set to {:?}", existing.value()),
+                        ));
+                    }
+                }
+                _ => {
+                    return Err(syn::Error::new(
+                        key.span(),
+                        format!(
+                            "unknown key `{key}` in `#[metric(...)]`, expected `name` or `metric`"
+                        ),
+                    ))
+                }
+            }
+            self.parse_option(key, input)?;
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Parses the value of the option `key`, whose name was already consumed.
+    fn parse_option(&mut self, key: Ident, input: ParseStream) -> syn::Result<()> {
+        match key.to_string().as_str() {
+            "name" | "name_fn" => {
+                input.parse::<Token![=]>()?;
+                if let Some(existing) = &self.custom_name {
+                    return Err(syn::Error::new(
+                        key.span(),
+                        format!(
+                            "only one custom name can be given, and it is already `{}`",
+                            existing.written()
+                        ),
+                    ));
+                }
+                self.custom_name = Some(if key == "name_fn" {
+                    if input.peek(LitStr) {
+                        return Err(input.error(
+                            "`name_fn` takes a path to a `const fn() -> &'static str`, a literal \
+                             name goes in `name = \"...\"`",
+                        ));
+                    }
+                    CustomName::ConstFn(input.parse()?)
+                } else if input.peek(LitStr) {
+                    CustomName::Literal(check_custom_name(input.parse()?)?)
+                } else if input.peek(syn::Lit) {
+                    return Err(input.error(
+                        "`name` takes a string literal or a path to a `&'static str` constant",
+                    ));
+                } else {
+                    CustomName::Path(input.parse()?)
+                });
+            }
+            "metric" => {
+                input.parse::<Token![=]>()?;
+                self.metric = Some(parse_metric(input)?);
+            }
+            "namespace" => {
+                input.parse::<Token![=]>()?;
+                self.namespace = Some(parse_namespace(input)?);
+            }
+            "namespace_separator" => {
+                input.parse::<Token![=]>()?;
+                let separator: LitStr = input.parse()?;
+                if !matches!(separator.value().as_str(), "_" | ".") {
+                    return Err(syn::Error::new(
+                        separator.span(),
+                        "`namespace_separator` must be `\"_\"` or `\".\"`",
+                    ));
+                }
+                self.namespace_separator = Some(separator);
+            }
+            "also_record" => {
+                input.parse::<Token![=]>()?;
+                let name = parse_metric(input)?;
+                if self
+                    .also_record
+                    .iter()
+                    .any(|other| other.value() == name.value())
+                {
+                    return Err(syn::Error::new(
+                        name.span(),
+                        format!("`{}` is already recorded to", name.value()),
+                    ));
+                }
+                self.also_record.push(name);
+            }
+            // `expected = "..."` names the magnitude calls are expected to take, which picks
+            // the unit the same way `unit = "..."` does.
+            "unit" | "expected" => {
+                input.parse::<Token![=]>()?;
+                self.unit = Unit::from_lit(&input.parse()?)?;
+            }
+            "as" => {
+                input.parse::<Token![=]>()?;
+                self.instrument = Instrument::from_lit(&input.parse()?)?;
+            }
+            "clock" => {
+                input.parse::<Token![=]>()?;
+                self.clock = Clock::from_lit(&input.parse()?)?;
+            }
+            "result" => self.result = Some(key),
+            "split_result" => self.split_result = Some(key),
+            "error_kind" => {
+                let kind = if input.peek(Token![=]) {
+                    input.parse::<Token![=]>()?;
+                    parse_error_kind(input)?
+                } else {
+                    ErrorKind::TypeName
+                };
+                self.error_kind = Some((key, kind));
+            }
+            "count" => self.count = Some(key),
+            "interarrival" => self.interarrival = Some(key),
+            "lifecycle" => self.lifecycle = Some(key),
+            "inflight" => self.inflight = Some(key),
+            "no_histogram" => self.no_histogram = Some(key),
+            "record_len" => self.record_len = Some(key),
+            "success_ratio" => self.success_ratio = Some(key),
+            "windowed_error_rate" => {
+                input.parse::<Token![=]>()?;
+                let window: LitStr = input.parse()?;
+                let millis = parse_window(&window)?;
+                self.windowed_error_rate = Some((window, millis));
+            }
+            "record_serialized_len" => {
+                input.parse::<Token![=]>()?;
+                self.record_serialized_len = Some(input.parse()?);
+            }
+            "label_variant" => self.label_variant = Some(key),
+            "strict" => self.strict = Some(key),
+            "location" => self.location = Some(key),
+            "qualified" => self.qualified = Some(key),
+            "type_name" => {
+                input.parse::<Token![=]>()?;
+                self.type_name = Some(input.parse()?);
+            }
+            "strip_const" => self.strip_const = Some(key),
+            "named_inner" => self.named_inner = Some(key),
+            "enabled_if" => {
+                input.parse::<Token![=]>()?;
+                self.enabled_if = Some(input.parse()?);
+            }
+            "top_level_only" => self.top_level_only = Some(key),
+            "skip_in_test" => self.skip_in_test = Some(key),
+            "depth_label" => self.depth_label = Some(key),
+            "time_bucket" => {
+                let zone = if input.peek(Token![=]) {
+                    input.parse::<Token![=]>()?;
+                    parse_time_zone(input.parse()?)?
+                } else {
+                    TimeZone::Utc
+                };
+                self.time_bucket = Some((key, zone));
+            }
+            "skip_if" => {
+                input.parse::<Token![=]>()?;
+                self.skip_if = Some(input.parse()?);
+            }
+            "busy" => self.busy = Some(key),
+            "poll_count" => self.poll_count = Some(key),
+            "sync_prefix" => self.sync_prefix = Some(key),
+            "dual_unit" => self.dual_unit = Some(key),
+            "last_duration" => self.last_duration = Some(key),
+            "timestamps" => self.timestamps = Some(key),
+            "summary" => self.summary = Some(key),
+            "cpu_time" => {
+                if !cfg!(feature = "cpu_time") {
+                    return Err(syn::Error::new(
+                        key.span(),
+                        "`cpu_time` reads the thread's CPU clock and requires the `cpu_time` \
+                         feature of metrics-utils",
+                    ));
+                }
+                self.cpu_time = Some(key);
+            }
+            "utilization" => {
+                if !cfg!(feature = "cpu_time") {
+                    return Err(syn::Error::new(
+                        key.span(),
+                        "`utilization` reads the thread's CPU clock and requires the `cpu_time` \
+                         feature of metrics-utils",
+                    ));
+                }
+                self.utilization = Some(key);
+            }
+            "alloc_count" => {
+                if !cfg!(feature = "alloc_count") {
+                    return Err(syn::Error::new(
+                        key.span(),
+                        "`alloc_count` counts allocations through \
+                         `metrics_utils::alloc::CountingAllocator` and requires the \
+                         `alloc_count` feature of metrics-utils",
+                    ));
+                }
+                self.alloc_count = Some(key);
+            }
+            "span" => {
+                if !cfg!(feature = "tracing") {
+                    return Err(syn::Error::new(
+                        key.span(),
+                        "`span` creates a `tracing` span and requires the `tracing` feature of \
+                         metrics-utils-macros",
+                    ));
+                }
+                self.span = Some(key);
+            }
+            "log_level" => {
+                input.parse::<Token![=]>()?;
+                self.log_level = Some(parse_log_level(input.parse()?)?);
+            }
+            "record" => {
+                input.parse::<Token![=]>()?;
+                self.record_span = parse_record(input.parse()?)?;
+            }
+            "max_duration_ms" => {
+                input.parse::<Token![=]>()?;
+                self.max_duration_ms = Some(parse_threshold(input)?);
+            }
+            "min_duration_ms" => {
+                input.parse::<Token![=]>()?;
+                self.min_duration_ms = Some(parse_threshold(input)?);
+            }
+            "slow_threshold_ms" => {
+                input.parse::<Token![=]>()?;
+                self.slow_threshold_ms = Some(parse_threshold(input)?);
+            }
+            "sink" => {
+                input.parse::<Token![=]>()?;
+                self.sink = Some(input.parse()?);
+            }
+            "handle" => {
+                input.parse::<Token![=]>()?;
+                self.handle = Some(input.parse()?);
+            }
+            "value_type" => {
+                input.parse::<Token![=]>()?;
+                self.integer = parse_value_type(input.parse()?)?;
+            }
+            "convert" => {
+                input.parse::<Token![=]>()?;
+                self.convert = Some(input.parse()?);
+            }
+            "quantize" => {
+                input.parse::<Token![=]>()?;
+                self.quantize = Some(parse_quantize(input)?);
+            }
+            "on_record" => {
+                input.parse::<Token![=]>()?;
+                self.on_record = Some(input.parse()?);
+            }
+            "expose_as" => {
+                input.parse::<Token![=]>()?;
+                self.expose_as = Some(input.parse()?);
+            }
+            "after" => {
+                input.parse::<Token![=]>()?;
+                self.after = Some((key, input.parse()?));
+            }
+            "exemplar_from" => {
+                input.parse::<Token![=]>()?;
+                self.exemplar_from = Some(input.parse()?);
+            }
+            "monomorphized" => self.monomorphized = Some(key),
+            "type_param" => {
+                input.parse::<Token![=]>()?;
+                self.type_param = Some(input.parse()?);
+            }
+            "sample" => {
+                input.parse::<Token![=]>()?;
+                self.sample = Some(parse_sample(input)?);
+            }
+            "tail_sample" => self.tail_sample = Some(parse_tail_sample(key, input)?),
+            "max_cardinality" => {
+                input.parse::<Token![=]>()?;
+                let max: LitInt = input.parse()?;
+                if max.base10_parse::<usize>()? == 0 {
+                    return Err(syn::Error::new(
+                        max.span(),
+                        "`max_cardinality` must keep at least one value",
+                    ));
+                }
+                self.max_cardinality = Some(max);
+            }
+            "ringbuffer" => {
+                input.parse::<Token![=]>()?;
+                let capacity: LitInt = input.parse()?;
+                if capacity.base10_parse::<usize>()? == 0 {
+                    return Err(syn::Error::new(
+                        capacity.span(),
+                        "`ringbuffer` must keep at least one duration",
+                    ));
+                }
+                self.ringbuffer = Some(capacity);
+            }
+            "warmup" => {
+                input.parse::<Token![=]>()?;
+                let calls: LitInt = input.parse()?;
+                calls.base10_parse::<u64>()?;
+                self.warmup = Some(calls);
+            }
+            "buckets" => {
+                input.parse::<Token![=]>()?;
+                self.buckets = Some(parse_buckets(input)?);
+            }
+            "ewma" => {
+                let alpha = if input.peek(Token![=]) {
+                    input.parse::<Token![=]>()?;
+                    parse_alpha(input)?
+                } else {
+                    DEFAULT_EWMA_ALPHA
+                };
+                self.ewma = Some((key, alpha));
+            }
+            "describe" => {
+                let text = if input.peek(Token![=]) {
+                    input.parse::<Token![=]>()?;
+                    Some(input.parse()?)
+                } else {
+                    None
+                };
+                self.describe = Some((key, text));
+            }
+            "label_key" => {
+                input.parse::<Token![=]>()?;
+                self.label_key = Some(check_label_key(input.parse()?)?);
+            }
+            "labels" => {
+                for (key, value) in parse_labels(input)? {
+                    self.add_label(key, value)?;
+                }
+            }
+            "with_pkg_labels" => self.with_pkg_labels = Some(key),
+            "with_profile_label" => self.with_profile_label = Some(key),
+            "task_id_label" => {
+                if !cfg!(feature = "tokio") {
+                    return Err(syn::Error::new(
+                        key.span(),
+                        "`task_id_label` reads `tokio::task::try_id` and requires the `tokio` \
+                         feature of metrics-utils-macros",
+                    ));
+                }
+                self.task_id_label = Some(key);
+            }
+            "path_label_from_cfg" => {
+                input.parse::<Token![=]>()?;
+                self.path_label_from_cfg = Some((key, input.parse()?));
+            }
+            "arity" => self.arity = Some(key),
+            "label_from" | "label_from_present" => {
+                input.parse::<Token![=]>()?;
+                if self.label_from.is_some() {
+                    return Err(syn::Error::new(
+                        key.span(),
+                        "`label_from` and `label_from_present` both pick the parameter providing \
+                         the label, give only one",
+                    ));
+                }
+                self.label_from = Some(input.parse()?);
+                if key == "label_from_present" {
+                    self.label_from_present = Some(key);
+                }
+            }
+            "correlation_from" => {
+                input.parse::<Token![=]>()?;
+                self.correlation_from = Some(input.parse()?);
+            }
+            "label_from_context" => {
+                input.parse::<Token![=]>()?;
+                self.label_from_context = Some((key, input.parse()?));
+            }
+            "labels_from" => {
+                input.parse::<Token![=]>()?;
+                self.labels_from = Some(input.parse()?);
+            }
+            "context_key" => {
+                input.parse::<Token![=]>()?;
+                self.context_key = Some(check_label_key(input.parse()?)?);
+            }
+            // Any other `key = "value"` is a static label, unless the key is a near miss of an
+            // option, which is far more likely a typo than a label.
+            _ if input.peek(Token![=]) && closest_key(&key.to_string()).is_none() => {
+                input.parse::<Token![=]>()?;
+                let (key, value) = parse_label(key, input)?;
+                self.add_label(key, value)?;
+            }
+            _ => return Err(unknown_key(&key, input)),
+        }
+        Ok(())
+    }
+
+    /// Adds a static label, given either bare or in `labels(...)`.
+    fn add_label(&mut self, key: Ident, value: LitStr) -> syn::Result<()> {
+        if self.labels.iter().any(|(existing, _)| *existing == key) {
+            return Err(syn::Error::new(
+                key.span(),
+                format!("the `{key}` label is given more than once"),
+            ));
+        }
+        self.labels.push((key, value));
+        Ok(())
+    }
+}
+
+/// Every option key, in the order they are listed when an unknown key is rejected.
+const KEYS: &[&str] = &[
+    "name",
+    "name_fn",
+    "metric",
+    "also_record",
+    "namespace",
+    "namespace_separator",
+    "unit",
+    "dual_unit",
+    "expected",
+    "as",
+    "value_type",
+    "convert",
+    "quantize",
+    "clock",
+    "result",
+    "split_result",
+    "error_kind",
+    "count",
+    "interarrival",
+    "lifecycle",
+    "inflight",
+    "no_histogram",
+    "record_len",
+    "success_ratio",
+    "windowed_error_rate",
+    "record_serialized_len",
+    "label_variant",
+    "location",
+    "qualified",
+    "type_name",
+    "strip_const",
+    "named_inner",
+    "enabled_if",
+    "skip_if",
+    "top_level_only",
+    "skip_in_test",
+    "depth_label",
+    "time_bucket",
+    "busy",
+    "poll_count",
+    "sync_prefix",
+    "last_duration",
+    "ewma",
+    "timestamps",
+    "summary",
+    "ringbuffer",
+    "cpu_time",
+    "utilization",
+    "alloc_count",
+    "strict",
+    "span",
+    "record",
+    "log_level",
+    "monomorphized",
+    "type_param",
+    "sink",
+    "handle",
+    "exemplar_from",
+    "on_record",
+    "expose_as",
+    "after",
+    "slow_threshold_ms",
+    "max_duration_ms",
+    "min_duration_ms",
+    "sample",
+    "tail_sample",
+    "warmup",
+    "describe",
+    "buckets",
+    "label_key",
+    "label_from",
+    "correlation_from",
+    "label_from_present",
+    "label_from_context",
+    "labels_from",
+    "context_key",
+    "max_cardinality",
+    "labels",
+    "with_pkg_labels",
+    "with_profile_label",
+    "task_id_label",
+    "path_label_from_cfg",
+    "arity",
+];
+
+impl Parse for MacroArgs {
+    /// Parses an optional leading string literal (the custom name) followed by
+    /// comma-separated options, each either a bare flag or `key = value`. Every option but
+    /// `also_record` may be given at most once, and a trailing comma is accepted, as in the
+    /// lists inside `labels(...)` and `buckets = [...]`.
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut args = MacroArgs {
+            custom_name: None,
+            metric: None,
+            also_record: Vec::new(),
+            namespace: None,
+            namespace_separator: None,
+            unit: defaults::unit()?,
+            dual_unit: None,
+            instrument: Instrument::Histogram,
+            integer: None,
+            convert: None,
+            quantize: None,
+            clock: Clock::Std,
+            result: None,
+            split_result: None,
+            error_kind: None,
+            labels: Vec::new(),
+            with_pkg_labels: None,
+            with_profile_label: None,
+            task_id_label: None,
+            path_label_from_cfg: None,
+            arity: None,
+            label_key: defaults::label_key()?,
+            count: None,
+            interarrival: None,
+            lifecycle: None,
+            inflight: None,
+            no_histogram: None,
+            record_len: None,
+            success_ratio: None,
+            windowed_error_rate: None,
+            record_serialized_len: None,
+            label_variant: None,
+            describe: None,
+            buckets: None,
+            location: None,
+            qualified: None,
+            type_name: None,
+            strip_const: None,
+            named_inner: None,
+            enabled_if: None,
+            skip_if: None,
+            top_level_only: None,
+            skip_in_test: None,
+            depth_label: None,
+            time_bucket: None,
+            span: None,
+            record_span: None,
+            log_level: None,
+            label_from: None,
+            correlation_from: None,
+            label_from_present: None,
+            label_from_context: None,
+            labels_from: None,
+            context_key: None,
+            max_cardinality: None,
+            slow_threshold_ms: None,
+            max_duration_ms: None,
+            min_duration_ms: None,
+            sink: None,
+            handle: None,
+            exemplar_from: None,
+            on_record: None,
+            expose_as: None,
+            after: None,
+            monomorphized: None,
+            type_param: None,
+            busy: None,
+            poll_count: None,
+            sync_prefix: None,
+            summary: None,
+            ringbuffer: None,
+            cpu_time: None,
+            utilization: None,
+            alloc_count: None,
+            strict: None,
+            last_duration: None,
+            ewma: None,
+            timestamps: None,
+            sample: None,
+            warmup: None,
+            tail_sample: None,
+            success_only: false,
+            default_name: None,
+        };
+        let mut seen: Vec<Ident> = Vec::new();
+
+        while !input.is_empty() {
+            if input.peek(Lit) {
+                match input.parse()? {
+                    Lit::Str(name) if seen.is_empty() && args.custom_name.is_none() => {
+                        args.custom_name = Some(CustomName::Literal(check_custom_name(name)?));
+                    }
+                    lit => return Err(misplaced_literal(lit, args.custom_name.as_ref())),
+                }
+            } else {
+                // `as` is a keyword, so keys are parsed as any identifier.
+                let key = input.call(Ident::parse_any)?;
+                if seen.contains(&key) && key != "also_record" {
+                    return Err(syn::Error::new(
+                        key.span(),
+                        format!("`{key}` is given more than once"),
+                    ));
+                }
+                // `unit` and `expected` are two spellings of the same option.
+                let sets_unit = |key: &Ident| key == "unit" || key == "expected";
+                if let Some(other) = seen
+                    .iter()
+                    .find(|other| sets_unit(&key) && sets_unit(other))
+                {
+                    return Err(syn::Error::new(
+                        key.span(),
+                        format!("`{key}` and `{other}` both set the unit, give only one"),
+                    ));
+                }
+                seen.push(key.clone());
+                args.parse_option(key, input)?;
+            }
+
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+
+        // Whole nanoseconds are recorded to the nanoseconds histogram, whatever the default
+        // unit, so only an explicit coarser unit contradicts `value_type = "u64"`.
+        if let Some(lit) = &args.integer {
+            let unit = seen.iter().find(|key| *key == "unit" || *key == "expected");
+            if let (Some(key), false) = (unit, matches!(args.unit, Unit::Nanoseconds)) {
+                return Err(syn::Error::new(
+                    key.span(),
+                    format!(
+                        "`value_type = {}` records whole nanoseconds, so `{key}` must be \
+                         \"nanoseconds\"",
+                        lit.token()
+                    ),
+                ));
+            }
+            args.unit = Unit::Nanoseconds;
+        }
+
+        Ok(args)
+    }
+}
+
+/// The error for an unrecognised option. A near miss of an option is pointed at that option
+/// alone; otherwise every option is listed, with a hint for a custom name.
+fn unknown_key(key: &Ident, input: ParseStream) -> syn::Error {
+    let labelled = input.peek(Token![=]) && input.peek2(LitStr);
+    let message = if let Some(known) = closest_key(&key.to_string()) {
+        if labelled {
+            format!(
+                "unknown argument `{key}`, did you mean `{known}`? A label with this key goes in \
+                 `labels({key} = ...)`"
+            )
+        } else {
+            format!("unknown argument `{key}`, did you mean `{known}`?")
+        }
+    } else {
+        let expected = KEYS
+            .iter()
+            .map(|key| format!("`{key}`"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!(
+            "unknown argument `{key}`, expected one of {expected}; to use `{key}` as the name, \
+             write it as a string literal: `\"{key}\"`"
+        )
+    };
+    syn::Error::new(key.span(), message)
+}
+
+/// The option `key` is most likely a typo of: the closest of `KEYS` by edit distance, if it is
+/// within one edit for every four characters, so short keys only match a single typo.
+fn closest_key(key: &str) -> Option<&'static str> {
+    let allowed = key.chars().count().div_ceil(4);
+    KEYS.iter()
+        .map(|known| (edit_distance(key, known), *known))
+        .filter(|(distance, _)| *distance <= allowed)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, known)| known)
+}
+
+/// The Levenshtein distance between `a` and `b`: the fewest single-character insertions,
+/// deletions and substitutions turning one into the other.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Checks the leading custom name, which must not be empty.
+fn check_custom_name(name: LitStr) -> syn::Result<LitStr> {
+    if name.value().is_empty() {
+        return Err(syn::Error::new(
+            name.span(),
+            "the custom name must not be empty, remove it to use the function name",
+        ));
+    }
+    Ok(name)
+}
+
+/// The error for a literal where an argument name was expected: a custom name that isn't a
+/// string, a second custom name, or a custom name that doesn't come first.
+fn misplaced_literal(lit: Lit, name: Option<&CustomName>) -> syn::Error {
+    let message = match (&lit, name) {
+        (Lit::Str(_), Some(name)) => format!(
+            "only one custom name can be given, and it is already `{}`",
+            name.written()
+        ),
+        (Lit::Str(_), None) => "the custom name must be the first argument".to_owned(),
+        _ => {
+            let tokens = quote!(#lit).to_string();
+            format!("the custom name must be a string literal, e.g. `\"{tokens}\"`")
+        }
+    };
+    syn::Error::new(lit.span(), message)
+}
+
+/// Parses a `metric` value, which must be a valid Prometheus-style metric name.
+fn parse_metric(input: ParseStream) -> syn::Result<LitStr> {
+    let lit: LitStr = input.parse()?;
+    let name = lit.value();
+    let mut chars = name.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_' || first == ':')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':');
+    if !valid {
+        return Err(syn::Error::new(
+            lit.span(),
+            format!(
+                "`{name}` is not a valid metric name, use ASCII letters, digits, `_` and `:` \
+                 without a leading digit"
+            ),
+        ));
+    }
+    Ok(lit)
+}
+
+/// Parses a `namespace`, dot-separated components that are each valid in a metric name.
+fn parse_namespace(input: ParseStream) -> syn::Result<LitStr> {
+    let lit: LitStr = input.parse()?;
+    let namespace = lit.value();
+    let valid = namespace.split('.').all(|component| {
+        let mut chars = component.chars();
+        chars
+            .next()
+            .is_some_and(|first| first.is_ascii_alphabetic() || first == '_' || first == ':')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':')
+    });
+    if !valid {
+        return Err(syn::Error::new(
+            lit.span(),
+            format!(
+                "`{namespace}` is not a valid namespace, use components of ASCII letters, \
+                 digits, `_` and `:` separated by `.`, e.g. `\"db.queries\"`"
+            ),
+        ));
+    }
+    Ok(lit)
+}
+
+/// Checks a `label_key` value, which must be a valid Prometheus-style label name.
+pub(crate) fn check_label_key(lit: LitStr) -> syn::Result<LitStr> {
+    let key = lit.value();
+    if key.is_empty() {
+        return Err(syn::Error::new(lit.span(), "`label_key` must not be empty"));
+    }
+    let mut chars = key.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        return Err(syn::Error::new(
+            lit.span(),
+            format!(
+                "`{key}` is not a valid label key, use ASCII letters, digits and `_` \
+                 without a leading digit"
+            ),
+        ));
+    }
+    if RESERVED_LABELS.contains(&key.as_str()) {
+        return Err(syn::Error::new(
+            lit.span(),
+            format!("the `{key}` label is set by the macro and cannot be used as `label_key`"),
+        ));
+    }
+    Ok(lit)
+}
+
+/// Parses the `N` of `sample = N`, a positive integer literal.
+fn parse_sample(input: ParseStream) -> syn::Result<LitInt> {
+    let lit: LitInt = input.parse()?;
+    if lit.base10_parse::<u64>()? == 0 {
+        return Err(syn::Error::new(
+            lit.span(),
+            "`sample` must be at least 1, e.g. `sample = 100` records one call in every 100",
+        ));
+    }
+    Ok(lit)
+}
+
+/// Parses the level of `log_level = "..."` into the matching `tracing::Level` constant.
+fn parse_log_level(lit: LitStr) -> syn::Result<Ident> {
+    if !cfg!(feature = "tracing") {
+        return Err(syn::Error::new(
+            lit.span(),
+            "`log_level` logs with `tracing` and requires the `tracing` feature of \
+             metrics-utils-macros",
+        ));
+    }
+    match lit.value().as_str() {
+        level @ ("trace" | "debug" | "info" | "warn" | "error") => {
+            Ok(Ident::new(&level.to_uppercase(), lit.span()))
+        }
+        other => Err(syn::Error::new(
+            lit.span(),
+            format!(
+                "unsupported log level `{other}`, expected \"trace\", \"debug\", \"info\", \
+                 \"warn\" or \"error\""
+            ),
+        )),
+    }
+}
+
+/// Parses the destination of `record = "..."`, keeping the literal of `"span"`.
+fn parse_record(lit: LitStr) -> syn::Result<Option<LitStr>> {
+    match lit.value().as_str() {
+        "metrics" => Ok(None),
+        "span" if !cfg!(feature = "tracing") => Err(syn::Error::new(
+            lit.span(),
+            "`record = \"span\"` records to a `tracing` span and requires the `tracing` \
+             feature of metrics-utils-macros",
+        )),
+        "span" => Ok(Some(lit)),
+        other => Err(syn::Error::new(
+            lit.span(),
+            format!("unsupported destination `{other}`, expected \"metrics\" or \"span\""),
+        )),
+    }
+}
+
+/// Parses the window of `windowed_error_rate = "..."`, a positive whole number of `ms`, `s`,
+/// `m` or `h`, into milliseconds.
+fn parse_window(lit: &LitStr) -> syn::Result<u64> {
+    let value = lit.value();
+    let invalid = || {
+        syn::Error::new(
+            lit.span(),
+            format!(
+                "unsupported window `{value}`, expected a positive number of \"ms\", \"s\", \
+                 \"m\" or \"h\", e.g. \"10s\""
+            ),
+        )
+    };
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(invalid)?;
+    let (number, unit) = value.split_at(split);
+    let factor = match unit {
+        "ms" => 1,
+        "s" => 1_000,
+        "m" => 60_000,
+        "h" => 3_600_000,
+        _ => return Err(invalid()),
+    };
+    match number
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(factor))
+    {
+        Some(millis) if millis > 0 => Ok(millis),
+        _ => Err(invalid()),
+    }
+}
+
+/// Parses the time zone of `time_bucket = "..."`.
+fn parse_time_zone(lit: LitStr) -> syn::Result<TimeZone> {
+    match lit.value().as_str() {
+        "utc" => Ok(TimeZone::Utc),
+        "local" if !cfg!(feature = "local_time") => Err(syn::Error::new(
+            lit.span(),
+            "`time_bucket = \"local\"` reads the local time zone and requires the `local_time` \
+             feature of metrics-utils",
+        )),
+        "local" => Ok(TimeZone::Local),
+        other => Err(syn::Error::new(
+            lit.span(),
+            format!("unsupported time zone `{other}`, expected \"utc\" or \"local\""),
+        )),
+    }
+}
+
+/// Parses the type of `value_type = "..."`, keeping the literal of `"u64"`.
+fn parse_value_type(lit: LitStr) -> syn::Result<Option<LitStr>> {
+    match lit.value().as_str() {
+        "f64" => Ok(None),
+        "u64" => Ok(Some(lit)),
+        other => Err(syn::Error::new(
+            lit.span(),
+            format!("unsupported value type `{other}`, expected \"f64\" or \"u64\""),
+        )),
+    }
+}
+
+/// Parses the granularity of `quantize = ...`, a positive float literal.
+fn parse_quantize(input: ParseStream) -> syn::Result<f64> {
+    if input.peek(Token![-]) {
+        return Err(input.error("`quantize` must be positive, e.g. `quantize = 0.5`"));
+    }
+    let step = match input.parse()? {
+        Lit::Float(float) => float,
+        Lit::Int(int) => {
+            return Err(syn::Error::new(
+                int.span(),
+                format!("`quantize` must be a float literal, write `{int}.0`"),
+            ))
+        }
+        other => {
+            return Err(syn::Error::new(
+                other.span(),
+                "`quantize` must be a float literal, e.g. `quantize = 0.5`",
+            ))
+        }
+    };
+    let value: f64 = step.base10_parse()?;
+    if value <= 0.0 || !value.is_finite() {
+        return Err(syn::Error::new(
+            step.span(),
+            "`quantize` must be positive, e.g. `quantize = 0.5`",
+        ));
+    }
+    Ok(value)
+}
+
+/// The smoothing factor of a bare `ewma`, weighing each call a tenth against the average.
+const DEFAULT_EWMA_ALPHA: f64 = 0.1;
+
+/// Parses the smoothing factor of `ewma = ...`, a float literal in `(0, 1]`.
+fn parse_alpha(input: ParseStream) -> syn::Result<f64> {
+    let alpha: LitFloat = input.parse().map_err(|error| {
+        syn::Error::new(
+            error.span(),
+            "`ewma` takes a float literal between 0 and 1, e.g. `ewma = 0.2`",
+        )
+    })?;
+    let value: f64 = alpha.base10_parse()?;
+    if !(value > 0.0 && value <= 1.0) {
+        return Err(syn::Error::new(
+            alpha.span(),
+            "the `ewma` smoothing factor must be greater than 0 and at most 1",
+        ));
+    }
+    Ok(value)
+}
+
+/// Parses a threshold in milliseconds, an integer literal that fits a `u64`.
+fn parse_threshold(input: ParseStream) -> syn::Result<LitInt> {
+    let threshold: LitInt = input.parse()?;
+    threshold.base10_parse::<u64>()?;
+    Ok(threshold)
+}
+
+/// The options of `tail_sample(threshold_ms = T, sample = N)`.
+#[derive(Clone)]
+pub(crate) struct TailSample {
+    pub(crate) key: Ident,
+    /// Calls slower than this many milliseconds are always recorded.
+    pub(crate) threshold_ms: LitInt,
+    /// One in this many of the faster calls is recorded.
+    pub(crate) sample: LitInt,
+}
+
+/// Parses the parenthesized `threshold_ms = T, sample = N` of `tail_sample`, in either order.
+fn parse_tail_sample(key: Ident, input: ParseStream) -> syn::Result<TailSample> {
+    let content;
+    let parens = syn::parenthesized!(content in input);
+    let mut threshold_ms = None;
+    let mut sample = None;
+    while !content.is_empty() {
+        let option: Ident = content.parse()?;
+        content.parse::<Token![=]>()?;
+        let (slot, value) = match option.to_string().as_str() {
+            "threshold_ms" => (&mut threshold_ms, parse_threshold(&content)?),
+            "sample" => (&mut sample, parse_sample(&content)?),
+            other => {
+                return Err(syn::Error::new(
+                    option.span(),
+                    format!(
+                        "unknown `tail_sample` option `{other}`, expected `threshold_ms` or \
+                         `sample`"
+                    ),
+                ))
+            }
+        };
+        if slot.replace(value).is_some() {
+            return Err(syn::Error::new(
+                option.span(),
+                format!("`{option}` is given more than once"),
+            ));
+        }
+        if !content.is_empty() {
+            content.parse::<Token![,]>()?;
+        }
+    }
+    match (threshold_ms, sample) {
+        (Some(threshold_ms), Some(sample)) => Ok(TailSample {
+            key,
+            threshold_ms,
+            sample,
+        }),
+        _ => Err(syn::Error::new(
+            parens.span.join(),
+            "`tail_sample` needs both options, e.g. `tail_sample(threshold_ms = 250, sample = 100)`",
+        )),
+    }
+}
+
+/// Parses the mode of `error_kind = ...`, either `type_name` or `display`.
+fn parse_error_kind(input: ParseStream) -> syn::Result<ErrorKind> {
+    let mode: Ident = input.parse()?;
+    match mode.to_string().as_str() {
+        "type_name" => Ok(ErrorKind::TypeName),
+        "display" => Ok(ErrorKind::Display(mode)),
+        other => Err(syn::Error::new(
+            mode.span(),
+            format!("unsupported `error_kind` mode `{other}`, expected `type_name` or `display`"),
+        )),
+    }
+}
+
+/// Parses a bracketed list of float literals such as `[0.001, 0.01, 0.1]`.
+fn parse_buckets(input: ParseStream) -> syn::Result<Vec<LitFloat>> {
+    let content;
+    let brackets = syn::bracketed!(content in input);
+    let mut buckets: Vec<LitFloat> = Vec::new();
+    for lit in content.parse_terminated(Lit::parse, Token![,])? {
+        let float = match lit {
+            Lit::Float(float) => float,
+            Lit::Int(int) => {
+                return Err(syn::Error::new(
+                    int.span(),
+                    format!("bucket boundaries must be float literals, write `{int}.0`"),
+                ))
+            }
+            other => {
+                return Err(syn::Error::new(
+                    other.span(),
+                    "bucket boundaries must be float literals",
+                ))
+            }
+        };
+        let value: f64 = float.base10_parse()?;
+        if let Some(previous) = buckets.last() {
+            if value <= previous.base10_parse::<f64>()? {
+                return Err(syn::Error::new(
+                    float.span(),
+                    "bucket boundaries must be strictly increasing",
+                ));
+            }
+        }
+        buckets.push(float);
+    }
+    if buckets.is_empty() {
+        return Err(syn::Error::new(
+            brackets.span.join(),
+            "`buckets` needs at least one boundary",
+        ));
+    }
+    Ok(buckets)
+}
+
+/// Parses the parenthesised `(key = "value", ...)` list of `labels`.
+fn parse_labels(input: ParseStream) -> syn::Result<Vec<(Ident, LitStr)>> {
+    let content;
+    syn::parenthesized!(content in input);
+    let mut labels = Vec::new();
+    while !content.is_empty() {
+        let key: Ident = content.parse()?;
+        content.parse::<Token![=]>()?;
+        labels.push(parse_label(key, &content)?);
+        if !content.is_empty() {
+            content.parse::<Token![,]>()?;
+        }
+    }
+    Ok(labels)
+}
+
+/// Parses the value of a static `key = "value"` label whose key and `=` were already consumed.
+fn parse_label(key: Ident, input: ParseStream) -> syn::Result<(Ident, LitStr)> {
+    if !input.peek(LitStr) {
+        return Err(input.error(format!(
+            "the value of label `{key}` must be a string literal, e.g. `{key} = \"value\"`"
+        )));
+    }
+    Ok((key, input.parse()?))
+}
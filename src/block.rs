@@ -0,0 +1,188 @@
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::quote;
+use syn::{parse::Parse, parse::ParseStream, Block, Expr, Ident, LitStr, Token};
+
+use crate::args::Unit;
+use crate::prefix;
+
+/// The input of `measure!("label", { ... })`.
+pub(crate) struct MeasureInput {
+    label: LitStr,
+    block: Block,
+}
+
+impl Parse for MeasureInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let label: LitStr = input.parse()?;
+        if label.value().is_empty() {
+            return Err(syn::Error::new(
+                label.span(),
+                "the block label must not be empty",
+            ));
+        }
+        input.parse::<Token![,]>()?;
+        let block = input.parse()?;
+        if !input.is_empty() {
+            input.parse::<Token![,]>()?;
+        }
+        Ok(MeasureInput { label, block })
+    }
+}
+
+/// Generates the expansion of `measure!`.
+///
+/// The block is inlined rather than wrapped in a closure, so `?`, `return` and `.await`
+/// inside it act on the enclosing function. The duration is recorded by a drop guard, which
+/// also covers those early exits and panics. With the `disabled` feature only the block is
+/// emitted.
+pub(crate) fn expand_measure(input: MeasureInput) -> TokenStream2 {
+    let MeasureInput { label, block } = input;
+    if cfg!(feature = "disabled") {
+        return quote! { #block };
+    }
+    let duration = Ident::new("__measured_duration", Span::call_site());
+    let value = Unit::Milliseconds.convert(&duration);
+    let histogram_name = prefix::metric_name("block_duration_milliseconds");
+    let track_prefix = prefix::track();
+    // The statements are spliced in without the block's own braces, which would otherwise trip
+    // `unused_braces` at the user's code.
+    let stmts = &block.stmts;
+    let switch = cfg!(feature = "runtime_switch").then(|| {
+        quote! {
+            if !::metrics_utils::switch::is_enabled() {
+                return;
+            }
+        }
+    });
+
+    quote! {
+        {
+            #track_prefix
+
+            struct __MeasuredBlock(std::time::Instant);
+
+            impl Drop for __MeasuredBlock {
+                fn drop(&mut self) {
+                    #switch
+                    let __measured_duration = self.0.elapsed();
+                    metrics::histogram!(
+                        #histogram_name,
+                        "block" => #label,
+                    ).record(#value);
+                }
+            }
+
+            let __measured_block = __MeasuredBlock(std::time::Instant::now());
+            #(#stmts)*
+        }
+    }
+}
+
+/// Generates the expansion of `exclude!`.
+///
+/// The block adds its own duration to the `__measured_excluded` total declared by the
+/// enclosing instrumented function, which subtracts it from the recorded duration. Like
+/// `measure!`, the block is inlined and a drop guard does the accounting, so early exits and
+/// panics are excluded too. The guard only holds a shared reference to an atomic, keeping an
+/// enclosing future `Send`. With the `disabled` feature only the block is emitted.
+pub(crate) fn expand_exclude(block: Block) -> TokenStream2 {
+    if cfg!(feature = "disabled") {
+        return quote! { #block };
+    }
+    let stmts = &block.stmts;
+    quote! {
+        {
+            struct __MeasuredExclude<'a>(&'a std::sync::atomic::AtomicU64, std::time::Instant);
+
+            impl Drop for __MeasuredExclude<'_> {
+                fn drop(&mut self) {
+                    self.0.fetch_add(
+                        self.1.elapsed().as_nanos() as u64,
+                        std::sync::atomic::Ordering::Relaxed,
+                    );
+                }
+            }
+
+            let __measured_exclude =
+                __MeasuredExclude(__measured_excluded, std::time::Instant::now());
+            #(#stmts)*
+        }
+    }
+}
+
+/// Generates the expansion of `region!`.
+///
+/// The block adds its own duration to the `__measured_region` total declared by the enclosing
+/// instrumented function, which records that total instead of the whole call. The accounting
+/// mirrors `exclude!`: the block is inlined and a drop guard holding a shared reference to an
+/// atomic adds the time, so early exits and panics are counted. With the `disabled` feature only
+/// the block is emitted.
+pub(crate) fn expand_region(block: Block) -> TokenStream2 {
+    if cfg!(feature = "disabled") {
+        return quote! { #block };
+    }
+    let stmts = &block.stmts;
+    quote! {
+        {
+            struct __MeasuredRegion<'a>(&'a std::sync::atomic::AtomicU64, std::time::Instant);
+
+            impl Drop for __MeasuredRegion<'_> {
+                fn drop(&mut self) {
+                    self.0.fetch_add(
+                        self.1.elapsed().as_nanos() as u64,
+                        std::sync::atomic::Ordering::Relaxed,
+                    );
+                }
+            }
+
+            let __measured_region_guard =
+                __MeasuredRegion(__measured_region, std::time::Instant::now());
+            #(#stmts)*
+        }
+    }
+}
+
+/// The input of `await_point!("name", expr)`.
+pub(crate) struct AwaitPointInput {
+    name: LitStr,
+    expr: Expr,
+}
+
+impl Parse for AwaitPointInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let name: LitStr = input.parse()?;
+        if name.value().is_empty() {
+            return Err(syn::Error::new(
+                name.span(),
+                "the await point name must not be empty",
+            ));
+        }
+        input.parse::<Token![,]>()?;
+        let expr = input.parse()?;
+        if !input.is_empty() {
+            input.parse::<Token![,]>()?;
+        }
+        Ok(AwaitPointInput { name, expr })
+    }
+}
+
+/// Generates the expansion of `await_point!`.
+///
+/// The expression is evaluated in place, so its `.await` suspends the enclosing `async fn`,
+/// and its duration is handed to the `__measured_await_point` recorder declared by that
+/// function. An expression that exits early or panics records nothing. With the `disabled`
+/// feature only the expression is emitted.
+pub(crate) fn expand_await_point(input: AwaitPointInput) -> TokenStream2 {
+    let AwaitPointInput { name, expr } = input;
+    if cfg!(feature = "disabled") {
+        return quote! { #expr };
+    }
+    quote! {
+        {
+            let __measured_point_start = std::time::Instant::now();
+            let __measured_point_value = #expr;
+            __measured_await_point(#name, __measured_point_start.elapsed());
+            __measured_point_value
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::quote;
+use syn::{parse::Parse, parse::ParseStream, Expr, ExprClosure, Ident, LitStr, Token};
+
+use crate::args::Unit;
+use crate::prefix;
+
+/// The input of `measured_closure!("label", |args| { ... })`.
+pub(crate) struct MeasuredClosureInput {
+    label: LitStr,
+    closure: ExprClosure,
+}
+
+impl Parse for MeasuredClosureInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let label: LitStr = input.parse()?;
+        if label.value().is_empty() {
+            return Err(syn::Error::new(
+                label.span(),
+                "the closure label must not be empty",
+            ));
+        }
+        input.parse::<Token![,]>()?;
+        let closure = input.parse()?;
+        if !input.is_empty() {
+            input.parse::<Token![,]>()?;
+        }
+        Ok(MeasuredClosureInput { label, closure })
+    }
+}
+
+/// Generates the expansion of `measured_closure!`.
+///
+/// Only the closure's body is replaced, with a drop guard followed by the original body, so
+/// its parameters, return type, captures and `move`-ness stay as written and the compiler
+/// infers the same `Fn`, `FnMut` or `FnOnce` kind. Each invocation records one sample, also
+/// when it returns early or panics. With the `disabled` feature the closure is emitted
+/// unchanged.
+pub(crate) fn expand_measured_closure(input: MeasuredClosureInput) -> TokenStream2 {
+    let MeasuredClosureInput { label, mut closure } = input;
+    if cfg!(feature = "disabled") {
+        return quote! { #closure };
+    }
+    let duration = Ident::new("__measured_duration", Span::call_site());
+    let value = Unit::Milliseconds.convert(&duration);
+    let histogram_name = prefix::metric_name("closure_duration_milliseconds");
+    let track_prefix = prefix::track();
+    let switch = cfg!(feature = "runtime_switch").then(|| {
+        quote! {
+            if !::metrics_utils::switch::is_enabled() {
+                return;
+            }
+        }
+    });
+
+    // A block body is spliced in without its braces, which would otherwise trip
+    // `unused_braces` at the user's code.
+    let body = match closure.body.as_ref() {
+        Expr::Block(block) if block.attrs.is_empty() && block.label.is_none() => {
+            let stmts = &block.block.stmts;
+            quote! { #(#stmts)* }
+        }
+        body => quote! { #body },
+    };
+    *closure.body = syn::parse_quote! {
+        {
+            let __measured_closure = __MeasuredClosure(std::time::Instant::now());
+            #body
+        }
+    };
+
+    quote! {
+        {
+            #track_prefix
+
+            struct __MeasuredClosure(std::time::Instant);
+
+            impl Drop for __MeasuredClosure {
+                fn drop(&mut self) {
+                    #switch
+                    let __measured_duration = self.0.elapsed();
+                    metrics::histogram!(
+                        #histogram_name,
+                        "closure" => #label,
+                    ).record(#value);
+                }
+            }
+
+            #closure
+        }
+    }
+}
@@ -0,0 +1,657 @@
+//! Checks rejecting combinations of options that contradict each other.
+//!
+//! Each option is parsed on its own, so the rules relating several of them live here, once
+//! for every attribute. A conflict is reported at the option that can't be honoured, with the
+//! reason it clashes.
+
+use proc_macro2::Span;
+use syn::Ident;
+
+use crate::args::{Instrument, MacroArgs, RESERVED_LABELS};
+use crate::expand::FnKind;
+
+/// Rejects the combinations of `args` that can't be honoured, or that can't be honoured on a
+/// function of this `kind`.
+pub(crate) fn check(args: &MacroArgs, kind: FnKind) -> syn::Result<()> {
+    check_name(args)?;
+    check_labels(args)?;
+    if let (Some(exemplar), None) = (&args.exemplar_from, &args.sink) {
+        return Err(syn::Error::new_spanned(
+            exemplar,
+            "`exemplar_from` needs a `sink = ...` to receive the exemplar, since the `metrics` \
+             facade cannot record exemplars",
+        ));
+    }
+    if let Some(max) = &args.max_cardinality {
+        if args.label_from.is_none()
+            && args.correlation_from.is_none()
+            && args.label_from_context.is_none()
+            && args.labels_from.is_none()
+        {
+            return Err(syn::Error::new(
+                max.span(),
+                "`max_cardinality` caps the values of runtime labels, set by `label_from`, \
+                 `label_from_present`, `correlation_from`, `label_from_context` or \
+                 `labels_from`, and none is set",
+            ));
+        }
+    }
+    if let (Some(separator), None) = (&args.namespace_separator, &args.namespace) {
+        return Err(syn::Error::new(
+            separator.span(),
+            "`namespace_separator` joins the components of a `namespace`, which is not set",
+        ));
+    }
+    match (&args.expose_as, &args.after) {
+        (Some(binding), None) => {
+            return Err(syn::Error::new(
+                binding.span(),
+                format!("`expose_as` binds `{binding}` for an `after = {{ ... }}` block, which is not set"),
+            ));
+        }
+        (None, Some((key, _))) => {
+            return Err(syn::Error::new(
+                key.span(),
+                "`after` needs `expose_as = name` to name the variable holding the duration",
+            ));
+        }
+        _ => {}
+    }
+    if let Some(lit) = &args.integer {
+        let float = match (&args.convert, &args.quantize) {
+            (Some(convert), _) => Some(("convert", syn::spanned::Spanned::span(convert))),
+            (None, Some(_)) => Some(("quantize", lit.span())),
+            (None, None) => None,
+        };
+        if let Some((option, span)) = float {
+            return Err(syn::Error::new(
+                span,
+                format!(
+                    "`{option}` produces a float and cannot be combined with `value_type = {}`, \
+                     which records whole nanoseconds",
+                    lit.token()
+                ),
+            ));
+        }
+    }
+    check_instrument(args)?;
+    check_split_result(args)?;
+    check_handle(args)?;
+    check_success_ratio(args)?;
+    if let (Some((flag, _)), Some((option, span))) = (&args.ewma, runtime_label(args)) {
+        return Err(syn::Error::new(
+            span,
+            format!(
+                "`{option}` labels calls at runtime, while `{flag}` keeps one moving average for \
+                 every call of the function"
+            ),
+        ));
+    }
+    check_no_histogram(args)?;
+    check_dual_unit(args)?;
+    if let Some(record) = &args.record_span {
+        if let Some((option, span)) = histogram_option(args, record.span()) {
+            return Err(syn::Error::new(
+                span,
+                format!(
+                    "`{option}` configures the duration histogram, which `record = \"span\"` \
+                     replaces with a span field"
+                ),
+            ));
+        }
+    }
+    check_sampling(args)?;
+    check_kind(args, kind)
+}
+
+/// Rejects options that provide the name label themselves next to a custom name.
+fn check_name(args: &MacroArgs) -> syn::Result<()> {
+    let Some(name) = &args.custom_name else {
+        return Ok(());
+    };
+    if args.type_name.is_some() {
+        return Err(syn::Error::new(
+            name.span(),
+            "a custom name cannot be combined with `type_name`, which only prefixes the \
+             function name",
+        ));
+    }
+    if args.label_from.is_some() {
+        return Err(syn::Error::new(
+            name.span(),
+            "a custom name cannot be combined with `label_from`, which provides the label value",
+        ));
+    }
+    Ok(())
+}
+
+/// Rejects static labels that would collide with a label set by the macro.
+fn check_labels(args: &MacroArgs) -> syn::Result<()> {
+    if let (Some(key), None) = (&args.context_key, &args.label_from_context) {
+        return Err(syn::Error::new(
+            key.span(),
+            "`context_key` names the label of `label_from_context`, which is not set",
+        ));
+    }
+    // Labels that only some options add, with the option adding them.
+    let mut flagged = Vec::new();
+    if let Some(flag) = &args.label_variant {
+        flagged.push(("variant", flag));
+    }
+    if let Some(flag) = &args.with_pkg_labels {
+        flagged.extend([("service", flag), ("version", flag)]);
+    }
+    if let Some(flag) = &args.with_profile_label {
+        flagged.push(("profile", flag));
+    }
+    if let Some((option, _)) = &args.path_label_from_cfg {
+        flagged.push(("path", option));
+    }
+    if let Some(flag) = &args.task_id_label {
+        flagged.push(("task_id", flag));
+    }
+    if let Some(flag) = &args.arity {
+        flagged.push(("arity", flag));
+    }
+    if let Some(flag) = &args.depth_label {
+        flagged.push(("depth", flag));
+    }
+    if let Some(param) = &args.correlation_from {
+        flagged.push(("correlation", param));
+    }
+    if let Some((flag, _)) = &args.time_bucket {
+        flagged.push(("hour", flag));
+    }
+    let label_key = args.label_key();
+    let context_key = args.context_key();
+    if let Some((option, _)) = &args.label_from_context {
+        if context_key == label_key || flagged.iter().any(|(key, _)| *key == context_key) {
+            let span = args
+                .context_key
+                .as_ref()
+                .map_or(option.span(), syn::LitStr::span);
+            return Err(syn::Error::new(
+                span,
+                format!(
+                    "the `{context_key}` label is already set by the macro, choose another \
+                     `context_key`"
+                ),
+            ));
+        }
+        flagged.push((context_key.as_str(), option));
+    }
+    for (key, _) in &args.labels {
+        if *key == label_key
+            || RESERVED_LABELS.iter().any(|reserved| key == reserved)
+            || flagged.iter().any(|(reserved, _)| key == reserved)
+        {
+            return Err(syn::Error::new(
+                key.span(),
+                format!("the `{key}` label is set by the macro and cannot be overridden"),
+            ));
+        }
+    }
+    match (
+        &args.label_key,
+        flagged.iter().find(|(key, _)| *key == label_key),
+    ) {
+        (Some(lit), Some((key, flag))) => Err(syn::Error::new(
+            lit.span(),
+            format!("`{flag}` sets the `{key}` label, so it cannot be the `label_key`"),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Rejects options that only apply to histograms when `as = "..."` selects another
+/// instrument, and sampling for counters, which would undercount the total.
+fn check_instrument(args: &MacroArgs) -> syn::Result<()> {
+    let instrument = args.instrument;
+    if instrument == Instrument::Histogram {
+        return Ok(());
+    }
+    let name = instrument.name();
+    if let Some(sink) = &args.sink {
+        return Err(syn::Error::new_spanned(
+            sink,
+            format!("`sink` receives histograms and cannot be combined with `as = \"{name}\"`"),
+        ));
+    }
+    if let Some(bucket) = args.buckets.iter().flatten().next() {
+        return Err(syn::Error::new(
+            bucket.span(),
+            format!(
+                "`buckets` configure a histogram and cannot be combined with `as = \"{name}\"`"
+            ),
+        ));
+    }
+    if instrument == Instrument::Counter {
+        let sampled = match (&args.sample, &args.tail_sample) {
+            (Some(sample), _) => Some(("sample", sample.span())),
+            (None, Some(tail)) => Some(("tail_sample", tail.key.span())),
+            (None, None) => None,
+        };
+        if let Some((option, span)) = sampled {
+            return Err(syn::Error::new(
+                span,
+                format!(
+                    "`{option}` would leave calls out of the total of `as = \"counter\"`, \
+                     which adds up every call"
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Rejects options that `split_result` replaces or that would record a call to more than the
+/// histogram of its outcome.
+fn check_split_result(args: &MacroArgs) -> syn::Result<()> {
+    let Some(flag) = &args.split_result else {
+        return Ok(());
+    };
+    if let Some(result) = &args.result {
+        return Err(syn::Error::new(
+            result.span(),
+            "`result` is redundant with `split_result`, which already records `Ok` and `Err` \
+             calls apart",
+        ));
+    }
+    if args.success_only {
+        return Err(syn::Error::new(
+            flag.span(),
+            "`split_result` cannot be used with `#[measured_try_function]`, which records only \
+             `Ok` calls",
+        ));
+    }
+    if let Some(name) = args.also_record.first() {
+        return Err(syn::Error::new(
+            name.span(),
+            "`also_record` cannot be combined with `split_result`, which records each call to \
+             the histogram of its outcome",
+        ));
+    }
+    Ok(())
+}
+
+/// Rejects options that would label the samples going into a `handle`, whose name and labels
+/// were fixed when it was resolved.
+fn check_handle(args: &MacroArgs) -> syn::Result<()> {
+    let Some(handle) = &args.handle else {
+        return Ok(());
+    };
+    if let Some(sink) = &args.sink {
+        return Err(syn::Error::new_spanned(
+            sink,
+            "`sink` and `handle` both receive the duration samples, only one can be given",
+        ));
+    }
+    if args.instrument != Instrument::Histogram {
+        return Err(syn::Error::new_spanned(
+            handle,
+            format!(
+                "`handle` records into a histogram and cannot be combined with `as = \"{}\"`",
+                args.instrument.name()
+            ),
+        ));
+    }
+    let varying = [
+        ("result", args.result.as_ref().map(Ident::span)),
+        ("split_result", args.split_result.as_ref().map(Ident::span)),
+        (
+            "error_kind",
+            args.error_kind.as_ref().map(|(key, _)| key.span()),
+        ),
+        (
+            "label_variant",
+            args.label_variant.as_ref().map(Ident::span),
+        ),
+        ("label_from", args.label_from.as_ref().map(Ident::span)),
+        (
+            "correlation_from",
+            args.correlation_from.as_ref().map(Ident::span),
+        ),
+        (
+            "time_bucket",
+            args.time_bucket.as_ref().map(|(key, _)| key.span()),
+        ),
+        (
+            "label_from_context",
+            args.label_from_context.as_ref().map(|(key, _)| key.span()),
+        ),
+        (
+            "task_id_label",
+            args.task_id_label.as_ref().map(Ident::span),
+        ),
+        (
+            "labels_from",
+            args.labels_from.as_ref().map(syn::spanned::Spanned::span),
+        ),
+        (
+            "monomorphized",
+            args.monomorphized.as_ref().map(Ident::span),
+        ),
+    ];
+    if let Some((option, span)) = varying
+        .into_iter()
+        .find_map(|(option, span)| Some((option, span?)))
+    {
+        return Err(syn::Error::new(
+            span,
+            format!(
+                "`{option}` labels each call, which a `handle` can't do since its labels are \
+                 fixed when it is resolved"
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Rejects options that label calls differently when `success_ratio` or
+/// `windowed_error_rate` counts all calls of the function together.
+fn check_success_ratio(args: &MacroArgs) -> syn::Result<()> {
+    let flag = match (&args.success_ratio, &args.windowed_error_rate) {
+        (Some(flag), _) => flag.clone(),
+        (None, Some((window, _))) => Ident::new("windowed_error_rate", window.span()),
+        (None, None) => return Ok(()),
+    };
+    if let Some((option, span)) = runtime_label(args) {
+        return Err(syn::Error::new(
+            span,
+            format!(
+                "`{option}` labels calls at runtime, while `{flag}` counts every call of the \
+                 function in one ratio"
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// The first option given that labels calls at runtime, with its span. Options keeping one
+/// state per function, such as `success_ratio` or `ewma`, would mix the series it splits.
+fn runtime_label(args: &MacroArgs) -> Option<(&'static str, Span)> {
+    let label_from = match &args.label_from_present {
+        Some(_) => "label_from_present",
+        None => "label_from",
+    };
+    let varying = [
+        (label_from, args.label_from.as_ref().map(Ident::span)),
+        (
+            "correlation_from",
+            args.correlation_from.as_ref().map(Ident::span),
+        ),
+        (
+            "label_from_context",
+            args.label_from_context.as_ref().map(|(key, _)| key.span()),
+        ),
+        (
+            "task_id_label",
+            args.task_id_label.as_ref().map(Ident::span),
+        ),
+        (
+            "labels_from",
+            args.labels_from.as_ref().map(syn::spanned::Spanned::span),
+        ),
+        (
+            "monomorphized",
+            args.monomorphized.as_ref().map(Ident::span),
+        ),
+    ];
+    varying
+        .into_iter()
+        .find_map(|(option, span)| Some((option, span?)))
+}
+
+/// The first option given that configures the duration histogram, with its span, or the span
+/// of `flag` when the option is an implicit choice such as `as`.
+fn histogram_option(args: &MacroArgs, flag: Span) -> Option<(&'static str, Span)> {
+    let configuring = [
+        (
+            "as",
+            (args.instrument != Instrument::Histogram).then_some(flag),
+        ),
+        ("sink", args.sink.as_ref().map(syn::spanned::Spanned::span)),
+        (
+            "handle",
+            args.handle.as_ref().map(syn::spanned::Spanned::span),
+        ),
+        (
+            "buckets",
+            args.buckets
+                .iter()
+                .flatten()
+                .next()
+                .map(syn::LitFloat::span),
+        ),
+        (
+            "describe",
+            args.describe.as_ref().map(|(key, _)| key.span()),
+        ),
+        (
+            "also_record",
+            args.also_record.first().map(syn::LitStr::span),
+        ),
+        ("split_result", args.split_result.as_ref().map(Ident::span)),
+        ("dual_unit", args.dual_unit.as_ref().map(Ident::span)),
+        (
+            "min_duration_ms",
+            args.min_duration_ms.as_ref().map(syn::LitInt::span),
+        ),
+        (
+            "exemplar_from",
+            args.exemplar_from.as_ref().map(syn::spanned::Spanned::span),
+        ),
+    ];
+    configuring
+        .into_iter()
+        .find_map(|(option, span)| Some((option, span?)))
+}
+
+/// Checks that `dual_unit` can derive the name and value of the second unit: the unit must be
+/// milliseconds or seconds, and the name and value must come from it.
+fn check_dual_unit(args: &MacroArgs) -> syn::Result<()> {
+    let Some(flag) = &args.dual_unit else {
+        return Ok(());
+    };
+    if args.unit.dual().is_none() {
+        return Err(syn::Error::new(
+            flag.span(),
+            format!(
+                "`dual_unit` records milliseconds and seconds side by side, but the unit is {}",
+                args.unit.suffix()
+            ),
+        ));
+    }
+    let fixed = [
+        ("metric", args.metric.as_ref().map(syn::LitStr::span)),
+        (
+            "convert",
+            args.convert.as_ref().map(syn::spanned::Spanned::span),
+        ),
+        ("split_result", args.split_result.as_ref().map(Ident::span)),
+    ];
+    if let Some((option, span)) = fixed
+        .into_iter()
+        .find_map(|(option, span)| Some((option, span?)))
+    {
+        return Err(syn::Error::new(
+            span,
+            format!(
+                "`dual_unit` derives the second metric from the unit, which `{option}` \
+                 doesn't follow"
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Rejects options configuring the duration histogram that `no_histogram` leaves out, and
+/// `no_histogram` without any other metric, which would record nothing at all.
+fn check_no_histogram(args: &MacroArgs) -> syn::Result<()> {
+    let Some(flag) = &args.no_histogram else {
+        return Ok(());
+    };
+    if let Some((option, span)) = histogram_option(args, flag.span()) {
+        return Err(syn::Error::new(
+            span,
+            format!(
+                "`{option}` configures the duration histogram, which `no_histogram` leaves out"
+            ),
+        ));
+    }
+    if let Some(record) = &args.record_span {
+        return Err(syn::Error::new(
+            record.span(),
+            "`record = \"span\"` records the duration in place of the histogram, which \
+             `no_histogram` leaves out",
+        ));
+    }
+    let recording = args.count.is_some()
+        || args.inflight.is_some()
+        || args.lifecycle.is_some()
+        || args.interarrival.is_some()
+        || args.last_duration.is_some()
+        || args.ewma.is_some()
+        || args.timestamps.is_some()
+        || args.summary.is_some()
+        || args.ringbuffer.is_some()
+        || args.slow_threshold_ms.is_some()
+        || args.log_level.is_some()
+        || args.record_len.is_some()
+        || args.record_serialized_len.is_some()
+        || args.success_ratio.is_some()
+        || args.windowed_error_rate.is_some()
+        || args.busy.is_some()
+        || args.poll_count.is_some()
+        || args.sync_prefix.is_some()
+        || args.cpu_time.is_some()
+        || args.utilization.is_some()
+        || args.alloc_count.is_some()
+        || args.span.is_some()
+        || args.on_record.is_some()
+        || args.after.is_some();
+    if !recording {
+        return Err(syn::Error::new(
+            flag.span(),
+            "`no_histogram` leaves out the duration histogram and no other metric is enabled, \
+             so nothing would be recorded; add one such as `count`, `inflight` or \
+             `last_duration`",
+        ));
+    }
+    Ok(())
+}
+
+/// Rejects options that decide per call when `tail_sample` decides once the duration is known.
+fn check_sampling(args: &MacroArgs) -> syn::Result<()> {
+    let Some(tail) = &args.tail_sample else {
+        return Ok(());
+    };
+    if let Some(sample) = &args.sample {
+        return Err(syn::Error::new(
+            sample.span(),
+            format!(
+                "`sample` cannot be combined with `{}`, which samples the faster calls itself",
+                tail.key
+            ),
+        ));
+    }
+    let sized = match (&args.record_len, &args.record_serialized_len) {
+        (Some(flag), _) => Some(("record_len", flag.span())),
+        (None, Some(path)) => Some(("record_serialized_len", syn::spanned::Spanned::span(path))),
+        (None, None) => None,
+    };
+    if let Some((option, span)) = sized {
+        return Err(syn::Error::new(
+            span,
+            format!(
+                "`{option}` cannot be combined with `{}`, which decides per duration rather \
+                 than per call",
+                tail.key
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Rejects options that measure something only one kind of function has.
+fn check_kind(args: &MacroArgs, kind: FnKind) -> syn::Result<()> {
+    if let (Some(flag), FnKind::Sync) = (&args.busy, kind) {
+        return Err(syn::Error::new(
+            flag.span(),
+            "`busy` measures time spent polling and is only supported on async functions",
+        ));
+    }
+    if let (Some(flag), FnKind::Sync) = (&args.poll_count, kind) {
+        return Err(syn::Error::new(
+            flag.span(),
+            "`poll_count` counts the polls of a future and is only supported on async functions",
+        ));
+    }
+    if let (Some(flag), FnKind::Sync) = (&args.task_id_label, kind) {
+        return Err(syn::Error::new(
+            flag.span(),
+            "`task_id_label` labels calls with their Tokio task and is only supported on async \
+             functions",
+        ));
+    }
+    if let (Some(flag), FnKind::Sync) = (&args.sync_prefix, kind) {
+        return Err(syn::Error::new(
+            flag.span(),
+            "`sync_prefix` measures the time until the first suspension and is only supported \
+             on async functions",
+        ));
+    }
+    if let Some(flag) = &args.top_level_only {
+        if !matches!(kind, FnKind::Sync) {
+            return Err(syn::Error::new(
+                flag.span(),
+                "`top_level_only` tracks nesting per thread and is only supported on sync \
+                 functions, since a future can be polled on several threads",
+            ));
+        }
+    }
+    if let Some(flag) = &args.depth_label {
+        if !matches!(kind, FnKind::Sync) {
+            return Err(syn::Error::new(
+                flag.span(),
+                "`depth_label` tracks nesting per thread and is only supported on sync \
+                 functions, since a future can be polled on several threads",
+            ));
+        }
+        if let Some(top_level_only) = &args.top_level_only {
+            return Err(syn::Error::new(
+                top_level_only.span(),
+                "`top_level_only` records only the outermost call, whose `depth` is always 0, \
+                 so it cannot be combined with `depth_label`",
+            ));
+        }
+    }
+    if let Some(flag) = &args.cpu_time {
+        if !matches!(kind, FnKind::Sync) {
+            return Err(syn::Error::new(
+                flag.span(),
+                "`cpu_time` reads the CPU clock of the calling thread and is only supported on \
+                 sync functions, since a future can be polled on several threads",
+            ));
+        }
+    }
+    if let Some(flag) = &args.utilization {
+        if !matches!(kind, FnKind::Sync) {
+            return Err(syn::Error::new(
+                flag.span(),
+                "`utilization` reads the CPU clock of the calling thread and is only supported on \
+                 sync functions, since a future can be polled on several threads",
+            ));
+        }
+    }
+    if let Some(flag) = &args.alloc_count {
+        if !matches!(kind, FnKind::Sync) {
+            return Err(syn::Error::new(
+                flag.span(),
+                "`alloc_count` counts the allocations of the calling thread and is only \
+                 supported on sync functions, since a future can be polled on several threads",
+            ));
+        }
+    }
+    Ok(())
+}
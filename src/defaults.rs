@@ -0,0 +1,48 @@
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::quote;
+use syn::LitStr;
+
+use crate::args::{check_label_key, Unit};
+
+/// The environment variable holding the crate-wide default for `unit`.
+const UNIT_VAR: &str = "METRICS_MACROS_DEFAULT_UNIT";
+/// The environment variable holding the crate-wide default for `label_key`.
+const LABEL_KEY_VAR: &str = "METRICS_MACROS_DEFAULT_LABEL_KEY";
+
+/// The unit from `METRICS_MACROS_DEFAULT_UNIT` at expansion time, milliseconds if unset or
+/// empty. An explicit `unit = "..."` still overrides it.
+pub(crate) fn unit() -> syn::Result<Unit> {
+    match read(UNIT_VAR) {
+        Some(value) => Unit::from_lit(&value).map_err(|error| from_var(UNIT_VAR, error)),
+        None => Ok(Unit::Milliseconds),
+    }
+}
+
+/// The label key from `METRICS_MACROS_DEFAULT_LABEL_KEY` at expansion time, if set. An
+/// explicit `label_key = "..."` still overrides it.
+pub(crate) fn label_key() -> syn::Result<Option<LitStr>> {
+    read(LABEL_KEY_VAR)
+        .map(|value| check_label_key(value).map_err(|error| from_var(LABEL_KEY_VAR, error)))
+        .transpose()
+}
+
+/// Makes the instrumented crate depend on the default variables, like `prefix::track`.
+pub(crate) fn track() -> TokenStream2 {
+    quote! {
+        const _: Option<&str> = option_env!(#UNIT_VAR);
+        const _: Option<&str> = option_env!(#LABEL_KEY_VAR);
+    }
+}
+
+/// The non-empty value of `var`, as a literal spanned at the attribute.
+fn read(var: &str) -> Option<LitStr> {
+    std::env::var(var)
+        .ok()
+        .filter(|value| !value.is_empty())
+        .map(|value| LitStr::new(&value, Span::call_site()))
+}
+
+/// Points an error about a default at the variable it came from.
+fn from_var(var: &str, error: syn::Error) -> syn::Error {
+    syn::Error::new(Span::call_site(), format!("invalid `{var}`: {error}"))
+}
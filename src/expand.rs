@@ -0,0 +1,2555 @@
+use proc_macro2::{Span, TokenStream as TokenStream2, TokenTree};
+use quote::{format_ident, quote, quote_spanned, ToTokens};
+use syn::ext::IdentExt;
+use syn::visit::Visit;
+use syn::{
+    Attribute, Block, FnArg, GenericArgument, GenericParam, Ident, ItemFn, LitInt, Meta, Pat, Path,
+    PathArguments, ReturnType, Signature, Stmt, Type, TypeParamBound,
+};
+
+use crate::args::{CustomName, ErrorKind, Instrument, MacroArgs, TimeZone, Unit};
+use crate::{conflicts, defaults, prefix, strict};
+
+/// Whether the instrumented function is a plain `fn`, an `async fn` or a plain `fn` returning
+/// `impl Future`, `Pin<Box<dyn Future>>` or `Box<dyn Future>`.
+#[derive(Clone, Copy)]
+pub(crate) enum FnKind {
+    Sync,
+    Async,
+    /// Times the returned future until it completes, not the call that constructs it.
+    Future,
+    /// Like `Future`, for the boxed futures that `#[async_trait]` methods return.
+    BoxedFuture,
+}
+
+impl FnKind {
+    /// Picks the kind from the function's own signature, as `#[measured]` does.
+    pub(crate) fn detect(input_fn: &ItemFn) -> Self {
+        if input_fn.sig.asyncness.is_some() {
+            FnKind::Async
+        } else if returns_impl_future(&input_fn.sig) {
+            FnKind::Future
+        } else if returns_boxed_future(&input_fn.sig) {
+            FnKind::BoxedFuture
+        } else {
+            FnKind::Sync
+        }
+    }
+
+    fn histogram_prefix(self) -> &'static str {
+        match self {
+            FnKind::Sync => "function_duration",
+            FnKind::Async | FnKind::Future | FnKind::BoxedFuture => "async_function_duration",
+        }
+    }
+
+    /// Whether the body returns a future to be wrapped rather than being the function's own
+    /// code.
+    fn returns_future(self) -> bool {
+        matches!(self, FnKind::Future | FnKind::BoxedFuture)
+    }
+}
+
+/// Generates the timing wrapper shared by every attribute entry point.
+///
+/// The entry points only parse their input and pick a `kind`, so an option is implemented
+/// once here and applies to sync and async functions alike. `kind` decides how the original
+/// body is evaluated (inlined, an awaited `async move` block or the awaited future the body
+/// returns), which histogram is recorded to and which labels describe an abnormal exit.
+/// Everything else is identical between sync and async functions.
+pub(crate) fn expand(
+    mut args: MacroArgs,
+    mut input_fn: ItemFn,
+    kind: FnKind,
+) -> syn::Result<TokenStream2> {
+    args.take_marker(&mut input_fn.attrs)?;
+    let ItemFn {
+        attrs,
+        vis,
+        sig,
+        block,
+    } = &input_fn;
+    let fn_name = &sig.ident;
+
+    // Reading the clock and recording metrics can't happen in a const context, so a `const fn`
+    // is either rejected or, with `strip_const`, instrumented as a plain function.
+    let stripped;
+    let sig = match (&sig.constness, &args.strip_const) {
+        (Some(constness), None) => {
+            return Err(syn::Error::new(
+                constness.span,
+                format!(
+                    "`const fn {fn_name}` cannot be timed, since the clock can't be read in a \
+                     const context; add `strip_const` to instrument it as a non-`const` function"
+                ),
+            ));
+        }
+        (Some(_), Some(_)) => {
+            stripped = Signature {
+                constness: None,
+                ..sig.clone()
+            };
+            &stripped
+        }
+        (None, _) => sig,
+    };
+
+    // A naked function's body may only be inline assembly, so there's nowhere to put the timer.
+    if let Some(naked) = attrs.iter().find(|attr| is_attr(attr, "naked")) {
+        return Err(syn::Error::new_spanned(
+            naked,
+            format!("`#[naked]` function `{fn_name}` cannot be instrumented"),
+        ));
+    }
+
+    if kind.returns_future() {
+        let (attribute, returned, matches) = match kind {
+            FnKind::BoxedFuture => (
+                "measured_boxed_future",
+                // Both types are spliced between the backticks of the messages below.
+                "Pin<Box<dyn Future>>` or `Box<dyn Future>",
+                returns_boxed_future(sig),
+            ),
+            _ => ("measured_future", "impl Future", returns_impl_future(sig)),
+        };
+        if let Some(asyncness) = &sig.asyncness {
+            return Err(syn::Error::new(
+                asyncness.span,
+                format!(
+                    "`#[{attribute}]` is for plain functions returning `{returned}`, \
+                     use `#[measured_async_function]` for an `async fn`"
+                ),
+            ));
+        }
+        if !matches {
+            return Err(syn::Error::new_spanned(
+                &sig.output,
+                format!("`#[{attribute}]` requires `{fn_name}` to return `{returned}`"),
+            ));
+        }
+    }
+
+    conflicts::check(&args, kind)?;
+
+    // With `named_inner` the rest of the expansion times a call to the nested function, which
+    // it treats like any other body.
+    let named;
+    let (sig, block) = match &args.named_inner {
+        Some(flag) => {
+            named = named_inner(flag, sig, block)?;
+            (&named.0, &named.1)
+        }
+        None => (sig, &**block),
+    };
+
+    // A custom name is used verbatim. Otherwise the function name (or the `Type::method` name
+    // from `#[measured_impl]` or `type_name`) is used, prefixed with the caller's module path
+    // if `qualified`. The name is taken from the identifier the attribute sees, which is the
+    // final one for functions generated by `macro_rules!`, without the `r#` of a raw
+    // identifier.
+    let metric_name = match &args.custom_name {
+        Some(name) => quote! { #name },
+        None => {
+            let name = match (&args.default_name, &args.type_name) {
+                (Some(name), _) => quote! { #name },
+                (None, Some(type_name)) => {
+                    let name = format!("{}::{}", type_name.value(), fn_name.unraw());
+                    quote! { #name }
+                }
+                (None, None) => {
+                    let name = fn_name.unraw().to_string();
+                    quote! { #name }
+                }
+            };
+            match args.qualified {
+                Some(_) => quote! { concat!(module_path!(), "::", #name) },
+                None => name,
+            }
+        }
+    };
+    // The body is emitted twice with `enabled_if`, `skip_if`, `skip_in_test` or
+    // `top_level_only`, instrumented and as written.
+    // Items declared in it are hoisted out so both copies share them, e.g. a `static` counter.
+    let mut hoisted = Vec::new();
+    let without_items;
+    let conditional = cfg!(feature = "runtime_switch")
+        || args.enabled_if.is_some()
+        || args.skip_if.is_some()
+        || args.skip_in_test.is_some()
+        || args.top_level_only.is_some();
+    let block = match kind {
+        FnKind::Sync | FnKind::Async if conditional => {
+            let (items, stmts): (Vec<Stmt>, Vec<Stmt>) = block
+                .stmts
+                .iter()
+                .cloned()
+                .partition(|stmt| matches!(stmt, Stmt::Item(_)));
+            hoisted = items;
+            without_items = Block {
+                brace_token: block.brace_token,
+                stmts,
+            };
+            &without_items
+        }
+        _ => block,
+    };
+
+    // `metric = "..."` replaces the whole histogram name, so functions can share one metric
+    // and tell each other apart by their label.
+    // A counter adds the durations up, so its default name carries the `_total` suffix of
+    // counters. With `split_result`, the outcome goes into the name ahead of the unit, or after
+    // a name given with `metric`.
+    let name_for = |outcome: Option<&str>, unit: Unit| {
+        let outcome = outcome
+            .map(|outcome| format!("_{outcome}"))
+            .unwrap_or_default();
+        args.metric_name(&match (&args.metric, args.instrument) {
+            (Some(metric), _) => format!("{}{outcome}", metric.value()),
+            (None, Instrument::Counter) => {
+                format!(
+                    "{}{outcome}_{}_total",
+                    kind.histogram_prefix(),
+                    unit.suffix()
+                )
+            }
+            (None, _) => format!("{}{outcome}_{}", kind.histogram_prefix(), unit.suffix()),
+        })
+    };
+    let histogram_name = name_for(None, args.unit);
+    let split_names = args.split_result.as_ref().map(|_| {
+        (
+            name_for(Some("ok"), args.unit),
+            name_for(Some("err"), args.unit),
+        )
+    });
+    // With `dual_unit`, the duration is also recorded under the name for the other unit.
+    let dual_unit = args
+        .dual_unit
+        .as_ref()
+        .and_then(|_| args.unit.dual())
+        .map(|unit| (name_for(None, unit), unit));
+    // The names the duration is actually recorded to.
+    let histogram_names = match &split_names {
+        Some((ok, err)) => vec![ok.clone(), err.clone()],
+        None => vec![histogram_name.clone()],
+    };
+    // With `also_record`, the same value is recorded to further names, e.g. while migrating
+    // to a new naming convention.
+    let also_record = args
+        .also_record
+        .iter()
+        .map(|name| {
+            let prefixed = prefix::metric_name(&name.value());
+            if prefixed == histogram_name {
+                return Err(syn::Error::new(
+                    name.span(),
+                    format!(
+                        "`{}` is already the histogram this function records to",
+                        name.value()
+                    ),
+                ));
+            }
+            Ok(prefixed)
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+    if args.strict.is_some() || cfg!(feature = "strict") {
+        check_strict(&args, &histogram_names, &also_record)?;
+    }
+    // Time spent in `exclude!` blocks of the body is summed up and subtracted from the
+    // duration. The total is declared once in front of everything else, so the blocks find it
+    // in every copy of the body, and is borrowed so an `async move` body doesn't take it.
+    let excludes = uses_macro(block.to_token_stream(), "exclude");
+    if excludes && kind.returns_future() {
+        return Err(syn::Error::new(
+            Span::call_site(),
+            "`exclude!` is not supported in functions returning a future, use an `async fn`",
+        ));
+    }
+    let exclude_total = excludes.then(|| {
+        quote! {
+            let __measured_excluded_nanos = std::sync::atomic::AtomicU64::new(0);
+            let __measured_excluded = &__measured_excluded_nanos;
+        }
+    });
+    // `region!` blocks work the same way, but their total replaces the call's duration.
+    let regions = uses_macro(block.to_token_stream(), "region");
+    if regions && kind.returns_future() {
+        return Err(syn::Error::new(
+            Span::call_site(),
+            "`region!` is not supported in functions returning a future, use an `async fn`",
+        ));
+    }
+    if regions && excludes {
+        return Err(syn::Error::new(
+            Span::call_site(),
+            "`region!` can't be combined with `exclude!`, leave the excluded code out of the region",
+        ));
+    }
+    let region_total = regions.then(|| {
+        quote! {
+            let __measured_region_nanos = std::sync::atomic::AtomicU64::new(0);
+            let __measured_region = &__measured_region_nanos;
+        }
+    });
+    // `await_point!` calls in the body record through a closure declared up front for the same
+    // reason. It only records once the call is being timed, so calls left out by `enabled_if`,
+    // `skip_if`, `skip_in_test` or `top_level_only` record nothing, and it only borrows an
+    // atomic, so the function's future stays `Send`.
+    let await_points = uses_macro(block.to_token_stream(), "await_point");
+    if await_points && !matches!(kind, FnKind::Async) {
+        return Err(syn::Error::new(
+            Span::call_site(),
+            "`await_point!` is only supported in an `async fn`",
+        ));
+    }
+    let (await_point_setup, await_point_on) = match await_points {
+        true => {
+            let histogram = args.metric_name(&format!("async_await_point_{}", args.unit.suffix()));
+            let label_key = args.label_key();
+            let static_labels = args.labels.iter().map(|(key, value)| {
+                let key = key.to_string();
+                quote! { #key => #value, }
+            });
+            let value = args.duration_value(&Ident::new("__measured_point", Span::call_site()));
+            (
+                Some(quote! {
+                    let __measured_points_on = std::sync::atomic::AtomicBool::new(false);
+                    let __measured_await_point =
+                        |__measured_name: &'static str, __measured_point: std::time::Duration| {
+                            if __measured_points_on.load(std::sync::atomic::Ordering::Relaxed) {
+                                metrics::histogram!(
+                                    #histogram,
+                                    #label_key => #metric_name,
+                                    #(#static_labels)*
+                                    "point" => __measured_name,
+                                )
+                                .record(#value);
+                            }
+                        };
+                }),
+                Some(quote! {
+                    __measured_points_on.store(true, std::sync::atomic::Ordering::Relaxed);
+                }),
+            )
+        }
+        false => (None, None),
+    };
+    let duration = Ident::new("__measured_duration", Span::call_site());
+    let value = args.duration_value(&duration);
+    let guard_struct = guard_struct();
+    let track_env = [prefix::track(), defaults::track(), strict::track()];
+    let calls = args.metric_name("function_calls_total");
+    let slow_calls = args.metric_name("function_slow_calls_total");
+    let inflight = args.metric_name("function_inflight");
+    let errors = args.metric_name("function_errors_total");
+    let interarrival = args.metric_name(&format!("function_interarrival_{}", args.unit.suffix()));
+    let started = args.metric_name("function_started_total");
+    let completed = args.metric_name("function_completed_total");
+    let cancelled = args.metric_name("function_cancelled_total");
+    let result_size = args.metric_name("function_result_size");
+    let response_bytes = args.metric_name("function_response_bytes");
+    let success_ratio = args.metric_name("function_success_ratio");
+    let error_rate = args.metric_name("function_error_rate");
+    let allocations_total = args.metric_name("function_allocations_total");
+    let last_duration = args.metric_name(&format!("function_last_duration_{}", args.unit.suffix()));
+    let ewma_gauge = args.metric_name(&format!("function_ewma_duration_{}", args.unit.suffix()));
+    let last_start = args.metric_name("function_last_start_seconds");
+    let last_end = args.metric_name("function_last_end_seconds");
+    let now = args.clock.now();
+
+    // `error_kind` implies `result`, so either flag needs a `Result` to inspect.
+    let result_flag = args
+        .result
+        .as_ref()
+        .or(args.error_kind.as_ref().map(|(flag, _)| flag));
+    if args.success_only && !returns_result(sig) {
+        return Err(syn::Error::new_spanned(
+            &sig.output,
+            format!("`#[measured_try_function]` requires `{fn_name}` to return a `Result`"),
+        ));
+    }
+    if let Some(flag) = result_flag.or(args.split_result.as_ref()) {
+        if !returns_result(sig) {
+            return Err(syn::Error::new(
+                flag.span(),
+                format!("`{flag}` requires `{fn_name}` to return a `Result`"),
+            ));
+        }
+    }
+
+    // `#[track_caller]` makes `Location::caller()` report the call site of the wrapper. It is
+    // only added if the function doesn't already carry it.
+    let (track_caller, location) = match &args.location {
+        Some(flag) => {
+            if !matches!(kind, FnKind::Sync) {
+                return Err(syn::Error::new(
+                    flag.span(),
+                    "`location` is not supported on async functions, \
+                     `#[track_caller]` does not carry over into the returned future",
+                ));
+            }
+            if let Some(abi) = &sig.abi {
+                return Err(syn::Error::new_spanned(
+                    abi,
+                    "`location` needs `#[track_caller]`, which requires the Rust ABI",
+                ));
+            }
+            (
+                if attrs.iter().any(|attr| is_attr(attr, "track_caller")) {
+                    TokenStream2::new()
+                } else {
+                    quote! { #[track_caller] }
+                },
+                quote! {
+                    let __measured_location = {
+                        let __measured_caller = std::panic::Location::caller();
+                        format!("{}:{}", __measured_caller.file(), __measured_caller.line())
+                    };
+                },
+            )
+        }
+        None => (TokenStream2::new(), TokenStream2::new()),
+    };
+
+    let type_label = type_label(&args, sig)?;
+    let label_from = label_from(&args, sig)?;
+    let correlation_from = correlation_from(&args, sig)?;
+    let skip_if = skip_if(&args, sig)?;
+
+    // With the `disabled` feature the function is emitted exactly as written, once the
+    // arguments have been validated so enabling the feature never hides a mistake.
+    if cfg!(feature = "disabled") {
+        return Ok(quote! { #input_fn });
+    }
+
+    // The labels shared by every metric of this function: its name plus any static labels.
+    let label_key = args.label_key();
+    let name_value = match label_from {
+        Some(_) => quote! { __measured_label.clone() },
+        None => metric_name.clone(),
+    };
+    let mut shared_labels = vec![(quote! { #label_key }, name_value)];
+    shared_labels.extend(args.labels.iter().map(|(key, value)| {
+        let key = key.to_string();
+        (quote! { #key }, quote! { #value })
+    }));
+    // Read by `env!` in the instrumented crate, so they name that crate and not this one.
+    if args.with_pkg_labels.is_some() {
+        shared_labels.push((quote! { "service" }, quote! { env!("CARGO_PKG_NAME") }));
+        shared_labels.push((quote! { "version" }, quote! { env!("CARGO_PKG_VERSION") }));
+    }
+    if args.with_profile_label.is_some() {
+        shared_labels.push((
+            quote! { "profile" },
+            quote! {
+                if cfg!(debug_assertions) {
+                    "debug"
+                } else {
+                    "release"
+                }
+            },
+        ));
+    }
+    // Expanded into the instrumented crate, so it's that crate's feature that is checked.
+    if let Some((_, feature)) = &args.path_label_from_cfg {
+        shared_labels.push((
+            quote! { "path" },
+            quote! {
+                if cfg!(feature = #feature) {
+                    "on"
+                } else {
+                    "off"
+                }
+            },
+        ));
+    }
+    // Counted in the signature as written, so a method's receiver isn't a parameter.
+    if args.arity.is_some() {
+        let arity = sig
+            .inputs
+            .iter()
+            .filter(|input| matches!(input, FnArg::Typed(_)))
+            .count()
+            .to_string();
+        shared_labels.push((quote! { "arity" }, quote! { #arity }));
+    }
+    if args.location.is_some() {
+        shared_labels.push((
+            quote! { "location" },
+            quote! { __measured_location.clone() },
+        ));
+    }
+    if args.depth_label.is_some() {
+        shared_labels.push((
+            quote! { "depth" },
+            quote! { __measured_depth_label.clone() },
+        ));
+    }
+    // Read each time a metric is recorded, from a static table of the 24 values.
+    if let Some((_, zone)) = &args.time_bucket {
+        let hour = match zone {
+            TimeZone::Utc => quote! { ::metrics_utils::hour::utc() },
+            TimeZone::Local => quote! { ::metrics_utils::hour::local() },
+        };
+        shared_labels.push((quote! { "hour" }, hour));
+    }
+    if correlation_from.is_some() {
+        shared_labels.push((
+            quote! { "correlation" },
+            quote! { __measured_correlation.clone() },
+        ));
+    }
+    shared_labels.extend(type_label);
+    let labels = label_list(&shared_labels);
+    // With `depth_label`, each call raises a per-function thread-local level for its duration
+    // and is labelled with the level it found, 0 for the outermost call. The level is lowered
+    // by a drop guard, so it is reset when a call unwinds as well.
+    let depth_label = args.depth_label.is_some().then(|| {
+        quote! {
+            std::thread_local! {
+                static __MEASURED_LEVEL: std::cell::Cell<u32> = const { std::cell::Cell::new(0) };
+            }
+
+            struct __MeasuredLevel;
+
+            impl Drop for __MeasuredLevel {
+                fn drop(&mut self) {
+                    __MEASURED_LEVEL.with(|level| level.set(level.get() - 1));
+                }
+            }
+
+            let __measured_depth_label = __MEASURED_LEVEL.with(|level| {
+                let __measured_level = level.get();
+                level.set(__measured_level + 1);
+                __measured_level.to_string()
+            });
+            let __measured_level = __MeasuredLevel;
+        }
+    });
+
+    // With `sample = N` only every N-th call is recorded, starting with the first. The
+    // decision is a single relaxed `fetch_add` on a counter local to this function, so
+    // skipped calls take no lock. `tail_sample` shares the counter but only advances it for
+    // calls under its threshold, once the duration is known.
+    let tail_sample = match &args.tail_sample {
+        Some(tail) => Some((
+            tail.threshold_ms.base10_parse::<u64>()?,
+            tail.sample.base10_parse::<u64>()?,
+        )),
+        None => None,
+    };
+    // With `warmup = N` the first N calls are left out on top of that, counted by a second
+    // per-function counter. Once they are over, it is only loaded, no longer written.
+    let (warmup, warm) = match &args.warmup {
+        Some(n) => {
+            let n: u64 = n.base10_parse()?;
+            let reset = resettable(quote! {
+                __MEASURED_WARMUP.store(0, std::sync::atomic::Ordering::Relaxed);
+            });
+            let warmup = quote! {
+                static __MEASURED_WARMUP: std::sync::atomic::AtomicU64 =
+                    std::sync::atomic::AtomicU64::new(0);
+                #reset
+                let __measured_warm = __MEASURED_WARMUP
+                    .load(std::sync::atomic::Ordering::Relaxed)
+                    >= #n
+                    || __MEASURED_WARMUP.fetch_add(1, std::sync::atomic::Ordering::Relaxed) >= #n;
+            };
+            (warmup, Some(quote! { __measured_warm && }))
+        }
+        None => (TokenStream2::new(), None),
+    };
+    let sampled = args.sample.is_some() || args.warmup.is_some();
+    let reset_calls = resettable(quote! {
+        __MEASURED_CALLS.store(0, std::sync::atomic::Ordering::Relaxed);
+    });
+    let sample = match &args.sample {
+        Some(n) => {
+            let n: u64 = n.base10_parse()?;
+            quote! {
+                #warmup
+                static __MEASURED_CALLS: std::sync::atomic::AtomicU64 =
+                    std::sync::atomic::AtomicU64::new(0);
+                #reset_calls
+                let __measured_sampled = #warm __MEASURED_CALLS
+                    .fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+                    % #n
+                    == 0;
+            }
+        }
+        None if args.tail_sample.is_some() => quote! {
+            #warmup
+            static __MEASURED_CALLS: std::sync::atomic::AtomicU64 =
+                std::sync::atomic::AtomicU64::new(0);
+            #reset_calls
+        },
+        None if args.warmup.is_some() => quote! {
+            #warmup
+            let __measured_sampled = __measured_warm;
+        },
+        None => TokenStream2::new(),
+    };
+
+    // With `busy`, the time spent inside `poll` is summed up next to the wall time.
+    let busy_histogram = args.metric_name(&format!("async_function_busy_{}", args.unit.suffix()));
+    let busy_value = args.duration_value(&Ident::new("__measured_busy", Span::call_site()));
+    // With `poll_count`, the polls of the future are counted the same way.
+    let poll_count = args.metric_name("async_function_poll_count");
+    // With `sync_prefix`, the time until the first `poll` returns is recorded as soon as it
+    // does, on every call.
+    let sync_prefix_histogram = args.metric_name(&format!(
+        "async_function_sync_prefix_{}",
+        args.unit.suffix()
+    ));
+    let sync_prefix = args.sync_prefix.is_some().then(|| {
+        let value = args.duration_value(&Ident::new("__measured_prefix", Span::call_site()));
+        let record = record_histogram(&args, &sync_prefix_histogram, &shared_labels, &value, None);
+        quote! {
+            if !__measured_polled {
+                __measured_polled = true;
+                let __measured_prefix = __measured_start.elapsed();
+                #record
+            }
+        }
+    });
+    // With `cpu_time`, the thread's CPU clock is read around the body as well. Platforms
+    // without one record nothing.
+    let cpu_histogram = args.metric_name(&format!("function_cpu_{}", args.unit.suffix()));
+    let cpu_value = args.duration_value(&Ident::new("__measured_cpu", Span::call_site()));
+    // With `utilization`, the CPU time is divided by the recorded wall time instead, which is
+    // left out when it is zero.
+    let utilization = args.metric_name("function_cpu_utilization");
+    // Checked on every call, sampled or not, against the duration that is also recorded. Slow
+    // calls are counted, and logged as well with the `tracing` feature.
+    let slow = match &args.slow_threshold_ms {
+        Some(threshold) => {
+            let threshold: u64 = threshold.base10_parse()?;
+            let warn = cfg!(feature = "tracing").then(|| {
+                quote! {
+                    tracing::warn!(
+                        function = #metric_name,
+                        elapsed_ms = __measured_duration.as_secs_f64() * 1000.0,
+                        threshold_ms = #threshold,
+                        "slow call",
+                    );
+                }
+            });
+            quote! {
+                if __measured_duration > std::time::Duration::from_millis(#threshold) {
+                    metrics::counter!(#slow_calls, #labels).increment(1);
+                    #warn
+                }
+            }
+        }
+        None => TokenStream2::new(),
+    };
+    // With `max_duration_ms`, a call over the budget panics in the instrumented crate's tests,
+    // or everywhere with the `test_util` feature, once it was recorded. A call that is already
+    // unwinding, and recorded from the guard, is left alone so the panic doesn't abort.
+    let budget = match &args.max_duration_ms {
+        Some(budget) => {
+            let budget: u64 = budget.base10_parse()?;
+            let enforced = match cfg!(feature = "test_util") {
+                true => quote! { true },
+                false => quote! { cfg!(test) },
+            };
+            quote! {
+                if #enforced
+                    && __measured_duration > std::time::Duration::from_millis(#budget)
+                    && !std::thread::panicking()
+                {
+                    panic!(
+                        "`{}` took {:?}, over its latency budget of {} ms",
+                        #metric_name,
+                        __measured_duration,
+                        #budget,
+                    );
+                }
+            }
+        }
+        None => TokenStream2::new(),
+    };
+    // With `log_level`, every call, sampled or not, is logged at that level with the duration
+    // that is also recorded.
+    let log = args.log_level.as_ref().map(|level| {
+        quote! {
+            tracing::event!(
+                tracing::Level::#level,
+                function = #metric_name,
+                elapsed_ms = __measured_duration.as_secs_f64() * 1000.0,
+                "call finished",
+            );
+        }
+    });
+    // With `span`, the call runs inside an `info_span!` named after the function, and the
+    // measured duration is recorded on it as `elapsed_ms` before it closes.
+    // With `record = "span"`, the duration replaces the histogram as a field named after the
+    // unit, on that span if there is one and on the caller's current span otherwise, which
+    // must declare the field for the value to be kept.
+    let span_field = args.unit.span_field();
+    let span_duration = args.record_span.as_ref().map(|_| match args.span {
+        Some(_) => quote! { __measured_span.record(#span_field, #value); },
+        None => quote! { tracing::Span::current().record(#span_field, #value); },
+    });
+    let (span_start, span_record) = match args.span {
+        Some(_) => {
+            let span_field = args
+                .record_span
+                .as_ref()
+                .map(|_| quote! { #span_field = tracing::field::Empty, });
+            (
+                quote! {
+                    let __measured_span = tracing::info_span!(
+                        #metric_name,
+                        elapsed_ms = tracing::field::Empty,
+                        #span_field
+                    );
+                },
+                quote! {
+                    __measured_span
+                        .record("elapsed_ms", __measured_duration.as_secs_f64() * 1000.0);
+                },
+            )
+        }
+        None => (TokenStream2::new(), TokenStream2::new()),
+    };
+    // With `last_duration`, a gauge holds the duration of the most recent call, whatever its
+    // outcome, so it only carries the shared labels.
+    let last = args.last_duration.is_some().then(|| {
+        quote! { metrics::gauge!(#last_duration, #labels).set(#value); }
+    });
+    // With `ewma`, the moving average lives in a per-function slot declared on entry. The first
+    // call starts it at its own duration, and each later one moves it by `alpha` of the
+    // difference.
+    let (ewma_state, ewma) = match args.ewma {
+        Some((_, alpha)) => (
+            Some({
+                let reset = resettable(quote! {
+                    *__MEASURED_EWMA
+                        .lock()
+                        .unwrap_or_else(std::sync::PoisonError::into_inner) = None;
+                });
+                quote! {
+                    static __MEASURED_EWMA: std::sync::Mutex<Option<f64>> =
+                        std::sync::Mutex::new(None);
+                    #reset
+                }
+            }),
+            Some(quote! {
+                let __measured_ewma = {
+                    let __measured_value: f64 = #value;
+                    let mut __measured_average = __MEASURED_EWMA
+                        .lock()
+                        .unwrap_or_else(std::sync::PoisonError::into_inner);
+                    let __measured_next = match *__measured_average {
+                        Some(__measured_previous) => {
+                            __measured_previous + #alpha * (__measured_value - __measured_previous)
+                        }
+                        None => __measured_value,
+                    };
+                    *__measured_average = Some(__measured_next);
+                    __measured_next
+                };
+                metrics::gauge!(#ewma_gauge, #labels).set(__measured_ewma);
+            }),
+        ),
+        None => (None, None),
+    };
+    // With `timestamps`, two gauges hold the wall-clock start and end of the most recent call
+    // in seconds since the Unix epoch, for lining it up with external logs. A clock set before
+    // the epoch leaves them unchanged.
+    let timestamps = args.timestamps.is_some().then(|| {
+        quote! {
+            if let (Ok(__measured_wall_start), Ok(__measured_wall_end)) = (
+                __measured_wall_start.duration_since(std::time::UNIX_EPOCH),
+                std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH),
+            ) {
+                metrics::gauge!(#last_start, #labels).set(__measured_wall_start.as_secs_f64());
+                metrics::gauge!(#last_end, #labels).set(__measured_wall_end.as_secs_f64());
+            }
+        }
+    });
+    // With `on_record`, the callback gets the name label's value and the recorded duration.
+    let on_record = args.on_record.as_ref().map(|callback| {
+        let label = match args.label_from {
+            Some(_) => quote! { &__measured_label },
+            None => metric_name.clone(),
+        };
+        quote! { #callback(#label, #value); }
+    });
+    // With `after`, the block runs next to it with the duration bound to the `expose_as` name.
+    // Its statements are spliced into a block that holds the binding, without their own braces,
+    // which would trip `unused_braces`.
+    let after = args.after.as_ref().map(|(_, block)| {
+        let binding = &args.expose_as;
+        let stmts = &block.stmts;
+        quote! {
+            {
+                let #binding: f64 = #value;
+                #(#stmts)*
+            }
+        }
+    });
+    let record_labelled = |name: &str, extra: &[(TokenStream2, TokenStream2)]| {
+        let labels = [shared_labels.as_slice(), extra].concat();
+        let busy = args.busy.is_some().then(|| {
+            let record_busy = record_histogram(&args, &busy_histogram, &labels, &busy_value, None);
+            quote! {
+                let __measured_busy = std::time::Duration::from_nanos(
+                    __measured_busy_nanos.load(std::sync::atomic::Ordering::Relaxed),
+                );
+                #record_busy
+            }
+        });
+        let polls = args.poll_count.is_some().then(|| {
+            let labels = label_list(&labels);
+            quote! {
+                metrics::histogram!(#poll_count, #labels).record(
+                    __measured_polls.load(std::sync::atomic::Ordering::Relaxed) as f64,
+                );
+            }
+        });
+        let cpu = (args.cpu_time.is_some() || args.utilization.is_some()).then(|| {
+            let labels = label_list(&labels);
+            let histogram = args.cpu_time.as_ref().map(|_| {
+                quote! { metrics::histogram!(#cpu_histogram, #labels).record(#cpu_value); }
+            });
+            let utilization = args.utilization.as_ref().map(|_| {
+                quote! {
+                    if !__measured_duration.is_zero() {
+                        metrics::gauge!(#utilization, #labels).set(
+                            __measured_cpu.as_secs_f64() / __measured_duration.as_secs_f64(),
+                        );
+                    }
+                }
+            });
+            quote! {
+                if let (Some(__measured_cpu_start), Some(__measured_cpu_end)) =
+                    (__measured_cpu_start, ::metrics_utils::cpu::thread_time())
+                {
+                    let __measured_cpu = __measured_cpu_end.saturating_sub(__measured_cpu_start);
+                    #histogram
+                    #utilization
+                }
+            }
+        });
+        let allocations = args.alloc_count.is_some().then(|| {
+            let labels = label_list(&labels);
+            quote! {
+                metrics::counter!(#allocations_total, #labels).increment(__measured_allocations);
+            }
+        });
+        let record_duration = record_value(
+            &args,
+            name,
+            &labels,
+            &duration,
+            &value,
+            args.exemplar_from.as_ref(),
+        );
+        // With `handle`, the sample goes into the pre-resolved histogram, and through the
+        // lookup above only while the handle is unset.
+        let record_duration = match &args.handle {
+            Some(handle) => quote! {
+                match ::metrics_utils::HistogramHandle::histogram(&#handle) {
+                    Some(__measured_histogram) => __measured_histogram.record(#value),
+                    None => { #record_duration }
+                }
+            },
+            None => record_duration,
+        };
+        // With `summary`, the same sample also goes into the quantile window for its labels.
+        let summary = args.summary.is_some().then(|| {
+            let labels = sample_labels(&args, &labels);
+            quote! {
+                ::metrics_utils::summary::record(#name, &#labels, #value);
+            }
+        });
+        // With `ringbuffer`, it is kept in the function's buffer of recent durations as well.
+        let ringbuffer = args.ringbuffer.as_ref().map(|capacity| {
+            quote! {
+                ::metrics_utils::ring::record(#metric_name, #capacity, #value);
+            }
+        });
+        // With `no_histogram`, only the opt-in metrics around the sample are recorded.
+        let record_duration = match (&args.no_histogram, &span_duration) {
+            (Some(_), _) => TokenStream2::new(),
+            (None, Some(span_duration)) => span_duration.clone(),
+            (None, None) => record_duration,
+        };
+        let record_also = also_record.iter().map(|name| {
+            record_value(
+                &args,
+                name,
+                &labels,
+                &duration,
+                &value,
+                args.exemplar_from.as_ref(),
+            )
+        });
+        let record_dual = dual_unit.as_ref().map(|(name, unit)| {
+            record_value(
+                &args,
+                name,
+                &labels,
+                &duration,
+                &unit.convert(&duration),
+                args.exemplar_from.as_ref(),
+            )
+        });
+        let record_duration = quote! {
+            #record_duration
+            #record_dual
+            #(#record_also)*
+            #summary
+            #ringbuffer
+        };
+        let elapsed = match excludes {
+            true => quote! {
+                let __measured_duration = __measured_start.elapsed().saturating_sub(
+                    std::time::Duration::from_nanos(
+                        __measured_excluded.load(std::sync::atomic::Ordering::Relaxed),
+                    ),
+                );
+            },
+            false if regions => quote! {
+                let __measured_duration = std::time::Duration::from_nanos(
+                    __measured_region.load(std::sync::atomic::Ordering::Relaxed),
+                );
+            },
+            false => quote! { let __measured_duration = __measured_start.elapsed(); },
+        };
+        // The allocation count is read right away, before recording can allocate.
+        let elapsed = match args.alloc_count {
+            Some(_) => quote! {
+                #elapsed
+                let __measured_allocations = ::metrics_utils::alloc::thread_allocations()
+                    .wrapping_sub(__measured_alloc_start);
+            },
+            None => elapsed,
+        };
+        // The slow check, the log event, the last call gauges and the span field apply to every
+        // call, sampled or not.
+        let every_call = args.slow_threshold_ms.is_some()
+            || args.max_duration_ms.is_some()
+            || args.log_level.is_some()
+            || args.last_duration.is_some()
+            || args.ewma.is_some()
+            || args.timestamps.is_some()
+            || args.span.is_some();
+        // With `min_duration_ms`, calls under the floor are left out of what is recorded, after
+        // the clock has been read all the same.
+        let recorded = quote! {
+            #record_duration
+            #busy
+            #polls
+            #cpu
+            #allocations
+            #on_record
+            #after
+        };
+        let recorded = match &args.min_duration_ms {
+            Some(floor) => quote! {
+                if __measured_duration >= std::time::Duration::from_millis(#floor) {
+                    #recorded
+                }
+            },
+            None => recorded,
+        };
+        if let Some((threshold, n)) = tail_sample {
+            // Slow calls short-circuit, so only the faster calls advance the counter.
+            return quote! {
+                #elapsed
+                let __measured_sampled = #warm (__measured_duration
+                    > std::time::Duration::from_millis(#threshold)
+                    || __MEASURED_CALLS.fetch_add(1, std::sync::atomic::Ordering::Relaxed) % #n
+                        == 0);
+                if __measured_sampled {
+                    #recorded
+                }
+                #slow
+                #budget
+                #log
+                #last
+                #ewma
+                #timestamps
+                #span_record
+            };
+        }
+        match (sampled, every_call) {
+            // Skipped calls don't even read the clock unless something else needs it.
+            (true, false) => quote! {
+                if __measured_sampled {
+                    #elapsed
+                    #recorded
+                }
+            },
+            (true, true) => quote! {
+                #elapsed
+                if __measured_sampled {
+                    #recorded
+                }
+                #slow
+                #budget
+                #log
+                #last
+                #ewma
+                #timestamps
+                #span_record
+            },
+            (false, _) => quote! {
+                #elapsed
+                #recorded
+                #slow
+                #budget
+                #log
+                #last
+                #ewma
+                #timestamps
+                #span_record
+            },
+        }
+    };
+
+    // With `label_from_context`, the accessor's value labels the duration and everything
+    // recorded along with it. With `task_id_label`, so does the id of the Tokio task the call
+    // completes in, read when it is recorded. Calls without one are recorded without the label,
+    // which takes a copy of the recording for either case.
+    let mut optional_labels = Vec::new();
+    if args.label_from_context.is_some() {
+        let key = args.context_key();
+        optional_labels.push(OptionalLabel {
+            key: quote! { #key },
+            read: quote! { &__measured_context },
+            binding: quote! { __measured_context },
+            value: quote! { __measured_context.clone() },
+        });
+    }
+    if args.task_id_label.is_some() {
+        optional_labels.push(OptionalLabel {
+            key: quote! { "task_id" },
+            read: quote! { tokio::task::try_id() },
+            binding: quote! { __measured_task_id },
+            value: quote! { __measured_task_id.to_string() },
+        });
+    }
+    let record_to = |name: &str, extra: &[(TokenStream2, TokenStream2)]| {
+        with_optional_labels(&optional_labels, extra.to_vec(), &|labels| {
+            record_labelled(name, labels)
+        })
+    };
+    let record = |extra: &[(TokenStream2, TokenStream2)]| record_to(&histogram_name, extra);
+
+    // Described once per instrumented function, on its first call.
+    let describe = args.describe.as_ref().map(|(_, text)| {
+        let text = match text {
+            Some(text) => quote! { #text },
+            // A constant name isn't a literal, so it can't go through `concat!`.
+            None => match &args.custom_name {
+                Some(name @ (CustomName::Path(_) | CustomName::ConstFn(_))) => {
+                    quote! { format!("Execution time of {}", #name) }
+                }
+                _ => quote! { concat!("Execution time of ", #metric_name) },
+            },
+        };
+        // Values from a `convert` function are in a unit of its own, so none is registered.
+        let unit = match args.convert {
+            Some(_) => TokenStream2::new(),
+            None => {
+                let unit = args.unit.metrics_unit();
+                quote! { #unit, }
+            }
+        };
+        let describe = match args.instrument {
+            Instrument::Histogram => quote! { describe_histogram },
+            Instrument::Counter => quote! { describe_counter },
+            Instrument::Gauge => quote! { describe_gauge },
+        };
+        quote! {
+            static __MEASURED_DESCRIBED: std::sync::Once = std::sync::Once::new();
+            __MEASURED_DESCRIBED.call_once(|| {
+                #(metrics::#describe!(#histogram_names, #unit #text);)*
+            });
+        }
+    });
+
+    // Registered once per instrumented function so exporters can pick the buckets up.
+    let buckets = args.buckets.as_ref().map(|buckets| {
+        let buckets = quote! { &[#(#buckets),*] };
+        quote! {
+            static __MEASURED_BUCKETS: std::sync::Once = std::sync::Once::new();
+            __MEASURED_BUCKETS.call_once(|| {
+                #(::metrics_utils::buckets::register(#histogram_names, #buckets);)*
+            });
+        }
+    });
+
+    // With the `warn_no_recorder` feature the first instrumented call of the process checks
+    // for a recorder. Later calls only see the completed `Once`.
+    let recorder_check = cfg!(feature = "warn_no_recorder").then(|| {
+        let report = match cfg!(feature = "tracing") {
+            true => quote! { |message| tracing::warn!("{}", message) },
+            false => quote! { |message| eprintln!("warning: {}", message) },
+        };
+        quote! { ::metrics_utils::recorder::check(#report); }
+    });
+
+    // With the `catalog` feature every function lists its histogram on the first call.
+    let catalog = (cfg!(feature = "catalog")
+        && args.no_histogram.is_none()
+        && args.record_span.is_none())
+    .then(|| {
+        let unit = args.unit.suffix();
+        let label = match args.label_from {
+            Some(_) => quote! { None },
+            None => quote! { Some(#metric_name) },
+        };
+        let function = fn_name.unraw().to_string();
+        quote! {
+            static __MEASURED_CATALOGUED: std::sync::Once = std::sync::Once::new();
+            __MEASURED_CATALOGUED.call_once(|| {
+                #(::metrics_utils::catalog::register(::metrics_utils::catalog::MetricDescriptor {
+                    name: #histogram_names,
+                    unit: #unit,
+                    label_key: #label_key,
+                    label: #label,
+                    function: #function,
+                    module_path: module_path!(),
+                });)*
+            });
+        }
+    });
+
+    // Counted on entry so every call is counted exactly once, whether it returns or unwinds.
+    let count = args.count.is_some().then(|| {
+        quote! {
+            metrics::counter!(#calls, #labels).increment(1);
+        }
+    });
+
+    // With `interarrival`, each call swaps its start time into a per-function slot and records
+    // the gap since the previous call. The lock serializes concurrent callers, so every gap is
+    // between two consecutive entries; the first call has no gap to record.
+    let interarrival = args.interarrival.as_ref().map(|_| {
+        let instant = args.clock.instant();
+        let gap = args.duration_value(&Ident::new("__measured_gap", Span::call_site()));
+        let reset = resettable(quote! {
+            *__MEASURED_LAST_CALL
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner) = None;
+        });
+        quote! {
+            static __MEASURED_LAST_CALL: std::sync::Mutex<Option<#instant>> =
+                std::sync::Mutex::new(None);
+            #reset
+            let __measured_gap = {
+                let __measured_now = #now;
+                let mut __measured_last = __MEASURED_LAST_CALL
+                    .lock()
+                    .unwrap_or_else(std::sync::PoisonError::into_inner);
+                __measured_last
+                    .replace(__measured_now)
+                    .map(|__measured_previous| __measured_now.duration_since(__measured_previous))
+            };
+            if let Some(__measured_gap) = __measured_gap {
+                metrics::histogram!(#interarrival, #labels).record(#gap);
+            }
+        }
+    });
+
+    // Raised on entry and lowered on every exit path, so cancelled and panicking calls
+    // don't leave the gauge permanently elevated.
+    let (inflight_inc, inflight_dec) = match args.inflight {
+        Some(_) => (
+            quote! { metrics::gauge!(#inflight, #labels).increment(1.0); },
+            quote! { metrics::gauge!(#inflight, #labels).decrement(1.0); },
+        ),
+        None => (TokenStream2::new(), TokenStream2::new()),
+    };
+
+    // Records a call that returned. With `result` the `status` label follows the returned
+    // `Result`, and with `error_kind` an `Err` also carries an `error_kind` label, so the two
+    // outcomes are recorded from separate match arms.
+    // With `label_variant`, returned values are labelled with their variant, read by reference
+    // so the value is returned untouched. The trait call is spanned at the flag, so a type
+    // without variant names is reported there.
+    let variant = args.label_variant.as_ref().map(|flag| {
+        let name = quote_spanned! {flag.span()=>
+            ::metrics_utils::VariantName::variant_name(&__measured_result)
+        };
+        (quote! { "variant" }, name)
+    });
+    let returned = |extra: &[(TokenStream2, TokenStream2)]| {
+        let with_status = |status: TokenStream2, kind: Option<TokenStream2>| {
+            let mut labels = vec![(quote! { "status" }, status)];
+            labels.extend_from_slice(extra);
+            labels.extend(kind.map(|kind| (quote! { "error_kind" }, kind)));
+            record(&labels)
+        };
+        if args.success_only {
+            // Only successful calls reach the histogram; errors are just counted, with their
+            // kind if `error_kind` is set.
+            let ok = with_status(quote! { "ok" }, None);
+            let (binding, kind, kind_label) = match &args.error_kind {
+                Some((_, error_kind)) => {
+                    let value = error_kind_value(error_kind);
+                    (
+                        quote! { __measured_error },
+                        quote! { let __measured_error_kind = #value; },
+                        quote! { "error_kind" => __measured_error_kind, },
+                    )
+                }
+                None => (quote! { _ }, TokenStream2::new(), TokenStream2::new()),
+            };
+            return quote! {
+                match &__measured_result {
+                    Ok(_) => { #ok }
+                    Err(#binding) => {
+                        #kind
+                        metrics::counter!(#errors, #labels #kind_label).increment(1);
+                    }
+                }
+            };
+        }
+        if let Some((ok_name, err_name)) = &split_names {
+            // The outcome is in the histogram's name, so there is no `status` label.
+            let ok = record_to(ok_name, extra);
+            let (binding, kind, kind_label) = match &args.error_kind {
+                Some((_, error_kind)) => {
+                    let value = error_kind_value(error_kind);
+                    (
+                        quote! { __measured_error },
+                        quote! { let __measured_error_kind = #value; },
+                        Some((quote! { "error_kind" }, quote! { __measured_error_kind })),
+                    )
+                }
+                None => (quote! { _ }, TokenStream2::new(), None),
+            };
+            let err = record_to(err_name, &[extra, kind_label.as_slice()].concat());
+            return quote! {
+                match &__measured_result {
+                    Ok(_) => { #ok }
+                    Err(#binding) => {
+                        #kind
+                        #err
+                    }
+                }
+            };
+        }
+        match (&args.error_kind, result_flag) {
+            (Some((_, error_kind)), _) => {
+                let ok = with_status(quote! { "ok" }, None);
+                let err = with_status(quote! { "err" }, Some(quote! { __measured_error_kind }));
+                let error_kind = error_kind_value(error_kind);
+                quote! {
+                    match &__measured_result {
+                        Ok(_) => { #ok }
+                        Err(__measured_error) => {
+                            let __measured_error_kind = #error_kind;
+                            #err
+                        }
+                    }
+                }
+            }
+            (None, Some(_)) => with_status(
+                quote! {
+                    match &__measured_result {
+                        Ok(_) => "ok",
+                        Err(_) => "err",
+                    }
+                },
+                None,
+            ),
+            (None, None) => with_status(quote! { "ok" }, None),
+        }
+    };
+
+    // A sync body is inlined so `?` and `return` act on the function exactly as written. Unless
+    // the recording depends on the returned value, through a `Result` status, `record_len`,
+    // `record_serialized_len`, `success_ratio`, `windowed_error_rate` or `label_variant`, the
+    // guard then records every exit, telling returns from panics with `thread::panicking()`,
+    // compared with its value on entry so a call made by a `Drop` during unwinding still
+    // returns as `ok`. A body whose value is inspected can only be inlined if it has no early
+    // exits, which would skip the recording after it, and is called as a closure otherwise.
+    let result_aware = result_flag.is_some()
+        || args.split_result.is_some()
+        || args.success_only
+        || args.record_len.is_some()
+        || args.record_serialized_len.is_some()
+        || args.success_ratio.is_some()
+        || args.windowed_error_rate.is_some()
+        || args.label_variant.is_some();
+    let records_on_drop = matches!(kind, FnKind::Sync) && !result_aware;
+    let unwinding =
+        records_on_drop.then(|| quote! { let __measured_unwinding = std::thread::panicking(); });
+    let panicked = match records_on_drop {
+        true => quote! { (std::thread::panicking() && !__measured_unwinding) },
+        false => quote! { std::thread::panicking() },
+    };
+    let (eval, on_drop, on_return) = match kind {
+        FnKind::Sync => {
+            // The statements are spliced in without the body's own braces, which would
+            // otherwise trip `unused_braces` at the user's code.
+            // The closure moves the parameters in and is called once, so a returned `&mut`
+            // borrowed from one is a reborrow it can hand out, as the function itself could. A
+            // closure called in place is `FnMut`, which ties such a result to the closure.
+            let stmts = &block.stmts;
+            let body = if result_aware && has_early_exit(block) {
+                quote! {
+                    {
+                        fn __measured_call_once<R>(f: impl FnOnce() -> R) -> R {
+                            f()
+                        }
+                        __measured_call_once(move || #block)
+                    }
+                }
+            } else if result_aware {
+                quote! { { #(#stmts)* } }
+            } else {
+                quote! { #(#stmts)* }
+            };
+            let eval = match args.span {
+                Some(_) => quote! {
+                    {
+                        let __measured_entered = __measured_span.enter();
+                        #body
+                    }
+                },
+                None => body,
+            };
+            let on_drop = if records_on_drop {
+                let record = record(&[(quote! { "status" }, quote! { __measured_status })]);
+                quote! {
+                    let __measured_status = if #panicked { "panic" } else { "ok" };
+                    #record
+                }
+            } else {
+                record(&[(quote! { "status" }, quote! { "panic" })])
+            };
+            (eval, on_drop, returned(variant.as_slice()))
+        }
+        FnKind::Async | FnKind::Future | FnKind::BoxedFuture => {
+            let future = match kind {
+                FnKind::Future | FnKind::BoxedFuture => quote! { __measured_future },
+                _ => quote! { async move #block },
+            };
+            // `Instrument` enters the span on every poll, so it follows the future across
+            // `.await` points and threads instead of staying entered while suspended.
+            let future = match args.span {
+                Some(_) => quote! {
+                    tracing::Instrument::instrument(#future, __measured_span.clone())
+                },
+                None => future,
+            };
+            let stmts = &block.stmts;
+            let polled = args.busy.is_some() || args.poll_count.is_some() || sync_prefix.is_some();
+            let eval = match polled {
+                // A plain `async fn` body without early exits runs in place, in the function's
+                // own future, so the timing adds no nested future. `return` or `?` would leave
+                // the function before the recording, so such a body stays in a block of its own,
+                // as it does when `span`, `busy`, `poll_count` or `sync_prefix` need a future to
+                // wrap.
+                false
+                    if matches!(kind, FnKind::Async)
+                        && args.span.is_none()
+                        && !has_early_exit(block) =>
+                {
+                    quote! { { #(#stmts)* } }
+                }
+                // The future is polled through `poll_fn` so each `poll` can be timed. The total
+                // is kept in an atomic rather than a `Cell` so the function's future stays
+                // `Send`.
+                true => {
+                    let poll_start = args
+                        .busy
+                        .is_some()
+                        .then(|| quote! { let __measured_poll_start = #now; });
+                    let busy = args.busy.is_some().then(|| {
+                        quote! {
+                            __measured_busy_nanos.fetch_add(
+                                __measured_poll_start.elapsed().as_nanos() as u64,
+                                std::sync::atomic::Ordering::Relaxed,
+                            );
+                        }
+                    });
+                    let count = args.poll_count.is_some().then(|| {
+                        quote! {
+                            __measured_polls.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                        }
+                    });
+                    let polled = sync_prefix
+                        .is_some()
+                        .then(|| quote! { let mut __measured_polled = false; });
+                    quote! {
+                        {
+                            let mut __measured_inner = std::pin::pin!(#future);
+                            #polled
+                            std::future::poll_fn(|__measured_cx| {
+                                #poll_start
+                                let __measured_poll = std::future::Future::poll(
+                                    __measured_inner.as_mut(),
+                                    __measured_cx,
+                                );
+                                #busy
+                                #count
+                                #sync_prefix
+                                __measured_poll
+                            })
+                            .await
+                        }
+                    }
+                }
+                false => quote! { #future.await },
+            };
+            let on_drop = record(&[
+                (quote! { "status" }, quote! { __measured_status }),
+                (quote! { "completion" }, quote! { __measured_completion }),
+            ]);
+            (
+                eval,
+                quote! {
+                    let (__measured_status, __measured_completion) = if std::thread::panicking() {
+                        ("panic", "completed")
+                    } else {
+                        ("cancelled", "cancelled")
+                    };
+                    #on_drop
+                },
+                returned(
+                    &[
+                        &[(quote! { "completion" }, quote! { "completed" })],
+                        variant.as_slice(),
+                    ]
+                    .concat(),
+                ),
+            )
+        }
+    };
+
+    // Unwinding and cancelled calls are neither successes nor returned errors, so nothing is
+    // recorded for them in success-only mode or with `split_result` beyond lowering the
+    // `inflight` gauge.
+    let on_drop = if args.success_only || args.split_result.is_some() {
+        TokenStream2::new()
+    } else {
+        on_drop
+    };
+
+    // With `lifecycle`, starts are counted on entry, and every start later ends up as a
+    // completion, a cancellation or, for calls that unwind, neither.
+    let (lifecycle_start, lifecycle_returned, lifecycle_dropped) = match args.lifecycle {
+        Some(_) => {
+            let completed = quote! { metrics::counter!(#completed, #labels).increment(1); };
+            let dropped = match kind {
+                FnKind::Sync if records_on_drop => completed.clone(),
+                FnKind::Sync => TokenStream2::new(),
+                _ => quote! { metrics::counter!(#cancelled, #labels).increment(1); },
+            };
+            (
+                quote! { metrics::counter!(#started, #labels).increment(1); },
+                completed,
+                (!dropped.is_empty()).then(|| {
+                    quote! {
+                        if !#panicked {
+                            #dropped
+                        }
+                    }
+                }),
+            )
+        }
+        None => (TokenStream2::new(), TokenStream2::new(), None),
+    };
+
+    let cpu_start = (args.cpu_time.is_some() || args.utilization.is_some()).then(|| {
+        quote! { let __measured_cpu_start = ::metrics_utils::cpu::thread_time(); }
+    });
+    // Read once per call, before the clock starts. Spanned at the accessor, so one of the
+    // wrong type is reported there.
+    let context = args.label_from_context.as_ref().map(|(_, accessor)| {
+        let read = quote_spanned! {syn::spanned::Spanned::span(accessor)=>
+            let __measured_context: std::option::Option<std::string::String> = #accessor();
+        };
+        let key = args.context_key();
+        let capped = capped(&args, "__measured_context", |max| {
+            quote! {
+                __measured_context.map(|__measured_value| {
+                    __MEASURED_CARDINALITY.admit(#key, __measured_value, #max)
+                })
+            }
+        });
+        quote! {
+            #read
+            #capped
+        }
+    });
+    // The runtime labels are read once per call too, and are merged into every duration sample.
+    let labels_from = args.labels_from.as_ref().map(|provider| {
+        let read = quote_spanned! {syn::spanned::Spanned::span(provider)=>
+            let __measured_labels_from: std::vec::Vec<metrics::Label> =
+                ::metrics_utils::LabelSet::into_labels(#provider());
+        };
+        let capped = capped(&args, "__measured_labels_from", |max| {
+            quote! {
+                __measured_labels_from
+                    .into_iter()
+                    .map(|__measured_label| __MEASURED_CARDINALITY.admit_label(__measured_label, #max))
+                    .collect::<std::vec::Vec<_>>()
+            }
+        });
+        quote! {
+            #read
+            #capped
+        }
+    });
+    let wall_start = args.timestamps.is_some().then(|| {
+        quote! { let __measured_wall_start = std::time::SystemTime::now(); }
+    });
+    let alloc_start = args.alloc_count.is_some().then(|| {
+        quote! { let __measured_alloc_start = ::metrics_utils::alloc::thread_allocations(); }
+    });
+    let busy_start = args.busy.is_some().then(|| {
+        quote! { let __measured_busy_nanos = std::sync::atomic::AtomicU64::new(0); }
+    });
+    let polls_start = args.poll_count.is_some().then(|| {
+        quote! { let __measured_polls = std::sync::atomic::AtomicU64::new(0); }
+    });
+
+    // A future's label is read before the body runs and moves the parameters into the future.
+    let label_from = quote! { #label_from #correlation_from };
+    let (outer_label_from, inner_label_from) = match kind {
+        FnKind::Future | FnKind::BoxedFuture => (Some(label_from), None),
+        _ => (None, Some(label_from)),
+    };
+    // With `record_len`, the size of the returned collection is recorded next to the duration.
+    // The trait call is spanned at the flag, so an unsupported type is reported there.
+    let record_len = args.record_len.as_ref().map(|flag| {
+        let len = quote_spanned! {flag.span()=>
+            ::metrics_utils::MeasuredLen::measured_len(&__measured_result)
+        };
+        let record = quote! {
+            if let Some(__measured_len) = #len {
+                metrics::histogram!(#result_size, #labels).record(__measured_len as f64);
+            }
+        };
+        match sampled {
+            true => quote! {
+                if __measured_sampled {
+                    #record
+                }
+            },
+            false => record,
+        }
+    });
+    // With `record_serialized_len`, the serializer is called on the returned value by reference
+    // and its byte count is recorded the same way, after the duration was taken.
+    let record_serialized_len = args.record_serialized_len.as_ref().map(|serializer| {
+        let record = quote! {
+            metrics::histogram!(#response_bytes, #labels)
+                .record(#serializer(&__measured_result) as f64);
+        };
+        match sampled {
+            true => quote! {
+                if __measured_sampled {
+                    #record
+                }
+            },
+            false => record,
+        }
+    });
+    // With `success_ratio`, every returned call, sampled or not, is counted in a per-function
+    // pair of totals. The ratio is computed under the same lock that updates them, so each
+    // published value is a consistent snapshot. The `Result::is_ok` call is spanned at the
+    // flag, so a function returning something else is reported there.
+    let success_ratio = args.success_ratio.as_ref().map(|flag| {
+        let is_ok = quote_spanned! {flag.span()=>
+            std::result::Result::is_ok(&__measured_result)
+        };
+        let reset = resettable(quote! {
+            *__MEASURED_OUTCOMES
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner) = (0, 0);
+        });
+        quote! {
+            static __MEASURED_OUTCOMES: std::sync::Mutex<(u64, u64)> =
+                std::sync::Mutex::new((0, 0));
+            #reset
+            let __measured_ratio = {
+                let mut __measured_outcomes = __MEASURED_OUTCOMES
+                    .lock()
+                    .unwrap_or_else(std::sync::PoisonError::into_inner);
+                let (__measured_ok, __measured_total) = &mut *__measured_outcomes;
+                *__measured_ok += u64::from(#is_ok);
+                *__measured_total += 1;
+                *__measured_ok as f64 / *__measured_total as f64
+            };
+            metrics::gauge!(#success_ratio, #labels).set(__measured_ratio);
+        }
+    });
+    // With `windowed_error_rate`, every returned call is counted in a per-function window of
+    // coarse time buckets instead, which publishes the share of errors among its calls. The
+    // `Result::is_err` call is spanned at the window for the same reason.
+    let error_rate = args.windowed_error_rate.as_ref().map(|(window, millis)| {
+        let is_err = quote_spanned! {window.span()=>
+            std::result::Result::is_err(&__measured_result)
+        };
+        let reset = resettable(quote! { __MEASURED_ERROR_WINDOW.clear(); });
+        quote! {
+            static __MEASURED_ERROR_WINDOW: ::metrics_utils::window::ErrorWindow =
+                ::metrics_utils::window::ErrorWindow::new(std::time::Duration::from_millis(#millis));
+            #reset
+            metrics::gauge!(#error_rate, #labels).set(__MEASURED_ERROR_WINDOW.record(#is_err));
+        }
+    });
+    // A function returning `!` or `Infallible` never returns normally, so there is nothing to
+    // record after the body and the guard is left to record a panic. Without the dead return
+    // path the expansion adds no unreachable code of its own. The same goes for a sync body
+    // whose guard records every exit.
+    let returning = if records_on_drop || diverges(sig) {
+        eval
+    } else {
+        // A body that diverges without saying so in its return type, e.g. one that is just
+        // `todo!()`, leaves the recording after it unreachable, which is no fault of the user's.
+        quote! {
+            let __measured_result = #eval;
+            #[allow(unreachable_code)]
+            let __measured_result = {
+                std::mem::forget(__measured_guard);
+                #inflight_dec
+                #lifecycle_returned
+                #on_return
+                #record_len
+                #record_serialized_len
+                #success_ratio
+                #error_rate
+                __measured_result
+            };
+            __measured_result
+        }
+    };
+    let body = quote! {
+        #(#track_env)*
+        #guard_struct
+        #location
+        #depth_label
+        #inner_label_from
+        #describe
+        #buckets
+        #catalog
+        #recorder_check
+        #count
+        #interarrival
+        #ewma_state
+        #lifecycle_start
+        #inflight_inc
+        #sample
+        #span_start
+        #busy_start
+        #polls_start
+        #cpu_start
+        #alloc_start
+        #context
+        #labels_from
+        #wall_start
+        #unwinding
+        let __measured_start = #now;
+        #await_point_on
+        let __measured_guard = __MeasuredGuard(|| {
+            #inflight_dec
+            #lifecycle_dropped
+            #on_drop
+        });
+        #returning
+    };
+    // With `enabled_if`, the predicate is called on the first call only. While it is false, or
+    // for calls whose `skip_if` parameter is `true`, nothing is recorded: the original body
+    // runs as written, or the returned future is awaited or returned as is. With
+    // `top_level_only`, the same goes for calls made while an outer call of the same function
+    // is running on this thread, tracked by a per-function thread-local depth. With
+    // `skip_in_test`, `cfg!(test)` is expanded in the instrumented crate, so calls are left
+    // out of its test builds only and the constant condition folds away in the others.
+    let mut declarations = Vec::new();
+    let mut conditions = Vec::new();
+    if args.skip_in_test.is_some() {
+        conditions.push(quote! { !cfg!(test) });
+    }
+    // With the `runtime_switch` feature, every call also checks the process-wide switch.
+    if cfg!(feature = "runtime_switch") {
+        conditions.push(quote! { ::metrics_utils::switch::is_enabled() });
+    }
+    if let Some(skip) = skip_if {
+        conditions.push(quote! { !#skip });
+    }
+    if args.top_level_only.is_some() {
+        declarations.push(quote! {
+            std::thread_local! {
+                static __MEASURED_DEPTH: std::cell::Cell<u32> = const { std::cell::Cell::new(0) };
+            }
+        });
+        conditions.push(quote! { __MEASURED_DEPTH.with(std::cell::Cell::get) == 0 });
+    }
+    if let Some(predicate) = &args.enabled_if {
+        declarations.push(quote! {
+            static __MEASURED_ENABLED: std::sync::OnceLock<bool> = std::sync::OnceLock::new();
+        });
+        conditions.push(quote! { *__MEASURED_ENABLED.get_or_init(#predicate) });
+    }
+    let enabled = (!conditions.is_empty()).then(|| {
+        quote! {
+            #(#declarations)*
+            let __measured_enabled = #(#conditions)&&*;
+        }
+    });
+    // The outermost call raises the depth for its duration. The depth is lowered by a drop
+    // guard, so it is reset when the call unwinds as well.
+    let depth = args.top_level_only.is_some().then(|| {
+        quote! {
+            struct __MeasuredDepth;
+
+            impl Drop for __MeasuredDepth {
+                fn drop(&mut self) {
+                    __MEASURED_DEPTH.with(|depth| depth.set(depth.get() - 1));
+                }
+            }
+
+            __MEASURED_DEPTH.with(|depth| depth.set(depth.get() + 1));
+            let __measured_depth = __MeasuredDepth;
+        }
+    });
+    // The future returned by the original body is awaited inside a new `async move` block, so
+    // the timer starts on its first poll and covers its execution, not just its construction.
+    let body = match kind {
+        FnKind::Future => {
+            let skip = enabled.is_some().then(|| {
+                quote! {
+                    if !__measured_enabled {
+                        return __measured_future.await;
+                    }
+                }
+            });
+            quote! {
+                #enabled
+                #outer_label_from
+                let __measured_future = (|| #block)();
+                async move {
+                    #skip
+                    #body
+                }
+            }
+        }
+        // Boxed again so the wrapper still matches the declared `Pin<Box<dyn Future>>`. A plain
+        // `Box<dyn Future>` is pinned to be awaited, and the wrapper is boxed without pinning.
+        FnKind::BoxedFuture => {
+            let skip = enabled.is_some().then(|| {
+                quote! {
+                    if !__measured_enabled {
+                        return __measured_future;
+                    }
+                }
+            });
+            let (pin, rebox) = match boxed_future(sig) {
+                Some(false) => (
+                    Some(quote! { let __measured_future = Box::into_pin(__measured_future); }),
+                    quote! { Box::new },
+                ),
+                _ => (None, quote! { Box::pin }),
+            };
+            quote! {
+                #enabled
+                #outer_label_from
+                let __measured_future = (|| #block)();
+                #skip
+                #pin
+                #rebox(async move { #body })
+            }
+        }
+        FnKind::Sync | FnKind::Async => match enabled {
+            Some(enabled) => {
+                let stmts = &block.stmts;
+                quote! {
+                    #(#hoisted)*
+                    #enabled
+                    if __measured_enabled {
+                        #depth
+                        #body
+                    } else {
+                        #(#stmts)*
+                    }
+                }
+            }
+            None => body,
+        },
+    };
+
+    // The signature is kept as written, including `unsafe`, the ABI and generics, so callers
+    // and function pointers see the same function.
+    Ok(quote! {
+        #(#attrs)*
+        #track_caller
+        #vis #sig {
+            #exclude_total
+            #region_total
+            #await_point_setup
+            #body
+        }
+    })
+}
+
+/// Whether `attr` is `#[name]`, or `#[unsafe(name)]` for attributes such as `no_mangle` and
+/// `naked` that are written with `unsafe(...)` since the 2024 edition.
+fn is_attr(attr: &Attribute, name: &str) -> bool {
+    if attr.path().is_ident(name) {
+        return true;
+    }
+    match &attr.meta {
+        Meta::List(list) if list.path.is_ident("unsafe") => list
+            .parse_args::<Path>()
+            .is_ok_and(|path| path.is_ident(name)),
+        _ => false,
+    }
+}
+
+/// The `error_kind` label value for the error bound to `__measured_error`.
+fn error_kind_value(error_kind: &ErrorKind) -> TokenStream2 {
+    match error_kind {
+        // `type_name_of_val` gives the full path, e.g. `my_crate::error::TimeoutError`; the
+        // module path is cut off, keeping any generic arguments as they are.
+        ErrorKind::TypeName => quote! {
+            {
+                let __measured_type_name = std::any::type_name_of_val(__measured_error);
+                let __measured_path_end = __measured_type_name
+                    .find('<')
+                    .unwrap_or(__measured_type_name.len());
+                let __measured_name_start = __measured_type_name[..__measured_path_end]
+                    .rfind("::")
+                    .map_or(0, |index| index + 2);
+                &__measured_type_name[__measured_name_start..]
+            }
+        },
+        // Spanned at `display`, so a non-`Display` error is reported at the argument rather
+        // than somewhere inside the expansion.
+        ErrorKind::Display(mode) => quote_spanned! {mode.span()=>
+            {
+                fn __measured_display<E: std::fmt::Display + ?Sized>(error: &E) -> &E {
+                    error
+                }
+                format!("{}", __measured_display(__measured_error))
+            }
+        },
+    }
+}
+
+/// Reads the `label_from` parameter into `__measured_label` before the body can move it.
+fn label_from(args: &MacroArgs, sig: &Signature) -> syn::Result<Option<TokenStream2>> {
+    let Some(param) = &args.label_from else {
+        return Ok(None);
+    };
+    // An `Option` parameter is labelled with its value, or `none` without one. With
+    // `label_from_present` only which of the two it is goes into the label.
+    let ty = typed_param(sig, param)?;
+    let value = match (&args.label_from_present, is_option(ty)) {
+        (Some(_), true) => quote! {
+            match &#param {
+                Some(_) => "some".to_owned(),
+                None => "none".to_owned(),
+            }
+        },
+        (Some(key), false) => {
+            return Err(syn::Error::new(
+                key.span(),
+                format!("`label_from_present` requires `{param}` to be an `Option`"),
+            ));
+        }
+        (None, true) => quote! {
+            match &#param {
+                Some(__measured_value) => std::string::ToString::to_string(__measured_value),
+                None => "none".to_owned(),
+            }
+        },
+        (None, false) => quote! { std::string::ToString::to_string(&#param) },
+    };
+    let key = args.label_key();
+    let capped = capped(args, "__measured_label", |max| {
+        quote! { __MEASURED_CARDINALITY.admit(#key, __measured_label, #max) }
+    });
+    Ok(Some(quote! {
+        let __measured_label = #value;
+        #capped
+    }))
+}
+
+/// Reads the `correlation_from` parameter into `__measured_correlation` the same way. The
+/// `ToString` call is spanned at the parameter, so one that isn't `Display` is reported there.
+fn correlation_from(args: &MacroArgs, sig: &Signature) -> syn::Result<Option<TokenStream2>> {
+    let Some(param) = &args.correlation_from else {
+        return Ok(None);
+    };
+    typed_param(sig, param)?;
+    let value = quote_spanned! {param.span()=>
+        std::string::ToString::to_string(&#param)
+    };
+    let capped = capped(args, "__measured_correlation", |max| {
+        quote! { __MEASURED_CARDINALITY.admit("correlation", __measured_correlation, #max) }
+    });
+    Ok(Some(quote! {
+        let __measured_correlation = #value;
+        #capped
+    }))
+}
+
+/// With `max_cardinality`, rebinds the runtime label values in `binding` to the result of
+/// `admit`, which replaces a value once its label has seen the maximum number of values. Each
+/// source of runtime labels keeps its own set of values in a `static` next to it.
+fn capped(
+    args: &MacroArgs,
+    binding: &str,
+    admit: impl FnOnce(&LitInt) -> TokenStream2,
+) -> Option<TokenStream2> {
+    let max = args.max_cardinality.as_ref()?;
+    let binding = Ident::new(binding, Span::call_site());
+    let admit = admit(max);
+    let reset = resettable(quote! { __MEASURED_CARDINALITY.clear(); });
+    Some(quote! {
+        let #binding = {
+            static __MEASURED_CARDINALITY: ::metrics_utils::cardinality::Cardinality =
+                ::metrics_utils::cardinality::Cardinality::new();
+            #reset
+            #admit
+        };
+    })
+}
+
+/// With the `test_util` feature, registers `reset` with `metrics_utils::reset` the first time
+/// it is reached, for the per-function `static` it resets. The statements must only name
+/// statics, so the closure captures nothing and coerces to a `fn()`.
+fn resettable(reset: TokenStream2) -> Option<TokenStream2> {
+    cfg!(feature = "test_util").then(|| {
+        quote! {
+            {
+                static __MEASURED_RESET: std::sync::Once = std::sync::Once::new();
+                __MEASURED_RESET.call_once(|| ::metrics_utils::reset::register(|| { #reset }));
+            }
+        }
+    })
+}
+
+/// Whether `ty` is an `Option`, possibly behind references.
+fn is_option(ty: &Type) -> bool {
+    match ty {
+        Type::Reference(reference) => is_option(&reference.elem),
+        Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "Option"),
+        _ => false,
+    }
+}
+
+/// Checks that the `skip_if` parameter is a `bool`, returning the condition that skips the
+/// instrumentation.
+fn skip_if(args: &MacroArgs, sig: &Signature) -> syn::Result<Option<TokenStream2>> {
+    let Some(param) = &args.skip_if else {
+        return Ok(None);
+    };
+    let ty = typed_param(sig, param)?;
+    if !matches!(ty, Type::Path(path) if path.qself.is_none() && path.path.is_ident("bool")) {
+        return Err(syn::Error::new_spanned(
+            ty,
+            format!("`skip_if` requires `{param}` to be a `bool`"),
+        ));
+    }
+    Ok(Some(quote! { #param }))
+}
+
+/// The type of the parameter named `param`, or an error if `sig` has none.
+fn typed_param<'a>(sig: &'a Signature, param: &Ident) -> syn::Result<&'a Type> {
+    sig.inputs
+        .iter()
+        .find_map(|input| match input {
+            FnArg::Typed(typed) => match typed.pat.as_ref() {
+                Pat::Ident(pat) if pat.ident == *param => Some(typed.ty.as_ref()),
+                _ => None,
+            },
+            FnArg::Receiver(_) => None,
+        })
+        .ok_or_else(|| {
+            syn::Error::new(
+                param.span(),
+                format!("`{}` has no parameter named `{param}`", sig.ident),
+            )
+        })
+}
+
+/// A label that only some calls have, read into `binding` by matching `read` against `Some`.
+struct OptionalLabel {
+    key: TokenStream2,
+    read: TokenStream2,
+    binding: TokenStream2,
+    value: TokenStream2,
+}
+
+/// Renders `record` for every combination of the optional labels being present or not, so
+/// each call is recorded with the ones it has, after `labels`.
+fn with_optional_labels(
+    optional: &[OptionalLabel],
+    labels: Vec<(TokenStream2, TokenStream2)>,
+    record: &impl Fn(&[(TokenStream2, TokenStream2)]) -> TokenStream2,
+) -> TokenStream2 {
+    let Some((label, rest)) = optional.split_first() else {
+        return record(&labels);
+    };
+    let without = with_optional_labels(rest, labels.clone(), record);
+    let mut labels = labels;
+    labels.push((label.key.clone(), label.value.clone()));
+    let with = with_optional_labels(rest, labels, record);
+    let OptionalLabel { read, binding, .. } = label;
+    quote! {
+        match #read {
+            Some(#binding) => { #with }
+            None => { #without }
+        }
+    }
+}
+
+/// Renders labels as the `key => value,` list taken by the `metrics` macros.
+fn label_list(labels: &[(TokenStream2, TokenStream2)]) -> TokenStream2 {
+    let (keys, values): (Vec<_>, Vec<_>) = labels.iter().cloned().unzip();
+    quote! { #(#keys => #values,)* }
+}
+
+/// The labels of a duration sample as an expression that a `&[metrics::Label]` can borrow.
+///
+/// With `labels_from`, the labels returned for the call follow the ones set by the macro. A
+/// returned label whose key the macro already sets is left out, so `function` and the other
+/// keys can't be doubled.
+fn sample_labels(args: &MacroArgs, labels: &[(TokenStream2, TokenStream2)]) -> TokenStream2 {
+    let (keys, values): (Vec<_>, Vec<_>) = labels.iter().cloned().unzip();
+    match args.labels_from {
+        Some(_) => quote! {
+            {
+                let mut __measured_labels = vec![#(metrics::Label::new(#keys, #values)),*];
+                __measured_labels.extend(
+                    __measured_labels_from
+                        .iter()
+                        .filter(|__measured_label| ![#(#keys),*].contains(&__measured_label.key()))
+                        .cloned(),
+                );
+                __measured_labels
+            }
+        },
+        None => quote! { [#(metrics::Label::new(#keys, #values)),*] },
+    }
+}
+
+/// The labels of a duration sample as the arguments following the name in a `metrics` macro.
+fn macro_labels(args: &MacroArgs, labels: &[(TokenStream2, TokenStream2)]) -> TokenStream2 {
+    match args.labels_from {
+        Some(_) => sample_labels(args, labels),
+        None => label_list(labels),
+    }
+}
+
+/// Records `value` to the histogram `name`, through the `sink` if one was given, through
+/// `metrics_utils::global::GlobalSink` with the `global_sink` feature, through
+/// `metrics_utils::otel::OtelSink` with the `otel` feature, through
+/// `metrics_utils::buffer::BufferedSink` with the `buffered` feature and with
+/// `metrics::histogram!` otherwise.
+///
+/// The sink is called through the `DurationSink` trait on a concrete value, so it is
+/// statically dispatched.
+///
+/// With `exemplar`, the provider is called for the sample and its trace id, if any, is handed
+/// to the sink along with it. Only a sink can take one, the `metrics` facade has no exemplars.
+fn record_histogram(
+    args: &MacroArgs,
+    name: &str,
+    labels: &[(TokenStream2, TokenStream2)],
+    value: &TokenStream2,
+    exemplar: Option<&Path>,
+) -> TokenStream2 {
+    let sink = match &args.sink {
+        Some(sink) => Some(quote! { #sink }),
+        None if cfg!(feature = "global_sink") => {
+            Some(quote! { ::metrics_utils::global::GlobalSink })
+        }
+        None if cfg!(feature = "otel") => Some(quote! { ::metrics_utils::otel::OtelSink }),
+        None if cfg!(feature = "buffered") => {
+            Some(quote! { ::metrics_utils::buffer::BufferedSink })
+        }
+        None => None,
+    };
+    match sink {
+        Some(sink) => {
+            let labels = sample_labels(args, labels);
+            if let Some(exemplar) = exemplar {
+                return quote! {
+                    ::metrics_utils::DurationSink::record_duration_with_exemplar(
+                        &#sink,
+                        #name,
+                        &#labels,
+                        #value,
+                        std::option::Option::<std::string::String>::as_deref(&#exemplar()),
+                    );
+                };
+            }
+            quote! {
+                ::metrics_utils::DurationSink::record_duration(&#sink, #name, &#labels, #value);
+            }
+        }
+        None => {
+            let labels = macro_labels(args, labels);
+            quote! { metrics::histogram!(#name, #labels).record(#value); }
+        }
+    }
+}
+
+/// Records the duration `value` to `name` with the instrument chosen by `as = "..."`.
+///
+/// Histograms go through `record_histogram`. Counters and gauges have no sink and are always
+/// recorded with the `metrics` facade. A counter is incremented by whole units, and the
+/// fraction left over is kept per label set in a static map and added to the next call with
+/// the same labels, so calls shorter than a unit still add up to the right total in every
+/// series. With `value_type = "u64"` the whole nanoseconds are taken straight from `duration`,
+/// so they aren't rounded through an `f64`.
+fn record_value(
+    args: &MacroArgs,
+    name: &str,
+    labels: &[(TokenStream2, TokenStream2)],
+    duration: &Ident,
+    value: &TokenStream2,
+    exemplar: Option<&Path>,
+) -> TokenStream2 {
+    match args.instrument {
+        Instrument::Histogram => record_histogram(args, name, labels, value, exemplar),
+        Instrument::Counter => match args.integer {
+            Some(_) => {
+                let labels = macro_labels(args, labels);
+                let increment = args.integer_value(duration);
+                quote! { metrics::counter!(#name, #labels).increment(#increment); }
+            }
+            None => {
+                let labels = sample_labels(args, labels);
+                quote! {
+                    {
+                        // The fraction of a unit not yet counted, for each label set of the
+                        // counter.
+                        static __MEASURED_REMAINDER: std::sync::Mutex<
+                            std::option::Option<
+                                std::collections::HashMap<std::vec::Vec<metrics::Label>, f64>,
+                            >,
+                        > = std::sync::Mutex::new(None);
+                        let __measured_labels = std::vec::Vec::from(#labels);
+                        let __measured_increment = {
+                            let mut __measured_remainders = __MEASURED_REMAINDER
+                                .lock()
+                                .unwrap_or_else(std::sync::PoisonError::into_inner);
+                            let __measured_remainder = __measured_remainders
+                                .get_or_insert_with(std::collections::HashMap::new)
+                                .entry(__measured_labels.clone())
+                                .or_insert(0.0);
+                            let __measured_total = *__measured_remainder + (#value);
+                            let __measured_increment = __measured_total as u64;
+                            *__measured_remainder = __measured_total - __measured_increment as f64;
+                            __measured_increment
+                        };
+                        metrics::counter!(#name, __measured_labels).increment(__measured_increment);
+                    }
+                }
+            }
+        },
+        Instrument::Gauge => {
+            let labels = macro_labels(args, labels);
+            quote! { metrics::gauge!(#name, #labels).set(#value); }
+        }
+    }
+}
+
+/// Checks the resolved metric names and the label keys against the naming rules of `strict`.
+/// Names written in the attribute are reported at their literal, generated ones at the
+/// `strict` flag, or at the attribute when the `strict` feature turned the check on.
+fn check_strict(
+    args: &MacroArgs,
+    histogram_names: &[String],
+    also_record: &[String],
+) -> syn::Result<()> {
+    let flag_span = args
+        .strict
+        .as_ref()
+        .map_or_else(Span::call_site, Ident::span);
+    let metric_span = args.metric.as_ref().map_or(flag_span, syn::LitStr::span);
+    for name in histogram_names {
+        strict::check_metric(name, metric_span)?;
+    }
+    for (name, lit) in also_record.iter().zip(&args.also_record) {
+        strict::check_metric(name, lit.span())?;
+    }
+    let label_key_span = args.label_key.as_ref().map_or(flag_span, syn::LitStr::span);
+    strict::check_label_key(&args.label_key(), label_key_span)?;
+    if let Some((option, _)) = &args.label_from_context {
+        let span = args
+            .context_key
+            .as_ref()
+            .map_or(option.span(), syn::LitStr::span);
+        strict::check_label_key(&args.context_key(), span)?;
+    }
+    for (key, _) in &args.labels {
+        strict::check_label_key(&key.to_string(), key.span())?;
+    }
+    Ok(())
+}
+
+/// The `"type" => std::any::type_name::<T>()` label added by `monomorphized`, or nothing.
+///
+/// `T` is the parameter named by `type_param`, or the first generic type parameter. The value
+/// is computed inside each instantiation, so every instantiation reports its own type.
+fn type_label(
+    args: &MacroArgs,
+    sig: &Signature,
+) -> syn::Result<Option<(TokenStream2, TokenStream2)>> {
+    let span = match (&args.monomorphized, &args.type_param) {
+        (Some(flag), _) => flag.span(),
+        (None, Some(name)) => name.span(),
+        (None, None) => return Ok(None),
+    };
+    let fn_name = &sig.ident;
+    let param = match &args.type_param {
+        Some(name) => sig
+            .generics
+            .type_params()
+            .find(|param| param.ident == name.value())
+            .ok_or_else(|| {
+                syn::Error::new(
+                    name.span(),
+                    format!("`{fn_name}` has no type parameter `{}`", name.value()),
+                )
+            })?,
+        None => sig.generics.type_params().next().ok_or_else(|| {
+            syn::Error::new(
+                span,
+                format!("`monomorphized` requires `{fn_name}` to have a generic type parameter"),
+            )
+        })?,
+    };
+    if args.label_key() == "type" {
+        return Err(syn::Error::new(
+            span,
+            "`monomorphized` sets the `type` label, which is already used as `label_key`",
+        ));
+    }
+    let ident = &param.ident;
+    Ok(Some((
+        quote! { "type" },
+        quote! { std::any::type_name::<#ident>() },
+    )))
+}
+
+/// Whether the declared return type is spelled as a `Result`, including paths such as
+/// `io::Result<T>` or `anyhow::Result<T>`.
+fn returns_result(sig: &Signature) -> bool {
+    match &sig.output {
+        ReturnType::Type(_, ty) => match ty.as_ref() {
+            Type::Path(path) => path
+                .path
+                .segments
+                .last()
+                .is_some_and(|segment| segment.ident == "Result"),
+            _ => false,
+        },
+        ReturnType::Default => false,
+    }
+}
+
+/// Whether `block` may leave the function before its end, through `return`, `?` or a macro
+/// that could expand to either.
+///
+/// Closures, async blocks and nested items have their own exits and are skipped. Macros from
+/// the standard library are only checked for a `return` or `?` written in their arguments,
+/// while any other macro, such as `anyhow::bail!`, is assumed to return early.
+fn has_early_exit(block: &Block) -> bool {
+    /// Standard library macros that evaluate their arguments in place.
+    const INLINE_MACROS: &[&str] = &[
+        "assert",
+        "assert_eq",
+        "assert_ne",
+        "debug_assert",
+        "debug_assert_eq",
+        "debug_assert_ne",
+        "dbg",
+        "eprint",
+        "eprintln",
+        "exclude",
+        "format",
+        "format_args",
+        "matches",
+        "panic",
+        "print",
+        "println",
+        "region",
+        "todo",
+        "unimplemented",
+        "unreachable",
+        "vec",
+        "write",
+        "writeln",
+    ];
+
+    struct Visitor(bool);
+
+    impl<'ast> Visit<'ast> for Visitor {
+        fn visit_expr_return(&mut self, _: &'ast syn::ExprReturn) {
+            self.0 = true;
+        }
+
+        fn visit_expr_try(&mut self, _: &'ast syn::ExprTry) {
+            self.0 = true;
+        }
+
+        fn visit_expr_closure(&mut self, _: &'ast syn::ExprClosure) {}
+
+        fn visit_expr_async(&mut self, _: &'ast syn::ExprAsync) {}
+
+        fn visit_item(&mut self, _: &'ast syn::Item) {}
+
+        fn visit_macro(&mut self, mac: &'ast syn::Macro) {
+            let inline = mac
+                .path
+                .get_ident()
+                .is_some_and(|ident| INLINE_MACROS.iter().any(|name| ident == name));
+            if !inline || tokens_exit(mac.tokens.clone()) {
+                self.0 = true;
+            }
+        }
+    }
+
+    fn tokens_exit(tokens: TokenStream2) -> bool {
+        tokens.into_iter().any(|token| match token {
+            TokenTree::Ident(ident) => ident == "return",
+            TokenTree::Punct(punct) => punct.as_char() == '?',
+            TokenTree::Group(group) => tokens_exit(group.stream()),
+            TokenTree::Literal(_) => false,
+        })
+    }
+
+    let mut visitor = Visitor(false);
+    visitor.visit_block(block);
+    visitor.0
+}
+
+/// Whether `tokens` invoke the macro `name`, such as `exclude!`, at any depth.
+fn uses_macro(tokens: TokenStream2, name: &str) -> bool {
+    let mut tokens = tokens.into_iter().peekable();
+    while let Some(token) = tokens.next() {
+        match token {
+            TokenTree::Ident(ident) if ident == name => {
+                if matches!(tokens.peek(), Some(TokenTree::Punct(punct)) if punct.as_char() == '!')
+                {
+                    return true;
+                }
+            }
+            TokenTree::Group(group) if uses_macro(group.stream(), name) => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Splits `named_inner`'s function into an outer signature, with the `mut` of its parameters
+/// dropped, and a body that defines `__measured_inner_<name>` with the original signature and
+/// body and forwards every parameter to it. The nested function doesn't see the generics of an
+/// enclosing `impl`, so methods, `Self` and patterns that can't be forwarded by name are
+/// rejected, as are the macros that only work in the body of the instrumented function itself.
+fn named_inner(flag: &Ident, sig: &Signature, block: &Block) -> syn::Result<(Signature, Block)> {
+    let fn_name = &sig.ident;
+    if let Some(FnArg::Receiver(receiver)) = sig.inputs.first() {
+        return Err(syn::Error::new_spanned(
+            receiver,
+            format!(
+                "`named_inner` moves the body of `{fn_name}` into a nested function, which \
+                 can't take `self`"
+            ),
+        ));
+    }
+    if mentions_self(sig.to_token_stream()) || mentions_self(block.to_token_stream()) {
+        return Err(syn::Error::new_spanned(
+            flag,
+            format!(
+                "`named_inner` moves the body of `{fn_name}` into a nested function, which \
+                 can't refer to `Self`"
+            ),
+        ));
+    }
+    for name in ["exclude", "region", "await_point"] {
+        if uses_macro(block.to_token_stream(), name) {
+            return Err(syn::Error::new_spanned(
+                flag,
+                format!("`{name}!` can't be used in the body of a `named_inner` function"),
+            ));
+        }
+    }
+
+    let inner_name = format_ident!("__measured_inner_{}", fn_name.unraw());
+    let mut outer = sig.clone();
+    let mut forwarded = Vec::new();
+    for input in &mut outer.inputs {
+        if let FnArg::Typed(typed) = input {
+            match typed.pat.as_mut() {
+                Pat::Ident(pat) if pat.by_ref.is_none() && pat.subpat.is_none() => {
+                    pat.mutability = None;
+                    forwarded.push(pat.ident.clone());
+                }
+                pat => {
+                    return Err(syn::Error::new_spanned(
+                        &*pat,
+                        "`named_inner` forwards parameters by name, so each one has to be a \
+                         plain identifier",
+                    ));
+                }
+            }
+        }
+    }
+    let inner_sig = Signature {
+        constness: None,
+        abi: None,
+        ident: inner_name.clone(),
+        ..sig.clone()
+    };
+
+    // Lifetimes are left to inference; type and const parameters are passed explicitly, since
+    // one that only appears in the return type couldn't be inferred.
+    let generics: Vec<_> = sig
+        .generics
+        .params
+        .iter()
+        .filter_map(|param| match param {
+            GenericParam::Type(param) => Some(&param.ident),
+            GenericParam::Const(param) => Some(&param.ident),
+            GenericParam::Lifetime(_) => None,
+        })
+        .collect();
+    let turbofish = (!generics.is_empty()).then(|| quote! { ::<#(#generics),*> });
+    let mut call = quote! { #inner_name #turbofish(#(#forwarded),*) };
+    if sig.unsafety.is_some() {
+        call = quote! { unsafe { #call } };
+    }
+    if sig.asyncness.is_some() {
+        call = quote! { #call.await };
+    }
+    let block = syn::parse2(quote! {{
+        #inner_sig #block
+        #call
+    }})?;
+    Ok((outer, block))
+}
+
+/// Whether `tokens` use `Self` anywhere, including inside groups.
+fn mentions_self(tokens: TokenStream2) -> bool {
+    tokens.into_iter().any(|token| match token {
+        TokenTree::Ident(ident) => ident == "Self",
+        TokenTree::Group(group) => mentions_self(group.stream()),
+        _ => false,
+    })
+}
+
+/// Whether the declared return type is `!` or `Infallible`, so the function can't return.
+fn diverges(sig: &Signature) -> bool {
+    match &sig.output {
+        ReturnType::Type(_, ty) => match ty.as_ref() {
+            Type::Never(_) => true,
+            Type::Path(path) => path
+                .path
+                .segments
+                .last()
+                .is_some_and(|segment| segment.ident == "Infallible"),
+            _ => false,
+        },
+        ReturnType::Default => false,
+    }
+}
+
+/// Whether the declared return type is `impl Future<...>`, possibly with extra bounds such as
+/// `+ Send`.
+pub(crate) fn returns_impl_future(sig: &Signature) -> bool {
+    match &sig.output {
+        ReturnType::Type(_, ty) => match ty.as_ref() {
+            Type::ImplTrait(impl_trait) => impl_trait.bounds.iter().any(|bound| match bound {
+                TypeParamBound::Trait(bound) => bound
+                    .path
+                    .segments
+                    .last()
+                    .is_some_and(|segment| segment.ident == "Future"),
+                _ => false,
+            }),
+            _ => false,
+        },
+        ReturnType::Default => false,
+    }
+}
+
+/// Whether the declared return type is `Pin<Box<dyn Future<...>>>`, the shape `#[async_trait]`
+/// gives its methods, or a plain `Box<dyn Future<...>>`, possibly with extra bounds such as
+/// `+ Send + 'a`.
+pub(crate) fn returns_boxed_future(sig: &Signature) -> bool {
+    boxed_future(sig).is_some()
+}
+
+/// Whether a boxed future return is pinned, `Some(false)` for `Box<dyn Future>`, or `None` if
+/// the function doesn't return a boxed future.
+fn boxed_future(sig: &Signature) -> Option<bool> {
+    let ReturnType::Type(_, ty) = &sig.output else {
+        return None;
+    };
+    let (pinned, boxed) = match single_generic_arg(ty, "Pin") {
+        Some(pinned) => (true, single_generic_arg(pinned, "Box")?),
+        None => (false, single_generic_arg(ty, "Box")?),
+    };
+    let Type::TraitObject(object) = boxed else {
+        return None;
+    };
+    object
+        .bounds
+        .iter()
+        .any(|bound| match bound {
+            TypeParamBound::Trait(bound) => bound
+                .path
+                .segments
+                .last()
+                .is_some_and(|segment| segment.ident == "Future"),
+            _ => false,
+        })
+        .then_some(pinned)
+}
+
+/// The type argument of `ty` if it is a path ending in `name<Arg>`, e.g. the `T` of
+/// `std::pin::Pin<T>` for `"Pin"`.
+fn single_generic_arg<'a>(ty: &'a Type, name: &str) -> Option<&'a Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    if segment.ident != name {
+        return None;
+    }
+    let PathArguments::AngleBracketed(arguments) = &segment.arguments else {
+        return None;
+    };
+    arguments.args.iter().find_map(|argument| match argument {
+        GenericArgument::Type(ty) => Some(ty),
+        _ => None,
+    })
+}
+
+/// Emits the local drop guard type used to record calls that unwind or are cancelled.
+///
+/// The guard runs its closure when dropped. On a normal return the generated code
+/// `mem::forget`s it after recording, so a call is never recorded twice.
+fn guard_struct() -> TokenStream2 {
+    quote! {
+        struct __MeasuredGuard<F: FnMut()>(F);
+
+        impl<F: FnMut()> Drop for __MeasuredGuard<F> {
+            fn drop(&mut self) {
+                (self.0)()
+            }
+        }
+    }
+}
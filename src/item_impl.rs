@@ -0,0 +1,139 @@
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, ToTokens};
+use syn::ext::IdentExt;
+use syn::{Attribute, ImplItem, ImplItemFn, ItemFn, ItemImpl, LitStr, Meta, Type};
+
+use crate::args::MacroArgs;
+use crate::expand::{expand, FnKind};
+
+/// The attributes that already instrument a function on their own.
+const MEASURED_ATTRIBUTES: &[&str] = &[
+    "measured",
+    "measured_function",
+    "measured_async_function",
+    "measured_future",
+    "measured_boxed_future",
+    "measured_try_function",
+];
+
+/// Whether `attr` is one of this crate's function attributes, however it was imported.
+pub(crate) fn is_measured_attr(attr: &Attribute) -> bool {
+    attr.path()
+        .segments
+        .last()
+        .is_some_and(|segment| MEASURED_ATTRIBUTES.iter().any(|name| segment.ident == name))
+}
+
+/// Generates `#[measured_impl]`: every non-`const` method or associated function of the impl
+/// block is wrapped as if it carried `#[measured]`, labelled `Type::method`.
+///
+/// Methods that already carry one of the function attributes are left for that attribute to
+/// expand, so they aren't wrapped twice, and so are methods marked `#[skip_measure]`.
+/// `#[measure_as("...")]` replaces a method's `Type::method` label. Both markers are removed
+/// here. Associated constants, types and macros pass through unchanged.
+pub(crate) fn expand_impl(args: MacroArgs, mut item_impl: ItemImpl) -> syn::Result<TokenStream2> {
+    if let Some(name) = &args.custom_name {
+        return Err(syn::Error::new(
+            name.span(),
+            "`#[measured_impl]` labels each method as `Type::method` and does not take a custom name",
+        ));
+    }
+    let type_name = match &args.type_name {
+        Some(name) => name.value(),
+        None => type_name(&item_impl.self_ty),
+    };
+
+    for item in &mut item_impl.items {
+        let ImplItem::Fn(method) = item else {
+            continue;
+        };
+        let (skipped, label) = take_markers(&mut method.attrs)?;
+        let wrapped = method.sig.constness.is_none() && !method.attrs.iter().any(is_measured_attr);
+        if let (Some(label), false) = (&label, wrapped && !skipped) {
+            return Err(syn::Error::new(
+                label.span(),
+                format!(
+                    "`#[measure_as]` relabels a method wrapped by `#[measured_impl]`, which `{}` \
+                     is not",
+                    method.sig.ident
+                ),
+            ));
+        }
+        if !wrapped || skipped {
+            continue;
+        }
+
+        let mut method_args = args.clone();
+        method_args.default_name = Some(match label {
+            Some(label) => label.value(),
+            None => format!("{type_name}::{}", method.sig.ident.unraw()),
+        });
+        *method = wrap_method(method_args, method)?;
+    }
+
+    Ok(item_impl.into_token_stream())
+}
+
+/// Removes the `#[skip_measure]` and `#[measure_as("...")]` markers from a method's
+/// attributes, returning whether it is skipped and the label it is given instead.
+fn take_markers(attrs: &mut Vec<Attribute>) -> syn::Result<(bool, Option<LitStr>)> {
+    let mut skipped = false;
+    let mut label: Option<LitStr> = None;
+    let mut index = 0;
+    while index < attrs.len() {
+        let attr = &attrs[index];
+        if attr.path().is_ident("skip_measure") {
+            if !matches!(attr.meta, Meta::Path(_)) {
+                return Err(syn::Error::new_spanned(
+                    attr,
+                    "`#[skip_measure]` takes no arguments",
+                ));
+            }
+            skipped = true;
+        } else if attr.path().is_ident("measure_as") {
+            let lit: LitStr = attr.parse_args()?;
+            if lit.value().is_empty() {
+                return Err(syn::Error::new(lit.span(), "the label must not be empty"));
+            }
+            if let Some(existing) = &label {
+                return Err(syn::Error::new(
+                    lit.span(),
+                    format!("the label is already set to {:?}", existing.value()),
+                ));
+            }
+            label = Some(lit);
+        } else {
+            index += 1;
+            continue;
+        }
+        attrs.remove(index);
+    }
+    Ok((skipped, label))
+}
+
+/// Runs a single method through the shared generator and turns the result back into an
+/// impl item.
+fn wrap_method(args: MacroArgs, method: &ImplItemFn) -> syn::Result<ImplItemFn> {
+    let input_fn = ItemFn {
+        attrs: method.attrs.clone(),
+        vis: method.vis.clone(),
+        sig: method.sig.clone(),
+        block: Box::new(method.block.clone()),
+    };
+    let kind = FnKind::detect(&input_fn);
+    let mut wrapped: ImplItemFn = syn::parse2(expand(args, input_fn, kind)?)?;
+    wrapped.defaultness = method.defaultness;
+    Ok(wrapped)
+}
+
+/// The name used for the `Type::method` labels: the last path segment of the implementing
+/// type without its generics, or its tokens for types that aren't paths.
+fn type_name(ty: &Type) -> String {
+    match ty {
+        Type::Path(path) => match path.path.segments.last() {
+            Some(segment) => segment.ident.to_string(),
+            None => quote!(#ty).to_string(),
+        },
+        _ => quote!(#ty).to_string().replace(' ', ""),
+    }
+}
@@ -0,0 +1,60 @@
+use proc_macro2::TokenStream as TokenStream2;
+use quote::ToTokens;
+use syn::ext::IdentExt;
+use syn::{Item, ItemMod};
+
+use crate::args::MacroArgs;
+use crate::expand::{expand, FnKind};
+use crate::item_impl::is_measured_attr;
+
+/// Generates `#[measured_mod]`: every non-`const` function directly inside the inline module
+/// is wrapped as if it carried `#[measured]`, labelled `module::function`.
+///
+/// Like `#[measured_impl]`, functions that already carry one of the function attributes are
+/// left for that attribute to expand. Nested modules, impl blocks and every other item pass
+/// through unchanged.
+pub(crate) fn expand_mod(args: MacroArgs, mut item_mod: ItemMod) -> syn::Result<TokenStream2> {
+    if let Some(name) = &args.custom_name {
+        return Err(syn::Error::new(
+            name.span(),
+            "`#[measured_mod]` labels each function as `module::function` and does not take a \
+             custom name",
+        ));
+    }
+    if let Some(type_name) = &args.type_name {
+        return Err(syn::Error::new(
+            type_name.span(),
+            "`#[measured_mod]` labels each function as `module::function` and does not take \
+             `type_name`",
+        ));
+    }
+    let Some((_, items)) = &mut item_mod.content else {
+        return Err(syn::Error::new(
+            item_mod
+                .semi
+                .map_or(item_mod.ident.span(), |semi| semi.span),
+            "`#[measured_mod]` needs the module's items, declare it inline as `mod name { ... }`",
+        ));
+    };
+    // With `qualified`, the module path the label is prefixed with already ends in the module.
+    let module = match args.qualified {
+        Some(_) => String::new(),
+        None => format!("{}::", item_mod.ident.unraw()),
+    };
+
+    for item in items {
+        let Item::Fn(function) = item else {
+            continue;
+        };
+        if function.sig.constness.is_some() || function.attrs.iter().any(is_measured_attr) {
+            continue;
+        }
+
+        let mut function_args = args.clone();
+        function_args.default_name = Some(format!("{module}{}", function.sig.ident.unraw()));
+        let kind = FnKind::detect(function);
+        *function = syn::parse2(expand(function_args, function.clone(), kind)?)?;
+    }
+
+    Ok(item_mod.into_token_stream())
+}
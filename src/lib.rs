@@ -1,40 +1,62 @@
 use proc_macro::TokenStream;
-use quote::quote;
-use syn::{parse::Parse, parse::ParseStream, parse_macro_input, ItemFn, LitStr};
+use syn::{parse_macro_input, Block, DeriveInput, ItemFn, ItemImpl, ItemMod};
 
-struct MacroArgs {
-    custom_name: Option<LitStr>,
-}
-
-impl Parse for MacroArgs {
-    fn parse(input: ParseStream) -> syn::Result<Self> {
-        if input.is_empty() {
-            return Ok(MacroArgs { custom_name: None });
-        }
+mod args;
+mod block;
+mod closure;
+mod conflicts;
+mod defaults;
+mod expand;
+mod item_impl;
+mod item_mod;
+mod prefix;
+mod strict;
+mod target;
+mod variant;
 
-        let custom_name = input.parse()?;
-        Ok(MacroArgs {
-            custom_name: Some(custom_name),
-        })
-    }
-}
+use args::MacroArgs;
+use block::{
+    expand_await_point, expand_exclude, expand_measure, expand_region, AwaitPointInput,
+    MeasureInput,
+};
+use closure::{expand_measured_closure, MeasuredClosureInput};
+use expand::{expand, FnKind};
+use item_impl::expand_impl;
+use item_mod::expand_mod;
+use target::parse_target;
+use variant::expand_variant_name;
 
 /// A procedural macro attribute that measures the execution time of an async function.
 ///
 /// This macro wraps an async function to record its execution duration as a histogram metric.
-/// The duration is recorded using the `FunctionDurationSeconds` metric with a "function" label
-/// containing either the function name or a custom name if provided.
+/// The duration is recorded to the `async_function_duration_milliseconds` histogram with a
+/// "function" label containing either the function name or a custom name if provided.
+///
+/// The function name is the identifier as it reaches the attribute, so a function generated
+/// by `macro_rules!` is labelled with its final, substituted name, and a raw identifier such as
+/// `r#match` as `match`. A macro that should control the label can pass it on as the custom
+/// name, e.g. `#[measured_async_function($label)]`.
+///
+/// Functions nested inside other functions are supported the same way and labelled with their
+/// own name.
+///
+/// The name and metric can also be given in a `#[metric(name = "...", metric = "...")]`
+/// helper attribute next to the function's other attributes. It must come after the
+/// instrumenting attribute, which consumes it, and can't repeat a setting given on the
+/// attribute itself.
 ///
 /// # Arguments
 ///
-/// * `attr` - Optional custom name for the metric label
+/// * `attr` - Optional custom name for the metric label, followed by optional flags and
+///   `key = value` settings, described by topic under [Options](#options). Each option may
+///   be given at most once, and unknown flags are rejected.
 /// * `item` - The async function to be measured
 ///
 /// # Examples
 ///
 /// Basic usage with default function name as label:
 /// ```ignore
-/// use metrics_macros::measured_async_function;
+/// use metrics_utils_macros::measured_async_function;
 ///
 /// #[measured_async_function]
 /// async fn process_data() {
@@ -44,7 +66,7 @@ impl Parse for MacroArgs {
 ///
 /// Using a custom name for the metric label:
 /// ```ignore
-/// use metrics_macros::measured_async_function;
+/// use metrics_utils_macros::measured_async_function;
 ///
 /// #[measured_async_function("custom_process_name")]
 /// async fn process_data() {
@@ -52,74 +74,875 @@ impl Parse for MacroArgs {
 /// }
 /// ```
 ///
+/// Recording in seconds instead of milliseconds:
+/// ```ignore
+/// use metrics_utils_macros::measured_async_function;
+///
+/// #[measured_async_function(unit = "seconds")]
+/// async fn process_data() {
+///     // Function implementation
+/// }
+/// ```
+///
 /// The macro will record timing metrics that can be queried like:
-/// `function_duration_seconds{function="process_data"}` or
-/// `function_duration_seconds{function="custom_process_name"}`
+/// `async_function_duration_milliseconds{function="process_data"}` or
+/// `async_function_duration_milliseconds{function="custom_process_name"}`
+///
+/// Values are fractional, so calls shorter than a millisecond are not recorded as zero.
+///
+/// If `METRICS_MACROS_PREFIX` is set at compile time, every emitted metric name starts with it,
+/// e.g. `myapp_async_function_duration_milliseconds` for `METRICS_MACROS_PREFIX=myapp`.
+/// Crate-wide defaults can be set the same way: `METRICS_MACROS_DEFAULT_UNIT` for `unit` and
+/// `METRICS_MACROS_DEFAULT_LABEL_KEY` for `label_key`. An option given on the attribute takes
+/// precedence over the variable, which takes precedence over the built-in default.
+///
+/// Every sample also carries a `status` label: `"ok"` when the function returned and `"panic"`
+/// when it unwound, so panicking calls still show up in the histogram.
+///
+/// A `completion` label tells finished futures (`"completed"`) apart from futures that were
+/// dropped before they finished, e.g. by a timeout or `select!` (`"cancelled"`). Cancelled
+/// samples record the time until the drop and carry `status="cancelled"`.
+///
+/// A plain `fn` returning `impl Future` is handled like [`measured_future`], so the timer
+/// covers the returned future's execution rather than its construction. A function that is
+/// neither `async` nor returns a future is rejected, since it has nothing to await; use
+/// [`measured`] or [`measured_function`] for it.
+///
+/// # Options
+///
+/// Options are grouped by what they change, and each says which functions it applies to when that
+/// is not all of them.
+///
+/// ## Naming and units
+///
+/// What the metrics are called and the unit and form of the recorded value.
+///
+/// * `name` - The custom name as an option instead of a leading literal, either a string literal or
+///   a path to a `&'static str` constant, e.g. `name = metrics::CHECKOUT`. A constant is emitted
+///   into the generated code rather than read by the macro, so names kept in one place for reuse
+///   don't have to be repeated as literals.
+/// * `name_fn` - The custom name as a path to a `const fn() -> &'static str`, e.g.
+///   `name_fn = names::checkout`, for names computed from structured constants. The function is
+///   called in a `const` item, so the name is still a compile-time constant and costs nothing per
+///   call.
+/// * `metric` - The name of the duration histogram, replacing the default
+///   `async_function_duration_<unit>`, e.g. `metric = "db_query_duration"`. The function name label
+///   is set independently, so related functions can share a metric and be told apart by label. The
+///   name is used as given, apart from `METRICS_MACROS_PREFIX`.
+/// * `also_record` - A further histogram name that receives the same value with the same labels,
+///   e.g. `also_record = "legacy_handler_latency"` while migrating metric names. It can be repeated
+///   to record to several names; the clock is still read once.
+/// * `namespace` - Place every metric of the function under a dot-separated namespace, e.g.
+///   `namespace = "db.queries"` records to `db_queries_function_duration_milliseconds`. It applies
+///   to generated names and to `metric`, while `also_record` names are used as given.
+///   `METRICS_MACROS_PREFIX` still goes in front.
+/// * `namespace_separator` - Join the namespace components and the name with `"."` instead of
+///   `"_"`, e.g. `db.queries.function_duration_milliseconds`, for backends with hierarchical names.
+/// * `qualified` - Prefix the default label with the module path, e.g.
+///   `function="my_crate::worker::run"`, so same-named functions in different modules don't
+///   collide. A custom name is still used as given. A function nested in another one gets the path
+///   of the enclosing module, since the attribute can't see the outer function.
+/// * `type_name` - Label a method as `Type::method`, e.g. `type_name = "Service"` records
+///   `function="Service::handle"`, for methods and associated functions such as `new` instrumented
+///   one by one rather than with `#[measured_impl]`. On `#[measured_impl]` it replaces the type
+///   name taken from the impl block. Cannot be combined with a custom name.
+/// * `unit` - `"nanoseconds"`, `"microseconds"`, `"milliseconds"` (the default) or `"seconds"`.
+///   Selects both the recorded value and the metric name suffix, e.g.
+///   `async_function_duration_seconds`. Coarser units keep the fraction of a unit, and
+///   `"nanoseconds"` suits functions that finish in well under a microsecond.
+/// * `dual_unit` - Also record each duration in the other of milliseconds and seconds, under the
+///   name for that unit, e.g. to both `function_duration_milliseconds` and
+///   `function_duration_seconds` while dashboards move from one to the other. The unit must be
+///   milliseconds or seconds, and the names must be derived from it, so `metric`, `convert` and
+///   `split_result` are rejected with it.
+/// * `expected` - The magnitude a call is expected to take, one of the `unit` values, e.g.
+///   `expected = "microseconds"`. Picks the unit exactly like `unit`, so only one of the two can be
+///   given.
+/// * `value_type` - `"f64"` (the default) or `"u64"`, which records the duration as whole
+///   nanoseconds to the `_nanoseconds` names. A counter is incremented by the exact `u64`;
+///   histograms and gauges only take an `f64`, which holds the count exactly up to 2^53 ns, about
+///   104 days. A `unit` other than `"nanoseconds"`, `convert` and `quantize` are rejected next to
+///   it.
+/// * `convert` - A `fn(std::time::Duration) -> f64` producing the recorded value instead of the
+///   unit conversion, e.g. `convert = log_scale` for a log-scaled value or an estimate in CPU
+///   cycles. It gets the measured `Duration` of every recorded sample. The metric names keep the
+///   `unit` suffix, so a bespoke unit is best named with `metric = "..."`.
+/// * `quantize` - Round every recorded duration to the nearest multiple of a positive float, e.g.
+///   `quantize = 0.5` records `1.5` for a call of 1.26 ms, for exporters that struggle with many
+///   distinct values. It applies after `unit` or `convert`.
+/// * `clock` - `"std"` (the default), `"tokio"` or `"quanta"`. Takes the start time from
+///   `std::time::Instant`, `tokio::time::Instant` (which follows `tokio::time::pause`, giving
+///   deterministic durations in tests) or `quanta::Instant`. The non-default clocks need the
+///   matching Cargo feature of this crate, and the instrumented crate must depend on `tokio` or
+///   `quanta` itself.
+/// * `describe` - Register the histogram's unit and help text with `metrics::describe_histogram!`
+///   on the first call. Use `describe = "text"` for custom help text; the default is
+///   `"Execution time of <function>"`. Descriptions belong to the metric name, so when several
+///   functions share a histogram the description registered last wins.
+/// * `strict` - Reject metric names that aren't snake_case or don't end in `_nanoseconds`,
+///   `_microseconds`, `_milliseconds`, `_seconds` or `_total`, and label keys that aren't
+///   snake_case. The names are checked after `METRICS_MACROS_PREFIX` is applied, and the suffixes
+///   can be replaced with a comma-separated list in `METRICS_MACROS_STRICT_SUFFIXES`. The `strict`
+///   feature of `metrics-utils` checks every function.
+///
+/// ## Labels
+///
+/// The labels carried by the samples, besides the function name and `status`.
+///
+/// * `label_key` - The key of the label holding the function name, `"function"` by default, e.g.
+///   `label_key = "handler"` records `handler="process_data"`.
+/// * `labels` - Constant labels added to every sample, e.g.
+///   `labels(component = "auth", tier = "critical")`. Values must be string literals.
+/// * Any other `key = "value"` pair - A constant label as well, e.g. `component = "auth"`, unless
+///   `key` is a near miss of an option, within one edit per four characters, which is rejected as a
+///   likely typo. Use `labels(...)` for a label whose key is that close to an option.
+/// * `label_from` - Use the runtime value of a parameter as the value of the function name label,
+///   e.g. `label_from = route` records `function="/users"` for `handle("/users")`. The parameter
+///   must implement `ToString`; it is read on entry, before the body can move it. An `Option`
+///   parameter is labelled with the value it holds, or `"none"`. Cannot be combined with a custom
+///   name.
+/// * `label_from_present` - Like `label_from` for an `Option` parameter, but the label is `"some"`
+///   or `"none"` rather than the value, e.g. `label_from_present = filter, label_key = "mode"`
+///   tells calls with and without a filter apart.
+/// * `correlation_from` - Label every metric of the call with the runtime value of a parameter as
+///   `correlation`, next to the function name, e.g. `correlation_from = pipeline_id` to reconstruct
+///   the stages of one pipeline run. The parameter must implement `Display`; it is read on entry,
+///   before the body can move it.
+/// * `label_from_context` - A `fn() -> Option<String>` called once per call, e.g. reading a
+///   task-local or thread-local tenant id. When it returns a value, the duration and the metrics
+///   recorded along with it carry it as a `context` label; calls without one are recorded without
+///   the label. Companion counters and gauges such as `count` and `inflight` don't get it.
+/// * `context_key` - The key of the `label_from_context` label, e.g. `context_key = "tenant"`.
+/// * `labels_from` - A function called once per call that returns further labels for the duration
+///   samples, as a `Vec<(&'static str, String)>` or `Vec<metrics::Label>` (anything implementing
+///   `metrics_utils::LabelSet`). They follow the labels set by the macro, and returned keys the
+///   macro already sets, such as `function`, are left out. Requires the `metrics-utils` crate.
+/// * `max_cardinality` - Cap the distinct values of each runtime label, from `label_from`,
+///   `label_from_present`, `correlation_from`, `label_from_context` or `labels_from`, e.g.
+///   `max_cardinality = 100`. The first `N` values of a label are recorded as they are and later
+///   new ones as `"__overflow__"`, so a label fed by unbounded input can't exhaust the exporter.
+///   The seen values are kept per function in a set behind a lock. Requires the `metrics-utils`
+///   crate.
+/// * `time_bucket` - Label every metric with the hour of day it is recorded in, `"0"` to `"23"`, as
+///   `hour`, to segment latency by time of day. The hour is taken in UTC, or in the local time zone
+///   with `time_bucket = "local"`, which requires the `local_time` feature of `metrics-utils`.
+///   Requires the `metrics-utils` crate.
+/// * `location` - Label samples with the caller's source location as `location="file:line"`, using
+///   `#[track_caller]`. Only supported on sync functions with the Rust ABI, since `#[track_caller]`
+///   does not carry over into a returned future.
+/// * `monomorphized` - Add a `type` label holding `std::any::type_name::<T>()` for the first
+///   generic type parameter, so each instantiation of a generic function reports separately, e.g.
+///   `type="u32"`. Use `type_param = "U"` to report a different parameter.
+/// * `with_pkg_labels` - Also label every metric with the instrumented crate's package name and
+///   version, as `service` and `version`, read with `env!("CARGO_PKG_NAME")` and
+///   `env!("CARGO_PKG_VERSION")` when the crate is compiled.
+/// * `with_profile_label` - Also label every metric with the instrumented crate's build profile as
+///   `profile`, `"debug"` or `"release"`, from `cfg!(debug_assertions)` when the crate is compiled,
+///   so a debug build running in production stands out.
+/// * `path_label_from_cfg` - Also label every metric with `path`, `"on"` or `"off"` depending on
+///   whether the instrumented crate was compiled with the named feature, e.g.
+///   `path_label_from_cfg = "fast_path"` checks `cfg!(feature = "fast_path")`.
+/// * `arity` - Also label every metric with the number of parameters, e.g. `arity="2"`, counted
+///   when the function is expanded. A method's `self` receiver is not counted.
+///
+/// ## Results
+///
+/// For functions whose returned value tells how the call went.
+///
+/// * `result` - For functions returning a `Result`: record `status="err"` for `Err` values instead
+///   of `status="ok"`. The error type needs no extra bounds.
+/// * `error_kind` - Implies `result` and adds an `error_kind` label to `Err` samples: the error's
+///   type name without its module path by default, e.g. `error_kind="TimeoutError"`, or its
+///   `Display` output with `error_kind = display`, which requires the error to implement `Display`.
+/// * `split_result` - For functions returning a `Result`: record `Ok` and `Err` calls to histograms
+///   of their own, `function_duration_ok_milliseconds` and `function_duration_err_milliseconds`,
+///   instead of labelling them with `status`. A name given with `metric` gets `_ok` or `_err`
+///   appended. Panicking and cancelled calls are not recorded. Cannot be combined with `result` or
+///   `also_record`.
+/// * `label_variant` - Label returned calls with the variant of the returned value as
+///   `variant="Name"`, through `metrics_utils::VariantName`, which `#[derive(VariantName)]`
+///   implements for enums and which `Option`, `Result` and `bool` implement already. Calls that
+///   unwind or are cancelled have no value and carry no `variant` label. Requires the
+///   `metrics-utils` crate.
+/// * `success_ratio` - For functions returning a `Result`, publish the share of returned calls that
+///   were `Ok` to a `function_success_ratio` gauge after each call, e.g. `0.98`. The counts are per
+///   function and updated together under a lock, so the gauge never shows a half-updated ratio.
+///   Calls that unwind or are cancelled aren't counted. Labels set at runtime, which would split
+///   the counts, can't be combined with it.
+/// * `windowed_error_rate` - For functions returning a `Result`, publish the share of `Err`s among
+///   the calls returned in the last window, e.g. `windowed_error_rate = "10s"`, to a
+///   `function_error_rate` gauge after each call. Windows are given in `ms`, `s`, `m` or `h` and
+///   counted in ten coarse time buckets, so calls leave the window a tenth of it at a time. Like
+///   `success_ratio`, it can't be combined with labels set at runtime. Requires the `metrics-utils`
+///   crate.
+/// * `record_len` - Also record the length of the returned value to a `function_result_size`
+///   histogram labelled like `count`, without `status`, through `metrics_utils::MeasuredLen`.
+///   Standard collections, strings and slices are supported, also inside `Option` or `Result`,
+///   where `None` and `Err` record nothing. Requires the `metrics-utils` crate.
+/// * `record_serialized_len` - A `fn(&T) -> usize` giving the serialized size of the returned `T`,
+///   e.g. `record_serialized_len = json_len` with `serde_json::to_vec(r).map_or(0, |v| v.len())`,
+///   recorded to a `function_response_bytes` histogram labelled like `count`. It is called on every
+///   returned value, so the serialization cost is only paid when opted in.
+///
+/// ## Instruments
+///
+/// The instrument the duration goes to and the metrics recorded next to it.
+///
+/// * `as` - The `metrics` instrument the duration goes to: `"histogram"` (the default), `"counter"`
+///   or `"gauge"`. A counter is incremented by the duration in whole units, giving the total time
+///   spent, and is named with a `_total` suffix, e.g. `function_duration_microseconds_total`. The
+///   fraction of a unit each call leaves over is carried to the next call with the same labels, so
+///   calls shorter than a unit still add up in every series. A gauge is set to the latest duration.
+///   Both are recorded with the `metrics` facade, so they don't combine with `sink` or `buckets`,
+///   and a counter doesn't combine with `sample` or `tail_sample`. For a plain call rate, use
+///   `count`.
+/// * `no_histogram` - Leave out the duration histogram and record only the opt-in metrics, e.g.
+///   `count, no_histogram` for a plain call counter. At least one other metric must be enabled, and
+///   options configuring the histogram, such as `buckets`, `sink` or `as`, are rejected next to it.
+/// * `buckets` - Histogram bucket boundaries for this metric, e.g. `buckets = [0.001, 0.01, 0.1]`.
+///   They are registered with `metrics_utils::buckets` on the first call, since the `metrics`
+///   facade cannot configure buckets itself. Requires the `metrics-utils` crate.
+/// * `summary` - Also record each duration to a client-side quantile summary in
+///   `metrics_utils::summary`, under the histogram's name and labels, for exporters that want
+///   quantiles rather than buckets. The histogram is kept. Requires the `metrics-utils` crate.
+/// * `count` - Also increment a `function_calls_total` counter with the same labels on every call.
+///   The counter is incremented on entry, so panicking and cancelled calls are counted.
+/// * `interarrival` - Also record the time since the previous call's entry to a
+///   `function_interarrival_milliseconds` histogram (suffixed by `unit`), on every call after the
+///   first, to show bursty call patterns. The previous entry's time is kept in a per-function
+///   `Mutex`, so concurrent calls each record the gap to the entry before them.
+/// * `lifecycle` - Count every call's lifecycle in `function_started_total`, incremented on entry,
+///   `function_completed_total`, incremented when the call returns, and `function_cancelled_total`,
+///   incremented when a future is dropped before completing. A started future counts even if it is
+///   never polled to completion, and calls that panic are neither completed nor cancelled, so the
+///   difference between the counters gives the in-flight and abandoned calls over a time window.
+/// * `inflight` - Track how many calls are currently running in a `function_inflight` gauge. The
+///   gauge is lowered on every exit, including cancellation and panics.
+/// * `slow_threshold_ms` - Increment a `function_slow_calls_total` counter, with the same labels as
+///   `count`, when a call takes longer than this many milliseconds, e.g. `slow_threshold_ms = 500`.
+///   With the `tracing` feature a `tracing::warn!` with the function name and elapsed time is
+///   logged as well, which needs a `tracing` dependency in the instrumented crate. The histogram is
+///   still recorded, and the check reuses the measured duration.
+/// * `last_duration` - Also set a `function_last_duration_milliseconds` (suffixed by `unit`) gauge,
+///   labelled with the function name, to the duration of the most recent call. It is updated on
+///   every call, including calls that fail, panic or are cancelled, and ignores `sample`.
+/// * `timestamps` - Also set `function_last_start_seconds` and `function_last_end_seconds` gauges,
+///   labelled with the function name, to the wall-clock start and end of the most recent call in
+///   seconds since the Unix epoch, read with `SystemTime::now()`. Updated like `last_duration`.
+/// * `ewma` - Also set a `function_ewma_duration_milliseconds` (suffixed by `unit`) gauge to an
+///   exponentially weighted moving average of the durations, e.g. `ewma = 0.2` to weigh each call a
+///   fifth against the average, or a bare `ewma` for `0.1`. The first call starts the average at
+///   its own duration. The average is kept per function under a lock held only for the update, and
+///   is updated like `last_duration`. Like `success_ratio`, it can't be combined with labels set at
+///   runtime, whose series would share the one average.
+/// * `ringbuffer` - Also keep the last N recorded durations of the function in memory, e.g.
+///   `ringbuffer = 64`, readable with `metrics_utils::ring::recent_durations("name")` for debugging
+///   and tests without a metrics stack. Requires the `metrics-utils` crate.
+/// * `cpu_time` - Also record the calling thread's CPU time across the body to a
+///   `function_cpu_milliseconds` histogram (suffixed by `unit`), next to the wall time. Sync
+///   functions only, and requires the `cpu_time` feature of `metrics-utils`.
+/// * `utilization` - Also set a `function_cpu_utilization` gauge, with the histogram's labels, to
+///   the calling thread's CPU time divided by the recorded wall time of each call: near 1.0 for
+///   CPU-bound calls, near 0 for calls waiting on I/O or locks. Calls with a zero duration leave it
+///   unchanged. Sync functions only, and requires the `cpu_time` feature of `metrics-utils`.
+/// * `alloc_count` - Also add the number of allocations the calling thread made during the body to
+///   a `function_allocations_total` counter with the histogram's labels, including `status`.
+///   Allocations are only counted with `metrics_utils::alloc::CountingAllocator` installed as the
+///   `#[global_allocator]`, which the application opts into itself. Sync functions only, and
+///   requires the `alloc_count` feature of `metrics-utils`.
+///
+/// ## Sampling and filtering
+///
+/// Which calls are instrumented and which are recorded.
+///
+/// * `enabled_if` - A `fn() -> bool` deciding whether calls are instrumented, e.g.
+///   `enabled_if = config::metrics_enabled`. It is called once, on the first call, and the answer
+///   is cached in a `OnceLock`. While it is `false` no metric is recorded and the body runs as
+///   written, without reading the clock.
+/// * `skip_if` - A `bool` parameter that skips the instrumentation for a single call, e.g.
+///   `skip_if = cached` on `fn lookup(key: &str, cached: bool)`. Calls passing `true` run the body
+///   as written and record nothing.
+/// * `skip_in_test` - Run the body as written, recording nothing, when the instrumenting crate is
+///   compiled with `cfg(test)`, so its unit tests need no recorder and see no metric noise. The
+///   function compiles the same either way. Integration tests and other crates link the regular
+///   build, which stays instrumented.
+/// * `top_level_only` - For recursive functions, instrument only the outermost call on each thread,
+///   so each logical invocation records one sample with its end-to-end latency and counts once.
+///   Nested calls run the body as written. The nesting depth is a per-function thread-local, reset
+///   by a drop guard when a call unwinds. Sync functions only.
+/// * `depth_label` - For recursive functions, label every call with its recursion depth on the
+///   current thread as `depth="0"` for the outermost call, `"1"` for the calls it makes, and so on,
+///   to see latency by level. The level is a per-function thread-local, lowered by a drop guard so
+///   unwinding calls reset it too. Each depth is a separate series, so it suits bounded recursion.
+///   Sync functions only, and not together with `top_level_only`.
+/// * `sample` - Record the histogram on only one call in every `N`, e.g. `sample = 100`. This is
+///   fixed-rate counting, not random sampling: a per-function atomic counter selects the 1st,
+///   `N+1`-th, ... call, so skipped calls cost one relaxed atomic add and no lock. Other metrics
+///   such as `count` and `inflight` still see every call.
+/// * `tail_sample` - Record every call slower than a threshold and one in every `N` faster calls,
+///   e.g. `tail_sample(threshold_ms = 250, sample = 100)`, so the tail percentiles stay accurate
+///   while the common fast calls are thinned out. The faster calls are picked by a counter like
+///   `sample`, which it cannot be combined with, and neither can `record_len`.
+/// * `warmup` - Leave the first `N` calls out of the histogram, e.g. `warmup = 3`, so lazy
+///   initialization on the first calls doesn't skew the steady-state latency. The calls are counted
+///   by a per-function atomic that is only read once the warm-up is over. Like `sample`, it applies
+///   to the histogram alone, and `sample` and `tail_sample` only start counting after it.
+/// * `min_duration_ms` - A noise floor: calls faster than this many milliseconds are left out of
+///   the histogram, e.g. `min_duration_ms = 1`. The clock is still read, and the measured duration
+///   is compared, so the floor saves samples rather than time. Like `sample`, it applies to what is
+///   recorded with the histogram; `count`, `inflight` and the last call gauges still see every
+///   call.
+/// * `max_duration_ms` - A latency budget for performance regression tests: in the instrumented
+///   crate's `#[cfg(test)]` builds, a call taking longer than this many milliseconds panics after
+///   it was recorded, e.g. `max_duration_ms = 50`. Integration tests build the crate without
+///   `cfg(test)` and can enforce it with the `test_util` feature of `metrics-utils`. Other builds
+///   only record the call.
+///
+/// ## Destinations and hooks
+///
+/// Where the duration is sent and what runs with it.
+///
+/// * `record` - Where the duration goes: `"metrics"` (the default) records the histogram, `"span"`
+///   instead records the value as a `duration_ms` field on a `tracing` span, named `duration_ns`,
+///   `duration_us` or `duration_s` for the other units. That is the span of `span` if given,
+///   otherwise the caller's current span, which must declare the field, e.g.
+///   `duration_ms = tracing::field::Empty`, since `tracing` drops undeclared fields. The
+///   histogram's labels don't apply, and options configuring it are rejected. Companion metrics
+///   such as `count` still go to `metrics`. Requires the `tracing` feature.
+/// * `sink` - Send the duration histograms to a `metrics_utils::DurationSink` value instead of the
+///   global `metrics` recorder, e.g. `sink = CaptureSink` in tests. The sink is called on the
+///   concrete value, so there is no dynamic dispatch. Requires the `metrics-utils` crate.
+/// * `handle` - Record the duration into a pre-resolved histogram instead of looking it up with
+///   `metrics::histogram!` on every call, e.g. `handle = ENCODE_DURATION` for a
+///   `static LazyLock<Histogram>`. Anything implementing `metrics_utils::HistogramHandle` can be
+///   given, including a `OnceLock<Histogram>`, which falls back to the lookup until it is set. The
+///   handle keeps the name and labels it was resolved with, so options labelling each call
+///   (`result`, `split_result`, `error_kind`, `label_variant`, `label_from`, `correlation_from`,
+///   `time_bucket`, `label_from_context`, `labels_from` and `monomorphized`) are rejected with it,
+///   as are `sink` and `as = "..."`. Requires the `metrics-utils` crate.
+/// * `exemplar_from` - A `fn() -> Option<String>` returning the current trace id, e.g.
+///   `exemplar_from = tracing_ctx::trace_id`. It is called for each duration sample and the id is
+///   passed to the sink's `record_duration_with_exemplar`. The `metrics` facade has no exemplar
+///   API, so this requires `sink`.
+/// * `on_record` - A `fn(&str, f64)` called with the function name label's value and the duration
+///   each time one is recorded, e.g. `on_record = slo::observe`, for custom processing next to the
+///   histogram. It runs synchronously in the instrumented function.
+/// * `expose_as` / `after` - Run a block after each recording with the recorded duration bound as
+///   an `f64` to the name given by `expose_as`, e.g.
+///   `expose_as = took, after = { if took > 250.0 { warn_slow(took) } }`. The block runs once the
+///   body is done, so the function's parameters may already have been moved. Each option requires
+///   the other.
+/// * `span` - Run each call inside a `tracing::info_span!` named after the function, and record the
+///   measured duration on it as an `elapsed_ms` field before it closes. Async calls are
+///   instrumented with `tracing::Instrument`, so the span is entered on every poll. Requires the
+///   `tracing` feature and a `tracing` dependency in the instrumented crate.
+/// * `log_level` - Log every call with a `tracing` event at the given level, `"trace"`, `"debug"`,
+///   `"info"`, `"warn"` or `"error"`, carrying the `function` name and the measured `elapsed_ms`,
+///   e.g. for auditing a few critical, low-traffic functions. Sampled out calls are logged too, and
+///   no extra clock read is made. Requires the `tracing` feature.
+///
+/// ## Async only
+///
+/// What happens inside the future between its polls.
+///
+/// * `busy` - Also record the time spent inside the future's `poll` calls to
+///   `async_function_busy_milliseconds` (suffixed by `unit`), with the same labels. Comparing it
+///   with the total duration tells CPU-bound work apart from time spent waiting. Async only.
+/// * `poll_count` - Also record how many times the future was polled before it finished to an
+///   `async_function_poll_count` histogram, with the same labels. Each poll after the first follows
+///   a suspension, so a high count points at code that yields more than expected. Async only.
+/// * `sync_prefix` - Also record the time from the start of the call until the future's first
+///   `poll` returns to `async_function_sync_prefix_milliseconds` (suffixed by `unit`), with the
+///   function and static labels. That is the synchronous work before the first suspension, which
+///   blocks the executor thread. Recorded on every call, as soon as the first `poll` returns. Async
+///   only.
+/// * `task_id_label` - Also label the duration of an async function with the id of the Tokio task
+///   it completes in, as `task_id`, read with `tokio::task::try_id()` when it is recorded. Calls
+///   completing outside a task, e.g. in `block_on`, are recorded without it. Every task gets a new
+///   id, so this is meant for debugging rather than production dashboards. Requires the `tokio`
+///   feature.
+///
+/// ## Function shape
+///
+/// How the attribute rewrites the function itself.
+///
+/// * `strip_const` - Allow the attribute on a `const fn`, which is otherwise rejected because the
+///   clock can't be read in a const context. The instrumented function is no longer `const`, so it
+///   can still be called at runtime but not in const contexts.
+/// * `named_inner` - Move the body into a nested `__measured_inner_<name>` function that the
+///   instrumented function calls, so backtraces and profiles show the body under its own symbol
+///   rather than mixed into the timing code. The nested function can't see the enclosing `impl`, so
+///   methods, `Self` and parameters that aren't plain identifiers are rejected, as are `exclude!`,
+///   `region!` and `await_point!` in the body.
 ///
 #[proc_macro_attribute]
 pub fn measured_async_function(attr: TokenStream, item: TokenStream) -> TokenStream {
-    let args = parse_macro_input!(attr as MacroArgs);
-    let input_fn = parse_macro_input!(item as ItemFn);
-    let fn_name = &input_fn.sig.ident;
-    let fn_block = &input_fn.block;
-    let vis = &input_fn.vis;
-    let attrs = &input_fn.attrs;
-    let sig = &input_fn.sig;
-
-    let metric_name = match args.custom_name {
-        Some(name) => quote! { #name },
-        None => quote! { stringify!(#fn_name) },
-    };
+    expand_attribute(
+        attr,
+        item,
+        "measured_async_function",
+        |_, input_fn| match FnKind::detect(input_fn) {
+            FnKind::Sync => Err(syn::Error::new(
+                input_fn.sig.fn_token.span,
+                format!(
+                    "`#[measured_async_function]` is for async functions and functions returning \
+                     a future, use `#[measured]` or `#[measured_function]` for `fn {}`",
+                    input_fn.sig.ident
+                ),
+            )),
+            kind => Ok(kind),
+        },
+    )
+}
 
-    let output = quote! {
-        #(#attrs)*
-        #vis #sig {
-            let __measured_async = async move #fn_block;
-            let __measured_start = std::time::Instant::now();
-            let __measured_result = __measured_async.await;
-            let __measured_duration = __measured_start.elapsed().as_millis() as f64;
-            metrics::histogram!(
-                "async_function_duration_milliseconds",
-                &[("function", #metric_name)]
-            ).record(__measured_duration);
-            __measured_result
-        }
-    };
+/// Same as measured_async_function but for sync functions.
+///
+/// Records to `function_duration_milliseconds` by default, or `function_duration_seconds`
+/// with `unit = "seconds"`. Panicking calls are recorded with `status="panic"`. An `async fn`
+/// is rejected, since timing it as a sync function would only cover creating its future; use
+/// [`measured`] or [`measured_async_function`] for it.
+///
+/// The signature is emitted unchanged, so generic type, lifetime and const parameters and
+/// `where` clauses work as written. Every instantiation of a generic function records under the
+/// same label, and state such as the `sample` counter is shared between them. Pass
+/// `monomorphized` to tell the instantiations apart with a `type` label.
+///
+/// The body is inlined into the instrumented function, so `?` and `return` behave exactly as
+/// written and returned references keep their borrows. With `result`, `error_kind` or
+/// `#[measured_try_function]` the returned value has to be inspected after the body, so a body
+/// that can return early (through `return`, `?` or a macro other than the standard formatting
+/// and assertion macros) is called as a closure instead. That includes the smallest bodies: a
+/// lone tail expression such as `{ Ok(input.parse()?) }` in a closure returns its error from the
+/// closure, and the value of the closure is what the function returns, so it type-checks and
+/// records exactly like the longer body it was refactored from. The closure takes the
+/// parameters by value and runs once, so a reference borrowed from one, including a `&mut`
+/// returned by a `fn get_mut(&mut self) -> Result<&mut T, E>`, is returned as the function
+/// itself would return it.
+///
+/// `unsafe` and `extern "C"` (or any other ABI) are kept as well, so FFI entry points keep their
+/// calling convention. The body is evaluated inside the function itself, and inside an
+/// `unsafe fn` it stays an unsafe context. A panic in an `extern "C"` function aborts the
+/// process, so it can't be recorded.
+///
+/// Other attributes on the function are kept and apply to the instrumented function:
+/// `#[inline]` and `#[cold]` cover the inlined body along with the timing, `#[no_mangle]` and
+/// `#[export_name]` keep the exported symbol, and a `#[cfg(...)]` below the attribute removes
+/// the whole function. `#[naked]` functions can't hold the timer and are rejected.
+///
+/// The instrumented function returns the body's value itself, so `#[must_use]` on the function
+/// or on its return type still warns at call sites that ignore the result. The same holds for
+/// the other attributes, where `#[must_use]` on an `async fn` covers the awaited value.
+///
+/// ```ignore
+/// use metrics_utils_macros::measured_function;
+///
+/// #[measured_function(result)]
+/// fn parse<T: std::str::FromStr, const STRICT: bool>(s: &str) -> Result<T, T::Err>
+/// where
+///     T: std::fmt::Debug,
+/// {
+///     if STRICT { s.parse() } else { s.trim().parse() }
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn measured_function(attr: TokenStream, item: TokenStream) -> TokenStream {
+    expand_attribute(
+        attr,
+        item,
+        "measured_function",
+        |_, input_fn| match &input_fn.sig.asyncness {
+            Some(asyncness) => Err(syn::Error::new(
+                asyncness.span,
+                format!(
+                    "`#[measured_function]` is for sync functions, use `#[measured]` or \
+                     `#[measured_async_function]` for `async fn {}`",
+                    input_fn.sig.ident
+                ),
+            )),
+            None => Ok(FnKind::Sync),
+        },
+    )
+}
 
-    output.into()
+/// Measures a plain `fn` that returns `impl Future` by timing the future it returns.
+///
+/// Timing only the call would record how long it takes to build the future, which is usually
+/// next to nothing. Instead the returned future is wrapped so the duration runs from its first
+/// poll until it completes, and is recorded to `async_function_duration_milliseconds` with the
+/// same `status` and `completion` labels as [`measured_async_function`].
+///
+/// ```ignore
+/// use std::future::Future;
+/// use metrics_utils_macros::measured_future;
+///
+/// #[measured_future]
+/// fn fetch(url: String) -> impl Future<Output = Vec<u8>> + Send {
+///     async move { download(&url).await }
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn measured_future(attr: TokenStream, item: TokenStream) -> TokenStream {
+    expand_attribute(attr, item, "measured_future", |_, _| Ok(FnKind::Future))
 }
 
-/// Same as measured_async_function but for sync functions
+/// Measures a plain `fn` that returns `Pin<Box<dyn Future>>` by timing the future it returns.
+///
+/// This is the shape `#[async_trait]` turns `async fn` methods into, so timing the call alone
+/// would record near-zero durations. The returned future is wrapped and boxed again, recording
+/// to `async_function_duration_milliseconds` like [`measured_future`]. An unpinned
+/// `Box<dyn Future>` return is supported as well; it is pinned while polled and the wrapper is
+/// returned in a plain `Box`. Bounds such as `+ Send` are kept either way.
+///
+/// [`measured_async_function`] and [`measured`] detect this return type too, so with
+/// `#[async_trait]` on the impl block the method attribute can stay as it is. `#[async_trait]`
+/// must come first, so the method attribute sees the desugared signature:
+///
+/// ```ignore
+/// use async_trait::async_trait;
+/// use metrics_utils_macros::measured_async_function;
+///
+/// #[async_trait]
+/// impl Store for Db {
+///     #[measured_async_function]
+///     async fn get(&self, key: &str) -> Option<Vec<u8>> {
+///         self.query(key).await
+///     }
+/// }
+/// ```
+///
+/// Trait default methods can be instrumented like any other function.
 #[proc_macro_attribute]
-pub fn measured_function(attr: TokenStream, item: TokenStream) -> TokenStream {
+pub fn measured_boxed_future(attr: TokenStream, item: TokenStream) -> TokenStream {
+    expand_attribute(attr, item, "measured_boxed_future", |_, _| {
+        Ok(FnKind::BoxedFuture)
+    })
+}
+
+/// Measures only the successful calls of a function returning a `Result`.
+///
+/// `Ok` returns are recorded to the usual histogram with `status="ok"`, while `Err` returns,
+/// including errors propagated with `?`, only increment a `function_errors_total` counter with
+/// the same labels. Calls that panic or are cancelled are not recorded. This keeps
+/// success-path latency percentiles free of fast failures. Sync and async functions are both
+/// supported, and the attribute accepts the same arguments as [`measured`].
+///
+/// ```ignore
+/// use metrics_utils_macros::measured_try_function;
+///
+/// #[measured_try_function]
+/// async fn load(id: u64) -> Result<User, Error> {
+///     let row = db().fetch(id).await?;
+///     Ok(User::from(row))
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn measured_try_function(attr: TokenStream, item: TokenStream) -> TokenStream {
+    expand_attribute(attr, item, "measured_try_function", |args, input_fn| {
+        args.success_only = true;
+        Ok(FnKind::detect(input_fn))
+    })
+}
+
+/// Measures a function like [`measured_function`] or [`measured_async_function`], picking the
+/// wrapper from the function's signature.
+///
+/// `async fn`s and functions returning `impl Future` record to
+/// `async_function_duration_milliseconds` and other plain `fn`s to
+/// `function_duration_milliseconds`, so a function that switches between the two during a
+/// refactor keeps being timed correctly. Accepts the same arguments as the other attributes.
+///
+/// ```ignore
+/// use metrics_utils_macros::measured;
+///
+/// #[measured]
+/// async fn fetch() {}
+///
+/// #[measured("parse_input", unit = "seconds")]
+/// fn parse() {}
+/// ```
+#[proc_macro_attribute]
+pub fn measured(attr: TokenStream, item: TokenStream) -> TokenStream {
+    expand_attribute(attr, item, "measured", |_, input_fn| {
+        Ok(FnKind::detect(input_fn))
+    })
+}
+
+/// Measures every method of an `impl` block, as if each carried [`measured`].
+///
+/// Each method is labelled `Type::method`, e.g. `function="Service::handle"`, or
+/// `my_crate::service::Service::handle` with `qualified`. Associated functions without a
+/// receiver, such as constructors, are wrapped too and labelled the same way, e.g.
+/// `Service::new`. The type name drops the impl's generics, so `impl<T> Service<T>` labels
+/// `Service::new` for every `T`. Sync and async methods record to their usual histograms. The
+/// attribute accepts the same options as the function attributes, except a custom name, and
+/// applies them to every method.
+///
+/// Methods that already carry `#[measured]`, `#[measured_function]`,
+/// `#[measured_async_function]` or `#[measured_future]` are left to that attribute and not
+/// wrapped twice. `const fn` methods, associated constants, types and macros are passed through
+/// unchanged.
+///
+/// Two markers on a method are read and removed by the attribute: `#[skip_measure]` leaves
+/// the method as written, and `#[measure_as("...")]` labels it with the given name instead of
+/// `Type::method`. `#[measure_as]` is rejected on a method that isn't wrapped.
+///
+/// ```ignore
+/// use metrics_utils_macros::{measured_function, measured_impl};
+///
+/// struct Service;
+///
+/// #[measured_impl(count)]
+/// impl Service {
+///     const LIMIT: usize = 10;
+///
+///     fn new() -> Self {
+///         Service
+///     }
+///
+///     fn handle(&self) {}
+///
+///     async fn fetch(&self) {}
+///
+///     #[measured_function("service_flush")]
+///     fn flush(&mut self) {}
+///
+///     #[skip_measure]
+///     fn debug_dump(&self) {}
+///
+///     #[measure_as("service_lookup")]
+///     fn lookup(&self) {}
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn measured_impl(attr: TokenStream, item: TokenStream) -> TokenStream {
     let args = parse_macro_input!(attr as MacroArgs);
-    let input_fn = parse_macro_input!(item as ItemFn);
-    let fn_name = &input_fn.sig.ident;
-    let fn_block = &input_fn.block;
-    let vis = &input_fn.vis;
-    let attrs = &input_fn.attrs;
-    let sig = &input_fn.sig;
-
-    let metric_name = match args.custom_name {
-        Some(name) => quote! { #name },
-        None => quote! { stringify!(#fn_name) },
+    let item_impl: ItemImpl = match parse_target("measured_impl", "`impl` blocks", item) {
+        Ok(item_impl) => item_impl,
+        Err(error) => return error.into_compile_error().into(),
     };
+    expand_impl(args, item_impl)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
 
-    let output = quote! {
-        #(#attrs)*
-        #vis #sig {
-            let start = std::time::Instant::now();
-            let result = (|| #fn_block)();
-            let duration = start.elapsed().as_millis() as f64;
+/// Measures every function of an inline module, as if each carried [`measured`].
+///
+/// Each function is labelled `module::function`, e.g. `function="billing::charge"`, or
+/// `my_crate::billing::charge` with `qualified`, and records to the histogram for its kind.
+/// The attribute accepts the same options as the function attributes, except a custom name
+/// and `type_name`, and applies them to every function.
+///
+/// Only the functions directly inside the module are wrapped. Functions that already carry one
+/// of the function attributes are left to it, and `const fn`s, nested modules, `impl` blocks and
+/// all other items are passed through unchanged; a nested module can carry its own
+/// `#[measured_mod]`. The module must be declared inline, since attributes on `mod name;` are
+/// not supported by stable Rust.
+///
+/// ```ignore
+/// use metrics_utils_macros::measured_mod;
+///
+/// #[measured_mod(count)]
+/// mod billing {
+///     use metrics_utils_macros::measured_function;
+///
+///     pub fn charge(amount: u64) {}
+///
+///     pub async fn refund(amount: u64) {}
+///
+///     #[measured_function("billing_audit")]
+///     pub fn audit() {}
+///
+///     mod internal {
+///         pub fn untouched() {}
+///     }
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn measured_mod(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(attr as MacroArgs);
+    let item_mod: ItemMod = match parse_target("measured_mod", "inline modules", item) {
+        Ok(item_mod) => item_mod,
+        Err(error) => return error.into_compile_error().into(),
+    };
+    expand_mod(args, item_mod)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
 
-            metrics::histogram!(
-                "function_duration_milliseconds",
-                &[("function", #metric_name)]
-            ).record(duration);
+/// Measures the execution time of a block inside a larger function.
+///
+/// Records to the `block_duration_milliseconds` histogram with a `block` label holding the
+/// given name, and evaluates to the block's value so it can be used in expression position.
+/// The block is inlined, so `?`, `return` and `.await` inside it behave exactly as they would
+/// without the macro; the duration is still recorded when the block exits early or panics.
+///
+/// ```ignore
+/// use metrics_utils_macros::measure;
+///
+/// fn handle(input: &str) -> Result<usize, std::num::ParseIntError> {
+///     let parsed = measure!("parse", { input.trim().parse::<usize>()? });
+///     Ok(parsed * 2)
+/// }
+///
+/// async fn load() -> Vec<u8> {
+///     measure!("fetch", { fetch_bytes().await })
+/// }
+/// ```
+#[proc_macro]
+pub fn measure(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as MeasureInput);
+    expand_measure(input).into()
+}
 
-            result
-        }
-    };
+/// Excludes a block from the duration recorded by the enclosing instrumented function.
+///
+/// The time spent in the block, measured with `std::time::Instant`, is subtracted from the
+/// function's recorded duration, e.g. to leave out an external blocking call and measure only
+/// the function's own work. The block is inlined and evaluates to its value; time is
+/// excluded even when it exits early or panics.
+///
+/// `exclude!` must be used directly in the body of a sync function or `async fn` carrying one of
+/// the function attributes, which sets up the total it adds to. It is not supported inside
+/// `#[measured_future]` bodies or in closures that outlive the call.
+///
+/// ```ignore
+/// use metrics_utils_macros::{exclude, measured_function};
+///
+/// #[measured_function]
+/// fn sync_order(order: &Order) -> Receipt {
+///     let payload = serialize(order);
+///     let response = exclude!({ http_post(&payload) });
+///     parse_receipt(response)
+/// }
+/// ```
+#[proc_macro]
+pub fn exclude(input: TokenStream) -> TokenStream {
+    let block = parse_macro_input!(input as Block);
+    expand_exclude(block).into()
+}
+
+/// Restricts the duration recorded by the enclosing instrumented function to a block.
+///
+/// Only the time spent in the block, measured with `std::time::Instant`, is recorded, e.g. to
+/// leave setup and teardown around the interesting part out of the function's latency. The
+/// block is inlined and evaluates to its value; time is counted even when it exits early or
+/// panics. Several `region!` blocks add up, and a call that never enters one records a zero
+/// duration.
+///
+/// `region!` follows the rules of `exclude!`: it must be used directly in the body of a sync
+/// function or `async fn` carrying one of the function attributes, and the two can't be combined
+/// in one function.
+///
+/// ```ignore
+/// use metrics_utils_macros::{measured_function, region};
+///
+/// #[measured_function]
+/// fn handle(request: Request) -> Response {
+///     let input = decode(request);
+///     let output = region!({ solve(&input) });
+///     encode(output)
+/// }
+/// ```
+#[proc_macro]
+pub fn region(input: TokenStream) -> TokenStream {
+    let block = parse_macro_input!(input as Block);
+    expand_region(block).into()
+}
+
+/// Records the duration of one `.await` inside an instrumented `async fn`.
+///
+/// Records to the `async_await_point_milliseconds` histogram (suffixed by the function's
+/// `unit`) with the function's name label, its static labels and a `point` label holding the
+/// given name, and evaluates to the value of the expression. Timing each slow `.await` of a
+/// function this way shows which of them dominates its latency.
+///
+/// `await_point!` must be used directly in the body of an `async fn` carrying one of the function
+/// attributes, which declares the recorder it calls. It records only for calls that the
+/// attribute times, and nothing if the expression returns early or panics.
+///
+/// ```ignore
+/// use metrics_utils_macros::{await_point, measured_async_function};
+///
+/// #[measured_async_function]
+/// async fn checkout(cart: Cart) -> Receipt {
+///     let prices = await_point!("prices", pricing.quote(&cart).await);
+///     await_point!("charge", payments.charge(prices.total()).await)
+/// }
+/// ```
+#[proc_macro]
+pub fn await_point(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as AwaitPointInput);
+    expand_await_point(input).into()
+}
+
+/// Wraps a closure so each invocation records its execution time.
+///
+/// Records to the `closure_duration_milliseconds` histogram with a `closure` label holding the
+/// given name, and evaluates to a closure with the same parameters, return type and captures.
+/// Only the body is instrumented, so the wrapped closure implements `Fn`, `FnMut` or `FnOnce`
+/// exactly when the original does, and can be passed wherever the original could. Every call
+/// records one sample, including calls that return early or panic.
+///
+/// ```ignore
+/// use metrics_utils_macros::measured_closure;
+///
+/// let scale = 2;
+/// let double = measured_closure!("double", move |x: u32| x * scale);
+/// let values: Vec<u32> = [1, 2, 3].into_iter().map(double).collect();
+///
+/// let name = String::from("job");
+/// let consume = measured_closure!("consume", move || name);
+/// let name = consume();
+/// ```
+#[proc_macro]
+pub fn measured_closure(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as MeasuredClosureInput);
+    expand_measured_closure(input).into()
+}
 
-    output.into()
+/// Derives `metrics_utils::VariantName` for an enum, naming each variant as written.
+///
+/// This is what `label_variant` records as the `variant` label of a function returning the
+/// enum. Unit, tuple and struct variants are all supported, and the value is only inspected by
+/// reference.
+///
+/// ```ignore
+/// use metrics_utils::{measured_function, VariantName};
+///
+/// #[derive(VariantName)]
+/// enum Outcome {
+///     Hit,
+///     Miss { key: String },
+///     Stale(u64),
+/// }
+///
+/// #[measured_function(label_variant)]
+/// fn lookup(key: &str) -> Outcome {
+///     // Recorded with `variant="Hit"`, `"Miss"` or `"Stale"`.
+/// }
+/// ```
+#[proc_macro_derive(VariantName)]
+pub fn variant_name(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_variant_name(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// The shared entry point of the function attributes: parses the options and the function,
+/// lets `kind` adjust the options and pick how the function is timed, and runs the generator.
+fn expand_attribute(
+    attr: TokenStream,
+    item: TokenStream,
+    attribute: &str,
+    kind: impl FnOnce(&mut MacroArgs, &ItemFn) -> syn::Result<FnKind>,
+) -> TokenStream {
+    let mut args = parse_macro_input!(attr as MacroArgs);
+    parse_target(attribute, "functions", item)
+        .and_then(|input_fn| {
+            let kind = kind(&mut args, &input_fn)?;
+            expand(args, input_fn, kind)
+        })
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
 }
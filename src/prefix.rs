@@ -0,0 +1,29 @@
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+
+/// The environment variable holding the prefix for every emitted metric name.
+const PREFIX_VAR: &str = "METRICS_MACROS_PREFIX";
+
+/// Prepends the `METRICS_MACROS_PREFIX` read at expansion time to `name`, joined with `_`.
+///
+/// The `metrics` macros need the name as a literal, so the prefix is applied here rather than
+/// by the generated code. An unset or empty variable leaves `name` unchanged.
+pub(crate) fn metric_name(name: &str) -> String {
+    match std::env::var(PREFIX_VAR) {
+        Ok(prefix) if prefix.is_empty() => name.to_owned(),
+        Ok(prefix) if prefix.ends_with('_') => format!("{prefix}{name}"),
+        Ok(prefix) => format!("{prefix}_{name}"),
+        Err(_) => name.to_owned(),
+    }
+}
+
+/// Makes the instrumented crate depend on `METRICS_MACROS_PREFIX`.
+///
+/// A proc macro's own environment reads are invisible to Cargo, but `option_env!` in the
+/// expansion is recorded in the crate's dep-info, so changing the variable triggers a rebuild
+/// instead of silently keeping the old names.
+pub(crate) fn track() -> TokenStream2 {
+    quote! {
+        const _: Option<&str> = option_env!(#PREFIX_VAR);
+    }
+}
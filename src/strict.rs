@@ -0,0 +1,92 @@
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::quote;
+
+/// The environment variable holding the comma-separated metric name suffixes `strict` accepts.
+const SUFFIXES_VAR: &str = "METRICS_MACROS_STRICT_SUFFIXES";
+
+/// The suffixes accepted when `METRICS_MACROS_STRICT_SUFFIXES` is unset or empty: the unit
+/// suffixes of the duration names, and `_total` for counters.
+const DEFAULT_SUFFIXES: &[&str] = &[
+    "_nanoseconds",
+    "_microseconds",
+    "_milliseconds",
+    "_seconds",
+    "_total",
+];
+
+/// Checks a resolved metric name against the naming rules of `strict`: snake_case, ending in
+/// one of the accepted suffixes. Errors are reported at `span`.
+pub(crate) fn check_metric(name: &str, span: Span) -> syn::Result<()> {
+    if !is_snake_case(name) {
+        return Err(syn::Error::new(
+            span,
+            format!("`strict` requires snake_case metric names, found `{name}`"),
+        ));
+    }
+    let suffixes = suffixes();
+    if !suffixes
+        .iter()
+        .any(|suffix| name.ends_with(suffix.as_str()))
+    {
+        return Err(syn::Error::new(
+            span,
+            format!(
+                "`strict` requires metric names to end in one of {}, found `{name}`",
+                suffixes
+                    .iter()
+                    .map(|suffix| format!("`{suffix}`"))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Checks a label key against the naming rules of `strict`, which requires snake_case.
+pub(crate) fn check_label_key(key: &str, span: Span) -> syn::Result<()> {
+    if is_snake_case(key) {
+        return Ok(());
+    }
+    Err(syn::Error::new(
+        span,
+        format!("`strict` requires snake_case label keys, found `{key}`"),
+    ))
+}
+
+/// Lowercase ASCII words of letters and digits joined by single underscores, starting with a
+/// letter.
+fn is_snake_case(name: &str) -> bool {
+    name.split('_').enumerate().all(|(index, word)| {
+        let mut chars = word.chars();
+        chars.next().is_some_and(|first| {
+            first.is_ascii_lowercase() || (index > 0 && first.is_ascii_digit())
+        }) && chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+    })
+}
+
+/// The accepted suffixes from `METRICS_MACROS_STRICT_SUFFIXES`, or the defaults.
+fn suffixes() -> Vec<String> {
+    let configured: Vec<String> = std::env::var(SUFFIXES_VAR)
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|suffix| !suffix.is_empty())
+        .map(str::to_owned)
+        .collect();
+    match configured.is_empty() {
+        true => DEFAULT_SUFFIXES
+            .iter()
+            .map(|&suffix| suffix.to_owned())
+            .collect(),
+        false => configured,
+    }
+}
+
+/// Makes the instrumented crate depend on `METRICS_MACROS_STRICT_SUFFIXES`, like
+/// `prefix::track`.
+pub(crate) fn track() -> TokenStream2 {
+    quote! {
+        const _: Option<&str> = option_env!(#SUFFIXES_VAR);
+    }
+}
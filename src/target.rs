@@ -0,0 +1,56 @@
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use syn::parse::Parse;
+use syn::spanned::Spanned;
+use syn::{Item, TraitItemFn};
+
+/// Parses the item an attribute was applied to as `T`.
+///
+/// If the item is valid Rust but not what the attribute expects, such as a `struct` under
+/// `#[measured_function]`, the error names the attribute and points at the item's keyword
+/// instead of syn's generic "expected `fn`". Anything else keeps the original parse error.
+pub(crate) fn parse_target<T: Parse>(
+    attribute: &str,
+    expected: &str,
+    item: proc_macro::TokenStream,
+) -> syn::Result<T> {
+    let item = TokenStream2::from(item);
+    syn::parse2(item.clone()).map_err(|error| {
+        let Some((span, found)) = describe(item) else {
+            return error;
+        };
+        syn::Error::new(
+            span,
+            format!("`#[{attribute}]` can only be applied to {expected}, not to {found}"),
+        )
+    })
+}
+
+/// The keyword span and a description of an item, if it parses as an item at all.
+fn describe(item: TokenStream2) -> Option<(Span, &'static str)> {
+    let described = match syn::parse2::<Item>(item.clone()).ok()? {
+        Item::Const(item) => (item.const_token.span, "a constant"),
+        Item::Enum(item) => (item.enum_token.span, "an enum"),
+        Item::ExternCrate(item) => (item.extern_token.span, "an `extern crate`"),
+        Item::Fn(item) => (item.sig.fn_token.span, "a function"),
+        Item::ForeignMod(item) => (item.abi.span(), "an `extern` block"),
+        Item::Impl(item) => (item.impl_token.span, "an `impl` block"),
+        Item::Macro(item) => (item.mac.path.span(), "a macro invocation"),
+        Item::Mod(item) => (item.mod_token.span, "a module"),
+        Item::Static(item) => (item.static_token.span, "a static"),
+        Item::Struct(item) => (item.struct_token.span, "a struct"),
+        Item::Trait(item) => (item.trait_token.span, "a trait"),
+        Item::TraitAlias(item) => (item.trait_token.span, "a trait alias"),
+        Item::Type(item) => (item.type_token.span, "a type alias"),
+        Item::Union(item) => (item.union_token.span, "a union"),
+        Item::Use(item) => (item.use_token.span, "a `use` declaration"),
+        // A trait method without a default body parses as a bare signature.
+        _ => match syn::parse2::<TraitItemFn>(item) {
+            Ok(method) if method.default.is_none() => (
+                method.sig.fn_token.span,
+                "a signature without a body, such as a required trait method",
+            ),
+            _ => return None,
+        },
+    };
+    Some(described)
+}
@@ -0,0 +1,32 @@
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{ext::IdentExt, Data, DeriveInput};
+
+/// Generates the `metrics_utils::VariantName` impl of `#[derive(VariantName)]`.
+///
+/// Every variant is matched with `{ .. }`, which fits unit, tuple and struct variants alike, and
+/// named as written, without the `r#` of a raw identifier.
+pub(crate) fn expand_variant_name(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let Data::Enum(data) = &input.data else {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "`VariantName` can only be derived for enums",
+        ));
+    };
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let arms = data.variants.iter().map(|variant| {
+        let ident = &variant.ident;
+        let label = ident.unraw().to_string();
+        quote! { Self::#ident { .. } => #label, }
+    });
+    Ok(quote! {
+        impl #impl_generics ::metrics_utils::VariantName for #name #ty_generics #where_clause {
+            fn variant_name(&self) -> &'static str {
+                match *self {
+                    #(#arms)*
+                }
+            }
+        }
+    })
+}
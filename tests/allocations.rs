@@ -0,0 +1,65 @@
+#![cfg(not(any(
+    feature = "disabled",
+    feature = "otel",
+    feature = "buffered",
+    feature = "global_sink",
+    feature = "strict"
+)))]
+
+mod common;
+
+use std::alloc::System;
+use std::hint::black_box;
+
+use common::capture;
+use metrics_utils::alloc::CountingAllocator;
+use metrics_utils::measured_function;
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator<System> = CountingAllocator::new(System);
+
+#[measured_function(alloc_count)]
+fn allocates(n: usize) -> usize {
+    let boxes: Vec<Box<usize>> = (0..n).map(|i| black_box(Box::new(i))).collect();
+    boxes.len()
+}
+
+#[test]
+fn alloc_count_adds_the_allocations_of_each_call() {
+    let recorded = capture(|| {
+        // Three boxes and the vector holding them.
+        assert_eq!(allocates(3), 3);
+        assert_eq!(allocates(5), 5);
+    });
+    assert_eq!(
+        recorded.counter(
+            "function_allocations_total",
+            &[("function", "allocates"), ("status", "ok")]
+        ),
+        Some(4 + 6)
+    );
+    let samples = recorded.histogram(
+        "function_duration_milliseconds",
+        &[("function", "allocates"), ("status", "ok")],
+    );
+    assert_eq!(samples.len(), 2);
+}
+
+#[measured_function(alloc_count)]
+fn allocation_free(value: u64) -> u64 {
+    value.wrapping_mul(31)
+}
+
+#[test]
+fn alloc_count_adds_nothing_for_allocation_free_calls() {
+    let recorded = capture(|| assert_eq!(allocation_free(2), 62));
+    assert_eq!(
+        recorded
+            .counter(
+                "function_allocations_total",
+                &[("function", "allocation_free"), ("status", "ok")]
+            )
+            .unwrap_or(0),
+        0
+    );
+}
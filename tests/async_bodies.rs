@@ -0,0 +1,228 @@
+#![cfg(not(any(
+    feature = "disabled",
+    feature = "otel",
+    feature = "buffered",
+    feature = "global_sink",
+    feature = "strict"
+)))]
+
+mod common;
+
+use std::future::Future;
+use std::task::{Context, Waker};
+use std::time::Duration;
+
+use common::{capture, capture_async};
+use metrics_utils::{measured_async_function, measured_future};
+
+#[measured_async_function]
+async fn two_sleeps() -> u32 {
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    7
+}
+
+#[test]
+fn the_sample_covers_every_await_in_the_body() {
+    let recorded = capture_async(async { assert_eq!(two_sleeps().await, 7) });
+    let samples = recorded.histogram(
+        "async_function_duration_milliseconds",
+        &[
+            ("function", "two_sleeps"),
+            ("status", "ok"),
+            ("completion", "completed"),
+        ],
+    );
+    assert_eq!(samples.len(), 1);
+    assert!((20.0..1000.0).contains(&samples[0]), "{samples:?}");
+}
+
+#[measured_async_function]
+async fn never_finishes() {
+    std::future::pending::<()>().await;
+}
+
+#[test]
+fn a_future_dropped_after_one_poll_is_recorded_as_cancelled() {
+    let recorded = capture(|| {
+        let mut future = std::pin::pin!(never_finishes());
+        let mut context = Context::from_waker(Waker::noop());
+        assert!(future.as_mut().poll(&mut context).is_pending());
+    });
+    let samples = recorded.histogram(
+        "async_function_duration_milliseconds",
+        &[
+            ("function", "never_finishes"),
+            ("status", "cancelled"),
+            ("completion", "cancelled"),
+        ],
+    );
+    assert_eq!(samples.len(), 1);
+    assert_eq!(
+        recorded
+            .label_sets("async_function_duration_milliseconds")
+            .len(),
+        1
+    );
+}
+
+#[test]
+fn a_future_cut_off_by_a_timeout_is_recorded_as_cancelled() {
+    let recorded = capture_async(async {
+        let timed_out = tokio::time::timeout(Duration::from_millis(5), never_finishes()).await;
+        assert!(timed_out.is_err());
+    });
+    let samples = recorded.histogram(
+        "async_function_duration_milliseconds",
+        &[
+            ("function", "never_finishes"),
+            ("status", "cancelled"),
+            ("completion", "cancelled"),
+        ],
+    );
+    assert!(
+        samples.len() == 1 && (5.0..1000.0).contains(&samples[0]),
+        "{samples:?}"
+    );
+}
+
+#[measured_future]
+fn returns_future() -> impl Future<Output = u32> {
+    async {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        3
+    }
+}
+
+#[measured_async_function]
+fn detected_future() -> impl Future<Output = u32> {
+    async {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        4
+    }
+}
+
+#[test]
+fn impl_future_returns_are_timed_until_the_future_completes() {
+    let recorded = capture_async(async {
+        assert_eq!(returns_future().await, 3);
+        assert_eq!(detected_future().await, 4);
+    });
+    for function in ["returns_future", "detected_future"] {
+        let samples = recorded.histogram(
+            "async_function_duration_milliseconds",
+            &[
+                ("function", function),
+                ("status", "ok"),
+                ("completion", "completed"),
+            ],
+        );
+        assert!(
+            samples.len() == 1 && (20.0..1000.0).contains(&samples[0]),
+            "{function}: {samples:?}"
+        );
+    }
+}
+
+#[measured_async_function(busy)]
+async fn mostly_waiting() {
+    tokio::time::sleep(Duration::from_millis(30)).await;
+}
+
+#[test]
+fn busy_time_leaves_out_the_time_spent_suspended() {
+    let recorded = capture_async(mostly_waiting());
+    let labels = [
+        ("function", "mostly_waiting"),
+        ("status", "ok"),
+        ("completion", "completed"),
+    ];
+    let total = recorded.histogram("async_function_duration_milliseconds", &labels);
+    let busy = recorded.histogram("async_function_busy_milliseconds", &labels);
+    assert!(total.len() == 1 && total[0] >= 30.0, "{total:?}");
+    assert!(busy.len() == 1 && busy[0] < total[0] / 3.0, "{busy:?}");
+}
+
+#[measured_async_function(sync_prefix)]
+async fn heavy_prologue() {
+    std::thread::sleep(Duration::from_millis(15));
+    tokio::time::sleep(Duration::from_millis(30)).await;
+}
+
+#[test]
+fn sync_prefix_records_the_work_before_the_first_suspension() {
+    let recorded = capture_async(heavy_prologue());
+    let prefix = recorded.histogram(
+        "async_function_sync_prefix_milliseconds",
+        &[("function", "heavy_prologue")],
+    );
+    let total = recorded.histogram(
+        "async_function_duration_milliseconds",
+        &[
+            ("function", "heavy_prologue"),
+            ("status", "ok"),
+            ("completion", "completed"),
+        ],
+    );
+    assert!(
+        prefix.len() == 1 && (15.0..30.0).contains(&prefix[0]),
+        "{prefix:?}"
+    );
+    assert!(total.len() == 1 && total[0] >= 45.0, "{total:?}");
+}
+
+#[measured_async_function(poll_count)]
+async fn yields_three_times() {
+    for _ in 0..3 {
+        tokio::task::yield_now().await;
+    }
+}
+
+#[measured_async_function(poll_count)]
+async fn never_suspends() -> u32 {
+    std::future::ready(7).await
+}
+
+#[test]
+fn poll_count_records_one_poll_per_suspension_and_the_last() {
+    let recorded = capture_async(async {
+        yields_three_times().await;
+        assert_eq!(never_suspends().await, 7);
+    });
+    let polls = |function| {
+        recorded.histogram(
+            "async_function_poll_count",
+            &[
+                ("function", function),
+                ("status", "ok"),
+                ("completion", "completed"),
+            ],
+        )
+    };
+    assert_eq!(polls("yields_three_times"), [4.0]);
+    assert_eq!(polls("never_suspends"), [1.0]);
+}
+
+#[measured_async_function(task_id_label)]
+async fn in_a_task() {
+    tokio::task::yield_now().await;
+}
+
+#[test]
+fn task_id_label_tells_the_tasks_apart() {
+    let recorded = capture_async(async {
+        let tasks: Vec<_> = (0..3).map(|_| tokio::spawn(in_a_task())).collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+        // Outside a task, in `block_on`, there is no id to label with.
+        in_a_task().await;
+    });
+    // Each task gets a label set of its own, and the call in `block_on` one without an id.
+    let sets = recorded.label_sets("async_function_duration_milliseconds");
+    let with_id = sets
+        .iter()
+        .filter(|labels| labels.iter().any(|(key, _)| key == "task_id"))
+        .count();
+    assert_eq!((sets.len(), with_id), (4, 3), "{sets:?}");
+}
@@ -0,0 +1,243 @@
+#![cfg(not(any(
+    feature = "disabled",
+    feature = "otel",
+    feature = "buffered",
+    feature = "global_sink",
+    feature = "strict"
+)))]
+
+mod common;
+
+use std::num::ParseIntError;
+use std::time::Duration;
+
+use common::{capture, capture_async};
+use metrics_utils::{
+    await_point, exclude, measure, measured_async_function, measured_function, region,
+};
+
+const BLOCKS: &str = "block_duration_milliseconds";
+
+fn parse_doubled(input: &str) -> Result<u32, ParseIntError> {
+    let parsed = measure!("parse", { input.trim().parse::<u32>()? });
+    Ok(parsed * 2)
+}
+
+fn first_even(values: &[u32]) -> Option<u32> {
+    measure!("scan", {
+        for value in values {
+            if value % 2 == 0 {
+                return Some(*value);
+            }
+        }
+    });
+    None
+}
+
+#[test]
+fn the_block_evaluates_to_its_value() {
+    let recorded = capture(|| {
+        let value = measure!("sum", { (1..=4).sum::<u32>() });
+        assert_eq!(value, 10);
+    });
+    assert_eq!(recorded.histogram(BLOCKS, &[("block", "sum")]).len(), 1);
+}
+
+#[test]
+fn question_mark_and_return_leave_the_enclosing_function() {
+    let recorded = capture(|| {
+        assert_eq!(parse_doubled(" 21 "), Ok(42));
+        assert!(parse_doubled("x").is_err());
+        assert_eq!(first_even(&[1, 2]), Some(2));
+        assert_eq!(first_even(&[1]), None);
+    });
+    assert_eq!(recorded.histogram(BLOCKS, &[("block", "parse")]).len(), 2);
+    assert_eq!(recorded.histogram(BLOCKS, &[("block", "scan")]).len(), 2);
+}
+
+#[test]
+fn blocks_can_await_in_async_code() {
+    let recorded = capture_async(async {
+        let value = measure!("nap", {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            7
+        });
+        assert_eq!(value, 7);
+    });
+    let samples = recorded.histogram(BLOCKS, &[("block", "nap")]);
+    assert!(
+        samples.len() == 1 && (10.0..1000.0).contains(&samples[0]),
+        "{samples:?}"
+    );
+}
+
+#[measured_function]
+fn mostly_excluded() -> u32 {
+    std::thread::sleep(Duration::from_millis(5));
+    exclude!({
+        std::thread::sleep(Duration::from_millis(50));
+        7
+    })
+}
+
+#[measured_async_function]
+async fn async_mostly_excluded() {
+    tokio::time::sleep(Duration::from_millis(5)).await;
+    exclude!({ std::thread::sleep(Duration::from_millis(50)) });
+}
+
+#[test]
+fn exclude_leaves_the_block_out_of_the_duration() {
+    let recorded = capture(|| assert_eq!(mostly_excluded(), 7));
+    let samples = recorded.histogram(
+        "function_duration_milliseconds",
+        &[("function", "mostly_excluded"), ("status", "ok")],
+    );
+    assert!(
+        samples.len() == 1 && (5.0..50.0).contains(&samples[0]),
+        "{samples:?}"
+    );
+}
+
+#[test]
+fn exclude_works_in_async_functions() {
+    let recorded = capture_async(async_mostly_excluded());
+    let samples = recorded.histogram(
+        "async_function_duration_milliseconds",
+        &[
+            ("function", "async_mostly_excluded"),
+            ("status", "ok"),
+            ("completion", "completed"),
+        ],
+    );
+    assert!(
+        samples.len() == 1 && (5.0..50.0).contains(&samples[0]),
+        "{samples:?}"
+    );
+}
+
+#[measured_function]
+fn excluded_then_returns(input: &str) -> Result<u32, ParseIntError> {
+    let value = exclude!({
+        std::thread::sleep(Duration::from_millis(50));
+        input.parse::<u32>()?
+    });
+    Ok(value)
+}
+
+#[test]
+fn exclude_still_subtracts_a_block_left_with_question_mark() {
+    let recorded = capture(|| assert!(excluded_then_returns("x").is_err()));
+    let samples = recorded.histogram(
+        "function_duration_milliseconds",
+        &[("function", "excluded_then_returns"), ("status", "ok")],
+    );
+    assert!(samples.len() == 1 && samples[0] < 50.0, "{samples:?}");
+}
+
+#[measured_async_function(labels(tier = "critical"))]
+async fn checkout() -> u32 {
+    let prices = await_point!(
+        "prices",
+        async {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            20
+        }
+        .await
+    );
+    let sleep = tokio::time::sleep(Duration::from_millis(20));
+    await_point!("charge", sleep.await);
+    prices + 1
+}
+
+#[test]
+fn await_points_are_recorded_by_name() {
+    let recorded = capture_async(async { assert_eq!(checkout().await, 21) });
+    let point = |point| {
+        recorded.histogram(
+            "async_await_point_milliseconds",
+            &[
+                ("function", "checkout"),
+                ("tier", "critical"),
+                ("point", point),
+            ],
+        )
+    };
+    let (prices, charge) = (point("prices"), point("charge"));
+    assert!(
+        prices.len() == 1 && (5.0..20.0).contains(&prices[0]),
+        "{prices:?}"
+    );
+    assert!(charge.len() == 1 && charge[0] >= 20.0, "{charge:?}");
+    assert_eq!(
+        recorded.label_sets("async_await_point_milliseconds").len(),
+        2
+    );
+}
+
+#[measured_function]
+fn solved_in_a_region(input: &str) -> usize {
+    // Setup and teardown outside the region are left out of the duration.
+    std::thread::sleep(Duration::from_millis(30));
+    let solved = region!({
+        std::thread::sleep(Duration::from_millis(5));
+        input.len()
+    });
+    std::thread::sleep(Duration::from_millis(30));
+    solved
+}
+
+#[measured_async_function]
+async fn awaited_in_regions() {
+    tokio::time::sleep(Duration::from_millis(30)).await;
+    region!({ tokio::time::sleep(Duration::from_millis(5)).await });
+    region!({ tokio::time::sleep(Duration::from_millis(5)).await });
+}
+
+#[measured_function]
+fn never_enters_its_region(enter: bool) {
+    std::thread::sleep(Duration::from_millis(5));
+    if enter {
+        region!({});
+    }
+}
+
+#[test]
+fn region_records_only_the_time_inside_it() {
+    let recorded = capture(|| assert_eq!(solved_in_a_region("abc"), 3));
+    let samples = recorded.histogram(
+        "function_duration_milliseconds",
+        &[("function", "solved_in_a_region"), ("status", "ok")],
+    );
+    assert!(
+        samples.len() == 1 && (5.0..30.0).contains(&samples[0]),
+        "{samples:?}"
+    );
+}
+
+#[test]
+fn regions_of_an_async_function_add_up() {
+    let recorded = capture_async(awaited_in_regions());
+    let samples = recorded.histogram(
+        "async_function_duration_milliseconds",
+        &[
+            ("function", "awaited_in_regions"),
+            ("status", "ok"),
+            ("completion", "completed"),
+        ],
+    );
+    assert!(
+        samples.len() == 1 && (10.0..30.0).contains(&samples[0]),
+        "{samples:?}"
+    );
+}
+
+#[test]
+fn a_call_outside_every_region_records_zero() {
+    let recorded = capture(|| never_enters_its_region(false));
+    let samples = recorded.histogram(
+        "function_duration_milliseconds",
+        &[("function", "never_enters_its_region"), ("status", "ok")],
+    );
+    assert_eq!(samples, [0.0]);
+}
@@ -0,0 +1,63 @@
+//! Run with `cargo test --features buffered,metrics-utils/buffered --test buffered`.
+
+#![cfg(feature = "buffered")]
+
+mod common;
+
+use common::{capture, capture_with, Recorded};
+use metrics_utils::buffer::{flush, CAPACITY};
+use metrics_utils::measured_function;
+
+const SYNC: &str = "function_duration_milliseconds";
+
+#[measured_function(count)]
+fn buffered() {}
+
+#[test]
+fn samples_reach_the_recorder_when_flushed() {
+    let labels = [("function", "buffered"), ("status", "ok")];
+    let recorded = capture_with(|snapshotter| {
+        for _ in 0..100 {
+            buffered();
+        }
+        let before = Recorded::from(snapshotter);
+        assert!(before.histogram(SYNC, &labels).is_empty());
+        // Companion metrics are not buffered.
+        assert_eq!(
+            before.counter("function_calls_total", &[("function", "buffered")]),
+            Some(100)
+        );
+        flush();
+    });
+    assert_eq!(recorded.histogram(SYNC, &labels).len(), 100);
+}
+
+#[measured_function]
+fn fills_the_buffer() {}
+
+#[test]
+fn a_full_buffer_is_forwarded_without_a_flush() {
+    let recorded = capture(|| {
+        for _ in 0..CAPACITY {
+            fills_the_buffer();
+        }
+    });
+    let samples = recorded.histogram(SYNC, &[("function", "fills_the_buffer"), ("status", "ok")]);
+    assert_eq!(samples.len(), CAPACITY);
+}
+
+#[measured_function(count, path_label_from_cfg = "buffered")]
+fn on_the_buffered_path() {}
+
+#[test]
+fn path_label_from_cfg_is_on_with_the_feature() {
+    // `tests/static_labels.rs` checks the same function with the feature off.
+    let recorded = capture(on_the_buffered_path);
+    assert_eq!(
+        recorded.counter(
+            "function_calls_total",
+            &[("function", "on_the_buffered_path"), ("path", "on")]
+        ),
+        Some(1)
+    );
+}
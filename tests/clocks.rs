@@ -0,0 +1,64 @@
+#![cfg(not(any(
+    feature = "disabled",
+    feature = "otel",
+    feature = "buffered",
+    feature = "global_sink",
+    feature = "strict"
+)))]
+
+mod common;
+
+use std::time::Duration;
+
+use common::{capture, capture_paused};
+use metrics_utils::{measured_async_function, measured_function};
+
+#[measured_async_function(clock = "tokio")]
+async fn paused_sleep() {
+    tokio::time::sleep(Duration::from_millis(50)).await;
+}
+
+#[measured_async_function]
+async fn std_clock_sleep() {
+    tokio::time::sleep(Duration::from_millis(50)).await;
+}
+
+#[measured_function(clock = "quanta")]
+fn quanta_sleep() {
+    std::thread::sleep(Duration::from_millis(5));
+}
+
+#[test]
+fn tokio_clock_follows_paused_time_exactly() {
+    let recorded = capture_paused(async {
+        paused_sleep().await;
+        std_clock_sleep().await;
+    });
+    let samples = |function| {
+        recorded.histogram(
+            "async_function_duration_milliseconds",
+            &[
+                ("function", function),
+                ("status", "ok"),
+                ("completion", "completed"),
+            ],
+        )
+    };
+    assert_eq!(samples("paused_sleep"), [50.0]);
+    // The paused clock skips the sleep, which `std::time::Instant` never sees.
+    let real = samples("std_clock_sleep");
+    assert!(real.len() == 1 && real[0] < 50.0, "{real:?}");
+}
+
+#[test]
+fn quanta_clock_measures_real_time() {
+    let recorded = capture(quanta_sleep);
+    let samples = recorded.histogram(
+        "function_duration_milliseconds",
+        &[("function", "quanta_sleep"), ("status", "ok")],
+    );
+    assert!(
+        samples.len() == 1 && (5.0..1000.0).contains(&samples[0]),
+        "{samples:?}"
+    );
+}
@@ -0,0 +1,68 @@
+#![cfg(not(any(
+    feature = "disabled",
+    feature = "otel",
+    feature = "buffered",
+    feature = "global_sink",
+    feature = "strict"
+)))]
+
+mod common;
+
+use common::capture;
+use metrics_utils::measured_closure;
+
+const CLOSURE: &str = "closure_duration_milliseconds";
+
+#[test]
+fn a_fn_closure_records_every_call() {
+    let scale = 2;
+    let recorded = capture(|| {
+        let double = measured_closure!("double", move |x: u32| x * scale);
+        let values: Vec<u32> = [1, 2, 3].into_iter().map(&double).collect();
+        assert_eq!(values, [2, 4, 6]);
+        assert_eq!(double(5), 10);
+    });
+    assert_eq!(
+        recorded.histogram(CLOSURE, &[("closure", "double")]).len(),
+        4
+    );
+}
+
+#[test]
+fn an_fnmut_closure_records_every_call() {
+    let mut total = 0;
+    let recorded = capture(|| {
+        let mut add = measured_closure!("add", |x: u32| total += x);
+        add(1);
+        add(2);
+    });
+    assert_eq!(total, 3);
+    assert_eq!(recorded.histogram(CLOSURE, &[("closure", "add")]).len(), 2);
+}
+
+#[test]
+fn an_fnonce_closure_records_its_call() {
+    let name = String::from("job");
+    let recorded = capture(|| {
+        let consume = measured_closure!("consume", move || name);
+        assert_eq!(consume(), "job");
+    });
+    assert_eq!(
+        recorded.histogram(CLOSURE, &[("closure", "consume")]).len(),
+        1
+    );
+}
+
+/// Only accepts closures callable through a shared reference.
+fn call_twice(f: impl Fn() -> u32) -> u32 {
+    f() + f()
+}
+
+#[test]
+fn the_wrapped_closure_keeps_its_kind() {
+    let recorded = capture(|| {
+        let one = measured_closure!("one", || 1);
+        assert_eq!(call_twice(one), 2);
+    });
+    assert_eq!(recorded.histogram(CLOSURE, &[("closure", "one")]).len(), 2);
+}
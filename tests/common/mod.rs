@@ -0,0 +1,193 @@
+//! Helpers shared by the integration tests: running instrumented code against a
+//! `DebuggingRecorder` and looking up what it recorded.
+//!
+//! The recorder is installed for the current thread only, so tests running in parallel don't
+//! see each other's samples. Async code runs on a current-thread runtime for the same reason.
+//!
+//! Features that send every sample elsewhere or change what is accepted (`disabled`, `otel`,
+//! `buffered`, `global_sink` and `strict`) are covered by test files of their own, which are
+//! compiled only with the feature on, e.g. `cargo test --features disabled --test disabled`.
+//! The other test files are left out of such builds.
+
+#![allow(dead_code)]
+
+use std::future::Future;
+use std::io;
+use std::sync::{Arc, Mutex};
+
+use metrics::{SharedString, Unit};
+use metrics_util::debugging::{DebugValue, DebuggingRecorder, Snapshotter};
+use metrics_util::{CompositeKey, MetricKind};
+
+/// Everything recorded while a test's code ran.
+pub struct Recorded {
+    entries: Vec<(CompositeKey, Option<Unit>, Option<SharedString>, DebugValue)>,
+}
+
+/// Runs `f` with a fresh recorder installed for the current thread.
+pub fn capture(f: impl FnOnce()) -> Recorded {
+    let recorder = DebuggingRecorder::new();
+    let snapshotter = recorder.snapshotter();
+    metrics::with_local_recorder(&recorder, f);
+    Recorded::from(&snapshotter)
+}
+
+/// Like [`capture`], handing `f` a snapshotter to look at metrics while it runs. Taking a
+/// snapshot resets counters and gauges to zero and empties histograms, so each snapshot, and
+/// the returned one, only holds what was recorded since the previous.
+pub fn capture_with(f: impl FnOnce(&Snapshotter)) -> Recorded {
+    let recorder = DebuggingRecorder::new();
+    let snapshotter = recorder.snapshotter();
+    metrics::with_local_recorder(&recorder, || f(&snapshotter));
+    Recorded::from(&snapshotter)
+}
+
+/// Runs `future` to completion on a current-thread runtime with a fresh recorder installed.
+pub fn capture_async(future: impl Future<Output = ()>) -> Recorded {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    capture(|| runtime.block_on(future))
+}
+
+/// Like [`capture_async`], with Tokio's clock paused, so sleeps advance it exactly.
+pub fn capture_paused(future: impl Future<Output = ()>) -> Recorded {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .start_paused(true)
+        .build()
+        .unwrap();
+    capture(|| runtime.block_on(future))
+}
+
+impl Recorded {
+    /// Everything recorded since the last snapshot of `snapshotter`.
+    pub fn from(snapshotter: &Snapshotter) -> Self {
+        Recorded {
+            entries: snapshotter.snapshot().into_vec(),
+        }
+    }
+
+    fn find(
+        &self,
+        kind: MetricKind,
+        name: &str,
+        labels: &[(&str, &str)],
+    ) -> Option<&(CompositeKey, Option<Unit>, Option<SharedString>, DebugValue)> {
+        let mut expected: Vec<_> = labels.to_vec();
+        expected.sort_unstable();
+        self.entries.iter().find(|(key, ..)| {
+            let mut actual: Vec<_> = key
+                .key()
+                .labels()
+                .map(|label| (label.key(), label.value()))
+                .collect();
+            actual.sort_unstable();
+            key.kind() == kind && key.key().name() == name && actual == expected
+        })
+    }
+
+    /// The samples of the histogram `name` with exactly `labels`, in any order.
+    pub fn histogram(&self, name: &str, labels: &[(&str, &str)]) -> Vec<f64> {
+        match self.find(MetricKind::Histogram, name, labels) {
+            Some((.., DebugValue::Histogram(values))) => values.iter().map(|v| v.0).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// The value of the counter `name` with exactly `labels`.
+    pub fn counter(&self, name: &str, labels: &[(&str, &str)]) -> Option<u64> {
+        match self.find(MetricKind::Counter, name, labels) {
+            Some((.., DebugValue::Counter(value))) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// The value of the gauge `name` with exactly `labels`.
+    pub fn gauge(&self, name: &str, labels: &[(&str, &str)]) -> Option<f64> {
+        match self.find(MetricKind::Gauge, name, labels) {
+            Some((.., DebugValue::Gauge(value))) => Some(value.0),
+            _ => None,
+        }
+    }
+
+    /// The unit and description registered for the histogram `name`.
+    pub fn description(&self, name: &str) -> Option<(Option<Unit>, String)> {
+        self.entries.iter().find_map(|(key, unit, description, _)| {
+            if key.kind() != MetricKind::Histogram || key.key().name() != name {
+                return None;
+            }
+            Some((*unit, description.as_ref()?.to_string()))
+        })
+    }
+
+    /// The label sets of every metric named `name`, each sorted by key.
+    pub fn label_sets(&self, name: &str) -> Vec<Vec<(String, String)>> {
+        let mut sets: Vec<_> = self
+            .entries
+            .iter()
+            .filter(|(key, ..)| key.key().name() == name)
+            .map(|(key, ..)| {
+                let mut labels: Vec<_> = key
+                    .key()
+                    .labels()
+                    .map(|label| (label.key().to_owned(), label.value().to_owned()))
+                    .collect();
+                labels.sort_unstable();
+                labels
+            })
+            .collect();
+        sets.sort_unstable();
+        sets
+    }
+
+    /// The names of every metric recorded, sorted.
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<_> = self
+            .entries
+            .iter()
+            .map(|(key, ..)| key.key().name().to_owned())
+            .collect();
+        names.sort_unstable();
+        names.dedup();
+        names
+    }
+}
+
+/// Runs `f` with a `tracing` subscriber installed for the current thread, returning what it
+/// logged, one line per event and per closed span, without colours.
+pub fn capture_logs(f: impl FnOnce()) -> String {
+    let logs = Logs::default();
+    let subscriber = tracing_subscriber::fmt()
+        .with_writer(logs.clone())
+        .with_ansi(false)
+        .with_span_events(tracing_subscriber::fmt::format::FmtSpan::CLOSE)
+        .finish();
+    tracing::subscriber::with_default(subscriber, f);
+    let bytes = logs.0.lock().unwrap().clone();
+    String::from_utf8(bytes).unwrap()
+}
+
+/// The buffer `capture_logs` collects the formatted output in.
+#[derive(Clone, Default)]
+struct Logs(Arc<Mutex<Vec<u8>>>);
+
+impl io::Write for Logs {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> tracing_subscriber::fmt::MakeWriter<'a> for Logs {
+    type Writer = Logs;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
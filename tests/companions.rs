@@ -0,0 +1,258 @@
+#![cfg(not(any(
+    feature = "disabled",
+    feature = "otel",
+    feature = "buffered",
+    feature = "global_sink",
+    feature = "strict"
+)))]
+
+mod common;
+
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::time::Duration;
+
+use common::{capture, capture_async, capture_with, Recorded};
+use metrics_util::debugging::Snapshotter;
+use metrics_utils::{measured_async_function, measured_function};
+use tokio::sync::Notify;
+
+#[measured_function(count)]
+fn counted() {}
+
+#[measured_function(count)]
+fn counted_panics() {
+    panic!("counted anyway");
+}
+
+#[measured_async_function(count)]
+async fn counted_async() {}
+
+#[test]
+fn count_increments_once_per_call() {
+    let recorded = capture(|| (0..3).for_each(|_| counted()));
+    assert_eq!(
+        recorded.counter("function_calls_total", &[("function", "counted")]),
+        Some(3)
+    );
+}
+
+#[test]
+fn count_includes_panicking_calls() {
+    let recorded = capture(|| {
+        assert!(panic::catch_unwind(AssertUnwindSafe(counted_panics)).is_err());
+    });
+    assert_eq!(
+        recorded.counter("function_calls_total", &[("function", "counted_panics")]),
+        Some(1)
+    );
+}
+
+#[test]
+fn count_works_for_async_functions() {
+    let recorded = capture_async(async {
+        counted_async().await;
+        counted_async().await;
+    });
+    assert_eq!(
+        recorded.counter("function_calls_total", &[("function", "counted_async")]),
+        Some(2)
+    );
+}
+
+#[measured_async_function(inflight)]
+async fn in_flight(release: Arc<Notify>) {
+    release.notified().await;
+}
+
+#[test]
+fn inflight_peaks_with_concurrent_calls_and_returns_to_zero() {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    // Snapshots reset the gauge, so its level is the sum of the changes seen so far.
+    let mut level = 0.0;
+    let mut inflight = |snapshotter: &Snapshotter| {
+        let recorded = Recorded::from(snapshotter);
+        level += recorded
+            .gauge("function_inflight", &[("function", "in_flight")])
+            .unwrap_or(0.0);
+        level
+    };
+    capture_with(|snapshotter| {
+        runtime.block_on(async {
+            let release = Arc::new(Notify::new());
+            let calls: Vec<_> = (0..3)
+                .map(|_| tokio::spawn(in_flight(release.clone())))
+                .collect();
+            let cancelled = tokio::spawn(in_flight(release.clone()));
+            tokio::task::yield_now().await;
+            assert_eq!(inflight(snapshotter), 4.0);
+
+            cancelled.abort();
+            assert!(cancelled.await.unwrap_err().is_cancelled());
+            assert_eq!(inflight(snapshotter), 3.0);
+
+            release.notify_waiters();
+            for call in calls {
+                call.await.unwrap();
+            }
+            assert_eq!(inflight(snapshotter), 0.0);
+        })
+    });
+}
+
+#[measured_async_function(lifecycle)]
+async fn saga(release: Arc<Notify>) {
+    release.notified().await;
+}
+
+#[test]
+fn lifecycle_counts_starts_completions_and_cancellations() {
+    let recorded = capture_async(async {
+        let release = Arc::new(Notify::new());
+        let completing: Vec<_> = (0..2)
+            .map(|_| tokio::spawn(saga(release.clone())))
+            .collect();
+        let aborted = tokio::spawn(saga(release.clone()));
+        tokio::task::yield_now().await;
+        aborted.abort();
+        assert!(aborted.await.unwrap_err().is_cancelled());
+        let timed_out = tokio::time::timeout(Duration::from_millis(1), saga(release.clone())).await;
+        assert!(timed_out.is_err());
+        release.notify_waiters();
+        for call in completing {
+            call.await.unwrap();
+        }
+    });
+    let labels = [("function", "saga")];
+    assert_eq!(recorded.counter("function_started_total", &labels), Some(4));
+    assert_eq!(
+        recorded.counter("function_completed_total", &labels),
+        Some(2)
+    );
+    assert_eq!(
+        recorded.counter("function_cancelled_total", &labels),
+        Some(2)
+    );
+}
+
+#[measured_function(top_level_only, count)]
+fn factorial(n: u64) -> u64 {
+    if n <= 1 {
+        1
+    } else {
+        n * factorial(n - 1)
+    }
+}
+
+#[test]
+fn top_level_only_records_one_sample_per_outer_call() {
+    let recorded = capture(|| {
+        assert_eq!(factorial(5), 120);
+        assert_eq!(factorial(3), 6);
+    });
+    let samples = recorded.histogram(
+        "function_duration_milliseconds",
+        &[("function", "factorial"), ("status", "ok")],
+    );
+    assert_eq!(samples.len(), 2);
+    assert_eq!(
+        recorded.counter("function_calls_total", &[("function", "factorial")]),
+        Some(2)
+    );
+}
+
+#[measured_function(top_level_only)]
+fn recurses_then_panics(n: u32) {
+    if n == 0 {
+        panic!("bottom");
+    }
+    recurses_then_panics(n - 1);
+}
+
+#[test]
+fn top_level_only_resets_after_a_panic() {
+    let recorded = capture(|| {
+        let result = panic::catch_unwind(|| recurses_then_panics(3));
+        assert!(result.is_err());
+        // A leftover depth would make this call look nested and go unrecorded.
+        let result = panic::catch_unwind(|| recurses_then_panics(0));
+        assert!(result.is_err());
+    });
+    let samples = recorded.histogram(
+        "function_duration_milliseconds",
+        &[("function", "recurses_then_panics"), ("status", "panic")],
+    );
+    assert_eq!(samples.len(), 2);
+}
+
+#[measured_function(depth_label)]
+fn parse_nested(depth: u32) -> u32 {
+    if depth == 0 {
+        0
+    } else {
+        1 + parse_nested(depth - 1)
+    }
+}
+
+#[measured_function(depth_label)]
+fn nested_then_panics(n: u32) {
+    if n == 0 {
+        panic!("bottom");
+    }
+    nested_then_panics(n - 1);
+}
+
+#[test]
+fn depth_label_labels_each_level_of_the_recursion() {
+    let recorded = capture(|| assert_eq!(parse_nested(3), 3));
+    for depth in ["0", "1", "2", "3"] {
+        let samples = recorded.histogram(
+            "function_duration_milliseconds",
+            &[
+                ("function", "parse_nested"),
+                ("status", "ok"),
+                ("depth", depth),
+            ],
+        );
+        assert_eq!(samples.len(), 1, "depth {depth}");
+    }
+    assert_eq!(
+        recorded.label_sets("function_duration_milliseconds").len(),
+        4
+    );
+}
+
+#[test]
+fn depth_label_resets_after_a_panic() {
+    let recorded = capture(|| {
+        let result = panic::catch_unwind(|| nested_then_panics(2));
+        assert!(result.is_err());
+        let result = panic::catch_unwind(|| nested_then_panics(0));
+        assert!(result.is_err());
+    });
+    let outermost = recorded.histogram(
+        "function_duration_milliseconds",
+        &[
+            ("function", "nested_then_panics"),
+            ("status", "panic"),
+            ("depth", "0"),
+        ],
+    );
+    assert_eq!(outermost.len(), 2);
+}
+
+#[measured_function(count, no_histogram)]
+fn only_counted() {}
+
+#[test]
+fn no_histogram_records_only_the_opt_in_metrics() {
+    let recorded = capture(|| (0..3).for_each(|_| only_counted()));
+    assert_eq!(recorded.names(), ["function_calls_total"]);
+    assert_eq!(
+        recorded.counter("function_calls_total", &[("function", "only_counted")]),
+        Some(3)
+    );
+}
@@ -0,0 +1,53 @@
+#![cfg(not(any(
+    feature = "disabled",
+    feature = "otel",
+    feature = "buffered",
+    feature = "global_sink",
+    feature = "strict"
+)))]
+
+//! `METRICS_MACROS_DEFAULT_UNIT` and `METRICS_MACROS_DEFAULT_LABEL_KEY`, read while the
+//! attributes expand. Without the variables this checks the built-in defaults; run it with them
+//! set to check they apply where an attribute doesn't say otherwise, e.g.
+//! `METRICS_MACROS_DEFAULT_UNIT=seconds METRICS_MACROS_DEFAULT_LABEL_KEY=handler cargo test --test defaults`.
+
+mod common;
+
+use common::capture;
+use metrics_utils::measured_function;
+
+const UNIT: &str = match option_env!("METRICS_MACROS_DEFAULT_UNIT") {
+    Some(unit) => unit,
+    None => "milliseconds",
+};
+
+const LABEL_KEY: &str = match option_env!("METRICS_MACROS_DEFAULT_LABEL_KEY") {
+    Some(key) => key,
+    None => "function",
+};
+
+#[measured_function]
+fn uses_the_defaults() {}
+
+#[measured_function(unit = "microseconds", label_key = "operation")]
+fn overrides_the_defaults() {}
+
+#[test]
+fn attributes_without_settings_use_the_defaults() {
+    let recorded = capture(uses_the_defaults);
+    let samples = recorded.histogram(
+        &format!("function_duration_{UNIT}"),
+        &[(LABEL_KEY, "uses_the_defaults"), ("status", "ok")],
+    );
+    assert_eq!(samples.len(), 1, "{:?}", recorded.names());
+}
+
+#[test]
+fn attribute_settings_override_the_defaults() {
+    let recorded = capture(overrides_the_defaults);
+    let samples = recorded.histogram(
+        "function_duration_microseconds",
+        &[("operation", "overrides_the_defaults"), ("status", "ok")],
+    );
+    assert_eq!(samples.len(), 1, "{:?}", recorded.names());
+}
@@ -0,0 +1,37 @@
+//! Run with `cargo test --features disabled --test disabled`.
+
+#![cfg(feature = "disabled")]
+
+mod common;
+
+use std::time::Duration;
+
+use common::{capture, capture_async};
+use metrics_utils::{measure, measured_async_function, measured_function};
+
+#[measured_function(count, result)]
+fn parse(input: &str) -> Result<u32, std::num::ParseIntError> {
+    input.parse()
+}
+
+#[measured_async_function(inflight)]
+async fn fetch() -> u32 {
+    tokio::time::sleep(Duration::from_millis(1)).await;
+    7
+}
+
+#[test]
+fn instrumented_functions_run_and_record_nothing() {
+    let recorded = capture(|| {
+        assert_eq!(parse("4"), Ok(4));
+        assert!(parse("x").is_err());
+        assert_eq!(measure!("block", { 1 + 1 }), 2);
+    });
+    assert!(recorded.names().is_empty(), "{:?}", recorded.names());
+}
+
+#[test]
+fn async_functions_run_and_record_nothing() {
+    let recorded = capture_async(async { assert_eq!(fetch().await, 7) });
+    assert!(recorded.names().is_empty(), "{:?}", recorded.names());
+}
@@ -0,0 +1,115 @@
+#![cfg(not(any(
+    feature = "disabled",
+    feature = "otel",
+    feature = "buffered",
+    feature = "global_sink",
+    feature = "strict"
+)))]
+
+mod common;
+
+use common::{capture, capture_async};
+use metrics_utils::{measured, measured_async_function, measured_function};
+
+mod explicit {
+    use super::*;
+
+    #[measured_function("lookup", count, labels(tier = "critical"))]
+    pub fn sync_lookup(key: u32) -> u32 {
+        key + 1
+    }
+
+    #[measured_async_function("fetch", count, labels(tier = "critical"))]
+    pub async fn async_fetch(key: u32) -> u32 {
+        key + 1
+    }
+}
+
+mod detected {
+    use super::*;
+
+    #[measured("lookup", count, labels(tier = "critical"))]
+    pub fn sync_lookup(key: u32) -> u32 {
+        key + 1
+    }
+
+    #[measured("fetch", count, labels(tier = "critical"))]
+    pub async fn async_fetch(key: u32) -> u32 {
+        key + 1
+    }
+}
+
+fn everything(recorded: &common::Recorded) -> Vec<(String, Vec<(String, String)>)> {
+    recorded
+        .names()
+        .into_iter()
+        .flat_map(|name| {
+            recorded
+                .label_sets(&name)
+                .into_iter()
+                .map(move |labels| (name.clone(), labels))
+        })
+        .collect()
+}
+
+#[test]
+fn measured_function_and_measured_record_the_same_sync_metrics() {
+    let explicit = capture(|| assert_eq!(explicit::sync_lookup(1), 2));
+    let detected = capture(|| assert_eq!(detected::sync_lookup(1), 2));
+    assert_eq!(everything(&explicit), everything(&detected));
+    let labels = [
+        ("function", "lookup"),
+        ("status", "ok"),
+        ("tier", "critical"),
+    ];
+    assert_eq!(
+        detected
+            .histogram("function_duration_milliseconds", &labels)
+            .len(),
+        1
+    );
+    assert_eq!(
+        detected.counter(
+            "function_calls_total",
+            &[("function", "lookup"), ("tier", "critical")]
+        ),
+        Some(1)
+    );
+}
+
+#[test]
+fn measured_async_function_and_measured_record_the_same_async_metrics() {
+    let explicit = capture_async(async { assert_eq!(explicit::async_fetch(1).await, 2) });
+    let detected = capture_async(async { assert_eq!(detected::async_fetch(1).await, 2) });
+    assert_eq!(everything(&explicit), everything(&detected));
+    let labels = [
+        ("function", "fetch"),
+        ("status", "ok"),
+        ("completion", "completed"),
+        ("tier", "critical"),
+    ];
+    assert_eq!(
+        detected
+            .histogram("async_function_duration_milliseconds", &labels)
+            .len(),
+        1
+    );
+}
+
+#[test]
+fn measured_picks_the_histogram_from_the_signature() {
+    let sync = capture(|| assert_eq!(detected::sync_lookup(1), 2));
+    let asynchronous = capture_async(async { assert_eq!(detected::async_fetch(1).await, 2) });
+    assert!(sync
+        .names()
+        .contains(&"function_duration_milliseconds".to_owned()));
+    assert!(!sync
+        .names()
+        .contains(&"async_function_duration_milliseconds".to_owned()));
+    assert!(asynchronous
+        .names()
+        .contains(&"async_function_duration_milliseconds".to_owned()));
+    assert!(!asynchronous
+        .names()
+        .contains(&"function_duration_milliseconds".to_owned()));
+}
@@ -0,0 +1,71 @@
+//! Run with `cargo test --features global_sink,metrics-utils/global_sink --test global_sink`.
+
+#![cfg(feature = "global_sink")]
+
+use std::sync::{Arc, Mutex, OnceLock};
+
+use metrics::Label;
+use metrics_utils::global::set_global_sink;
+use metrics_utils::{measure_fn, measured_function, DurationSink};
+
+type Labels = Vec<(String, String)>;
+
+/// Keeps every duration it receives, as the metric name and the labels.
+struct Collected(Mutex<Vec<(&'static str, Labels)>>);
+
+impl DurationSink for Collected {
+    fn record_duration(&self, metric: &'static str, labels: &[Label], _value: f64) {
+        let labels = labels
+            .iter()
+            .map(|label| (label.key().to_owned(), label.value().to_owned()))
+            .collect();
+        self.0.lock().unwrap().push((metric, labels));
+    }
+}
+
+/// Installs the collecting sink once for the whole test binary.
+fn collected() -> &'static Collected {
+    static COLLECTED: OnceLock<Arc<Collected>> = OnceLock::new();
+    COLLECTED.get_or_init(|| {
+        let collected = Arc::new(Collected(Mutex::new(Vec::new())));
+        set_global_sink(collected.clone()).unwrap_or_else(|_| panic!("already installed"));
+        collected
+    })
+}
+
+/// The labels of every duration recorded to `metric` with `function="<function>"`.
+fn recorded(metric: &str, function: &str) -> Vec<Labels> {
+    collected()
+        .0
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(name, labels)| {
+            *name == metric && labels.contains(&("function".to_owned(), function.to_owned()))
+        })
+        .map(|(_, labels)| labels.clone())
+        .collect()
+}
+
+#[measured_function]
+fn instrumented() {}
+
+#[test]
+fn instrumented_functions_record_to_the_installed_sink() {
+    collected();
+    instrumented();
+    assert_eq!(
+        recorded("function_duration_milliseconds", "instrumented").len(),
+        1
+    );
+}
+
+#[test]
+fn timing_helpers_record_to_the_installed_sink() {
+    collected();
+    measure_fn("function_duration_milliseconds", "helper", || ());
+    assert_eq!(
+        recorded("function_duration_milliseconds", "helper").len(),
+        1
+    );
+}
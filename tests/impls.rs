@@ -0,0 +1,304 @@
+#![cfg(not(any(
+    feature = "disabled",
+    feature = "otel",
+    feature = "buffered",
+    feature = "global_sink",
+    feature = "strict"
+)))]
+
+mod common;
+
+use common::{capture, capture_async};
+use metrics_utils::{measured_function, measured_impl, measured_mod};
+
+const SYNC: &str = "function_duration_milliseconds";
+const ASYNC: &str = "async_function_duration_milliseconds";
+
+struct Service;
+
+#[measured_impl]
+impl Service {
+    const LIMIT: usize = 10;
+
+    fn handle(&self) -> usize {
+        Self::LIMIT
+    }
+
+    async fn fetch(&self) -> usize {
+        Self::LIMIT + 1
+    }
+
+    #[measured_function("service_flush")]
+    fn flush(&self) {}
+}
+
+#[test]
+fn sync_methods_are_labelled_with_the_type() {
+    let recorded = capture(|| assert_eq!(Service.handle(), 10));
+    let samples = recorded.histogram(SYNC, &[("function", "Service::handle"), ("status", "ok")]);
+    assert_eq!(samples.len(), 1);
+}
+
+#[test]
+fn async_methods_record_to_the_async_histogram() {
+    let recorded = capture_async(async { assert_eq!(Service.fetch().await, 11) });
+    let labels = [
+        ("function", "Service::fetch"),
+        ("status", "ok"),
+        ("completion", "completed"),
+    ];
+    assert_eq!(recorded.histogram(ASYNC, &labels).len(), 1);
+}
+
+#[test]
+fn methods_with_their_own_attribute_are_wrapped_once() {
+    let recorded = capture(|| Service.flush());
+    assert_eq!(
+        recorded.label_sets(SYNC),
+        [[
+            ("function".to_owned(), "service_flush".to_owned()),
+            ("status".to_owned(), "ok".to_owned()),
+        ]]
+    );
+    assert_eq!(
+        recorded
+            .histogram(SYNC, &[("function", "service_flush"), ("status", "ok")])
+            .len(),
+        1
+    );
+}
+
+struct Counter(u32);
+
+impl Counter {
+    #[measured_function(type_name = "Counter")]
+    fn get(&self) -> u32 {
+        self.0
+    }
+
+    #[measured_function(type_name = "Counter")]
+    fn bump(&mut self) -> u32 {
+        self.0 += 1;
+        self.0
+    }
+
+    #[measured_function(type_name = "Counter")]
+    fn into_inner(self) -> u32 {
+        self.0
+    }
+
+    #[measured_function(type_name = "Counter")]
+    fn new() -> Self {
+        Counter(0)
+    }
+}
+
+#[test]
+fn type_name_labels_each_receiver_kind() {
+    let recorded = capture(|| {
+        let mut counter = Counter::new();
+        assert_eq!(counter.bump(), 1);
+        assert_eq!(counter.get(), 1);
+        assert_eq!(counter.into_inner(), 1);
+    });
+    for function in [
+        "Counter::new",
+        "Counter::get",
+        "Counter::bump",
+        "Counter::into_inner",
+    ] {
+        let samples = recorded.histogram(SYNC, &[("function", function), ("status", "ok")]);
+        assert_eq!(samples.len(), 1, "{function}");
+    }
+}
+
+struct Pool<T> {
+    items: Vec<T>,
+}
+
+#[measured_impl]
+impl<T: Clone> Pool<T> {
+    fn new() -> Self {
+        Pool { items: Vec::new() }
+    }
+
+    fn with_item(item: T) -> Self {
+        Pool { items: vec![item] }
+    }
+
+    fn len(&self) -> usize {
+        self.items.len()
+    }
+}
+
+#[test]
+fn constructors_of_a_generic_impl_are_labelled_with_the_type() {
+    let recorded = capture(|| {
+        assert_eq!(Pool::<u32>::new().len(), 0);
+        assert_eq!(Pool::with_item("a").len(), 1);
+        assert_eq!(Pool::with_item(1.5).len(), 1);
+    });
+    let calls = |function| {
+        recorded
+            .histogram(SYNC, &[("function", function), ("status", "ok")])
+            .len()
+    };
+    // Every `T` shares the label of the type without its generics.
+    assert_eq!(calls("Pool::new"), 1);
+    assert_eq!(calls("Pool::with_item"), 2);
+    assert_eq!(calls("Pool::len"), 3);
+}
+
+#[measured_impl]
+impl Counter {
+    fn peek(&self) -> u32 {
+        self.0
+    }
+
+    fn reset(&mut self) {
+        self.0 = 0;
+    }
+
+    fn finish(self) -> u32 {
+        self.0
+    }
+}
+
+#[test]
+fn measured_impl_labels_each_receiver_kind() {
+    let recorded = capture(|| {
+        let mut counter = Counter(3);
+        assert_eq!(counter.peek(), 3);
+        counter.reset();
+        assert_eq!(counter.finish(), 0);
+    });
+    for function in ["Counter::peek", "Counter::reset", "Counter::finish"] {
+        let samples = recorded.histogram(SYNC, &[("function", function), ("status", "ok")]);
+        assert_eq!(samples.len(), 1, "{function}");
+    }
+}
+
+#[measured_mod(count)]
+mod billing {
+    use metrics_utils::measured_function;
+
+    pub fn charge(amount: u64) -> u64 {
+        amount
+    }
+
+    pub async fn refund(amount: u64) -> u64 {
+        amount
+    }
+
+    #[measured_function("billing_audit")]
+    pub fn audit() {}
+
+    pub const fn fee() -> u64 {
+        3
+    }
+
+    pub mod internal {
+        pub fn untouched() {}
+    }
+
+    #[metrics_utils::measured_mod]
+    pub mod ledger {
+        pub fn post() {}
+    }
+}
+
+#[test]
+fn measured_mod_labels_each_function_with_the_module() {
+    let recorded = capture_async(async {
+        assert_eq!(billing::charge(5), 5);
+        assert_eq!(billing::refund(2).await, 2);
+        billing::audit();
+        billing::internal::untouched();
+        billing::ledger::post();
+    });
+    assert_eq!(
+        recorded.counter("function_calls_total", &[("function", "billing::charge")]),
+        Some(1)
+    );
+    assert_eq!(
+        recorded
+            .histogram(SYNC, &[("function", "billing::charge"), ("status", "ok")])
+            .len(),
+        1
+    );
+    let refund = [
+        ("function", "billing::refund"),
+        ("status", "ok"),
+        ("completion", "completed"),
+    ];
+    assert_eq!(recorded.histogram(ASYNC, &refund).len(), 1);
+    // A function with its own attribute keeps it, and isn't measured twice.
+    assert_eq!(
+        recorded.label_sets(SYNC),
+        [
+            vec![
+                ("function".to_owned(), "billing::charge".to_owned()),
+                ("status".to_owned(), "ok".to_owned()),
+            ],
+            vec![
+                ("function".to_owned(), "billing_audit".to_owned()),
+                ("status".to_owned(), "ok".to_owned()),
+            ],
+            vec![
+                ("function".to_owned(), "ledger::post".to_owned()),
+                ("status".to_owned(), "ok".to_owned()),
+            ],
+        ]
+    );
+    // `const fn`s pass through and can still be used in const contexts.
+    const FEE: u64 = billing::fee();
+    assert_eq!(FEE, 3);
+}
+
+trait Shape {
+    fn area(&self) -> f64;
+    fn name(&self) -> &'static str;
+    fn scaled(&self, by: f64) -> f64;
+}
+
+struct Square(f64);
+
+#[measured_impl]
+impl Shape for Square {
+    fn area(&self) -> f64 {
+        self.0 * self.0
+    }
+
+    #[skip_measure]
+    fn name(&self) -> &'static str {
+        "square"
+    }
+
+    #[measure_as("square_scale")]
+    fn scaled(&self, by: f64) -> f64 {
+        self.0 * by
+    }
+}
+
+#[test]
+fn skip_measure_and_measure_as_pick_out_methods_of_a_trait_impl() {
+    let recorded = capture(|| {
+        let square = Square(2.0);
+        assert_eq!(square.area(), 4.0);
+        assert_eq!(square.name(), "square");
+        assert_eq!(square.scaled(3.0), 6.0);
+    });
+    assert_eq!(
+        recorded.label_sets(SYNC),
+        [
+            [
+                ("function".to_owned(), "Square::area".to_owned()),
+                ("status".to_owned(), "ok".to_owned()),
+            ],
+            [
+                ("function".to_owned(), "square_scale".to_owned()),
+                ("status".to_owned(), "ok".to_owned()),
+            ],
+        ]
+    );
+}
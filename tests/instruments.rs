@@ -0,0 +1,548 @@
+#![cfg(not(any(
+    feature = "disabled",
+    feature = "otel",
+    feature = "buffered",
+    feature = "global_sink",
+    feature = "strict"
+)))]
+
+mod common;
+
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use common::{capture, capture_paused, Recorded};
+use metrics::Histogram;
+use metrics_util::debugging::DebuggingRecorder;
+use metrics_utils::{measured_async_function, measured_function};
+
+#[measured_function(as = "histogram")]
+fn as_histogram() {}
+
+#[measured_function(as = "counter")]
+fn as_counter() {
+    std::thread::sleep(Duration::from_millis(2));
+}
+
+#[measured_function(as = "counter")]
+fn sub_millisecond_counter() {
+    std::thread::sleep(Duration::from_micros(200));
+}
+
+#[measured_function(as = "gauge")]
+fn as_gauge() {
+    std::thread::sleep(Duration::from_millis(5));
+}
+
+#[test]
+fn histogram_is_the_explicit_default() {
+    let recorded = capture(as_histogram);
+    let samples = recorded.histogram(
+        "function_duration_milliseconds",
+        &[("function", "as_histogram"), ("status", "ok")],
+    );
+    assert_eq!(samples.len(), 1);
+}
+
+#[test]
+fn counter_adds_up_whole_milliseconds() {
+    let recorded = capture(|| (0..3).for_each(|_| as_counter()));
+    let labels = [("function", "as_counter"), ("status", "ok")];
+    let total = recorded
+        .counter("function_duration_milliseconds_total", &labels)
+        .unwrap();
+    assert!((6..1000).contains(&total), "{total}");
+    assert!(recorded
+        .histogram("function_duration_milliseconds", &labels)
+        .is_empty());
+}
+
+#[test]
+fn counter_carries_the_fraction_of_sub_unit_calls() {
+    let recorded = capture(|| (0..20).for_each(|_| sub_millisecond_counter()));
+    let total = recorded
+        .counter(
+            "function_duration_milliseconds_total",
+            &[("function", "sub_millisecond_counter"), ("status", "ok")],
+        )
+        .unwrap();
+    // 20 calls of at least 0.2 ms each add up to at least 4 ms.
+    assert!(total >= 4, "{total}");
+}
+
+fn half_a_unit(_: Duration) -> f64 {
+    0.5
+}
+
+#[measured_function(as = "counter", convert = half_a_unit, label_from = route)]
+fn half_unit_counter(route: &str) {}
+
+#[test]
+fn counter_carries_the_fraction_of_each_label_value_apart() {
+    let recorded = capture(|| {
+        for _ in 0..10 {
+            half_unit_counter("a");
+            half_unit_counter("b");
+        }
+    });
+    let total = |route| {
+        recorded.counter(
+            "function_duration_milliseconds_total",
+            &[("function", route), ("status", "ok")],
+        )
+    };
+    // A shared carry would complete every unit on the second route's calls.
+    assert_eq!(total("a"), Some(5));
+    assert_eq!(total("b"), Some(5));
+}
+
+#[test]
+fn gauge_holds_the_latest_duration() {
+    let recorded = capture(as_gauge);
+    let latest = recorded
+        .gauge(
+            "function_duration_milliseconds",
+            &[("function", "as_gauge"), ("status", "ok")],
+        )
+        .unwrap();
+    assert!((5.0..1000.0).contains(&latest), "{latest}");
+}
+
+#[measured_function(last_duration)]
+fn sleeps_for(ms: u64) -> Result<(), ()> {
+    std::thread::sleep(Duration::from_millis(ms));
+    if ms > 20 {
+        Err(())
+    } else {
+        Ok(())
+    }
+}
+
+#[test]
+fn last_duration_holds_the_most_recent_call() {
+    let recorded = capture(|| {
+        assert!(sleeps_for(40).is_err());
+        assert!(sleeps_for(5).is_ok());
+    });
+    let last = recorded
+        .gauge(
+            "function_last_duration_milliseconds",
+            &[("function", "sleeps_for")],
+        )
+        .unwrap();
+    assert!((5.0..40.0).contains(&last), "{last}");
+}
+
+#[test]
+fn last_duration_is_updated_by_failing_calls() {
+    let recorded = capture(|| {
+        assert!(sleeps_for(5).is_ok());
+        assert!(sleeps_for(40).is_err());
+    });
+    let last = recorded
+        .gauge(
+            "function_last_duration_milliseconds",
+            &[("function", "sleeps_for")],
+        )
+        .unwrap();
+    assert!(last >= 40.0, "{last}");
+}
+
+#[measured_async_function(ewma = 0.5, clock = "tokio")]
+async fn smoothed(ms: u64) {
+    tokio::time::sleep(Duration::from_millis(ms)).await;
+}
+
+#[test]
+fn ewma_starts_at_the_first_duration_and_converges() {
+    let recorded = capture_paused(async {
+        smoothed(80).await;
+        for _ in 0..5 {
+            smoothed(20).await;
+        }
+    });
+    // 80, then halfway to 20 on each call: 50, 35, 27.5, 23.75, 21.875.
+    let ewma = recorded.gauge(
+        "function_ewma_duration_milliseconds",
+        &[("function", "smoothed")],
+    );
+    assert_eq!(ewma, Some(21.875));
+}
+
+#[measured_async_function(ewma, clock = "tokio")]
+async fn smoothed_by_default(ms: u64) {
+    tokio::time::sleep(Duration::from_millis(ms)).await;
+}
+
+#[test]
+fn ewma_defaults_to_a_tenth_per_call() {
+    let recorded = capture_paused(async {
+        smoothed_by_default(100).await;
+        smoothed_by_default(0).await;
+    });
+    let ewma = recorded
+        .gauge(
+            "function_ewma_duration_milliseconds",
+            &[("function", "smoothed_by_default")],
+        )
+        .unwrap();
+    assert!((ewma - 90.0).abs() < 1e-9, "{ewma}");
+}
+
+#[measured_async_function("smoothed_tier", tier = "fast", ewma = 0.5, clock = "tokio")]
+async fn smoothed_fast(ms: u64) {
+    tokio::time::sleep(Duration::from_millis(ms)).await;
+}
+
+#[measured_async_function("smoothed_tier", tier = "slow", ewma = 0.5, clock = "tokio")]
+async fn smoothed_slow(ms: u64) {
+    tokio::time::sleep(Duration::from_millis(ms)).await;
+}
+
+#[test]
+fn ewma_keeps_one_average_per_label_value() {
+    let recorded = capture_paused(async {
+        for _ in 0..3 {
+            smoothed_fast(10).await;
+            smoothed_slow(90).await;
+        }
+    });
+    let ewma = |tier| {
+        recorded.gauge(
+            "function_ewma_duration_milliseconds",
+            &[("function", "smoothed_tier"), ("tier", tier)],
+        )
+    };
+    // Interleaved calls would pull a shared average between 10 and 90.
+    assert_eq!(ewma("fast"), Some(10.0));
+    assert_eq!(ewma("slow"), Some(90.0));
+}
+
+#[measured_function(record_len)]
+fn list(n: usize) -> Vec<u32> {
+    vec![0; n]
+}
+
+#[measured_function(record_len, result)]
+fn try_list(n: usize) -> Result<Vec<u32>, String> {
+    if n == 0 {
+        Err("empty".to_owned())
+    } else {
+        Ok(vec![0; n])
+    }
+}
+
+#[test]
+fn record_len_records_the_length_of_a_vec() {
+    let recorded = capture(|| {
+        assert_eq!(list(3).len(), 3);
+        assert_eq!(list(5).len(), 5);
+    });
+    let sizes = recorded.histogram("function_result_size", &[("function", "list")]);
+    assert_eq!(sizes, [3.0, 5.0]);
+    let durations = recorded.histogram(
+        "function_duration_milliseconds",
+        &[("function", "list"), ("status", "ok")],
+    );
+    assert_eq!(durations.len(), 2);
+}
+
+#[test]
+fn record_len_records_ok_vecs_and_skips_errors() {
+    let recorded = capture(|| {
+        assert!(try_list(4).is_ok());
+        assert!(try_list(0).is_err());
+    });
+    assert_eq!(recorded.label_sets("function_result_size").len(), 1);
+    let sizes = recorded.histogram("function_result_size", &[("function", "try_list")]);
+    assert_eq!(sizes, [4.0]);
+}
+
+#[measured_function(summary, labels(tier = "critical"))]
+fn summarized(ms: u64) {
+    std::thread::sleep(Duration::from_millis(ms));
+}
+
+#[test]
+fn summary_records_next_to_the_histogram_with_the_same_labels() {
+    let recorded = capture(|| {
+        summarized(2);
+        summarized(6);
+    });
+    let labels = [
+        ("function", "summarized"),
+        ("status", "ok"),
+        ("tier", "critical"),
+    ];
+    let histogram = recorded.histogram("function_duration_milliseconds", &labels);
+    assert_eq!(histogram.len(), 2);
+    let (key, summary) = metrics_utils::summary::snapshot()
+        .into_iter()
+        .find(|(key, _)| {
+            key.labels()
+                .any(|label| label.key() == "function" && label.value() == "summarized")
+        })
+        .unwrap();
+    assert_eq!(key.name(), "function_duration_milliseconds");
+    let mut summary_labels: Vec<_> = key.labels().map(|l| (l.key(), l.value())).collect();
+    summary_labels.sort_unstable();
+    assert_eq!(summary_labels, labels);
+    assert_eq!(summary.count(), 2);
+    assert_eq!(summary.sum(), histogram.iter().sum::<f64>());
+    assert!(summary.quantile(1.0).unwrap() >= 6.0);
+}
+
+#[measured_function(cpu_time)]
+fn spins() -> u64 {
+    let start = std::time::Instant::now();
+    let mut spins = 0u64;
+    while start.elapsed() < Duration::from_millis(20) {
+        spins = std::hint::black_box(spins + 1);
+    }
+    spins
+}
+
+#[measured_function(cpu_time)]
+fn sleeps() {
+    std::thread::sleep(Duration::from_millis(20));
+}
+
+#[test]
+fn cpu_time_follows_the_wall_time_of_busy_work() {
+    let recorded = capture(|| {
+        spins();
+    });
+    let labels = [("function", "spins"), ("status", "ok")];
+    let cpu = recorded.histogram("function_cpu_milliseconds", &labels);
+    let wall = recorded.histogram("function_duration_milliseconds", &labels);
+    assert!(cpu.len() == 1 && cpu[0] > 0.0, "{cpu:?}");
+    assert!(cpu[0] > wall[0] / 4.0, "cpu {cpu:?}, wall {wall:?}");
+}
+
+#[test]
+fn cpu_time_stays_near_zero_while_sleeping() {
+    let recorded = capture(sleeps);
+    let labels = [("function", "sleeps"), ("status", "ok")];
+    let cpu = recorded.histogram("function_cpu_milliseconds", &labels);
+    let wall = recorded.histogram("function_duration_milliseconds", &labels);
+    assert!(wall.len() == 1 && wall[0] >= 20.0, "{wall:?}");
+    assert!(cpu.len() == 1 && cpu[0] < 5.0, "{cpu:?}");
+}
+
+#[measured_function(utilization)]
+fn spins_busily() -> u64 {
+    let start = std::time::Instant::now();
+    let mut spins = 0u64;
+    while start.elapsed() < Duration::from_millis(20) {
+        spins = std::hint::black_box(spins + 1);
+    }
+    spins
+}
+
+#[measured_function(utilization)]
+fn sleeps_idly() {
+    std::thread::sleep(Duration::from_millis(20));
+}
+
+#[test]
+fn utilization_is_near_one_for_busy_work_and_near_zero_while_sleeping() {
+    let recorded = capture(|| {
+        spins_busily();
+        sleeps_idly();
+    });
+    let utilization = |function| {
+        recorded
+            .gauge(
+                "function_cpu_utilization",
+                &[("function", function), ("status", "ok")],
+            )
+            .unwrap()
+    };
+    let busy = utilization("spins_busily");
+    // The thread may be descheduled for part of the loop on a loaded machine.
+    assert!((0.25..=1.05).contains(&busy), "{busy}");
+    let idle = utilization("sleeps_idly");
+    assert!((0.0..0.25).contains(&idle), "{idle}");
+}
+
+#[measured_function(interarrival)]
+fn arrives() {}
+
+#[test]
+fn interarrival_records_the_gaps_between_calls() {
+    let recorded = capture(|| {
+        arrives();
+        std::thread::sleep(Duration::from_millis(10));
+        arrives();
+        std::thread::sleep(Duration::from_millis(30));
+        arrives();
+    });
+    let gaps = recorded.histogram(
+        "function_interarrival_milliseconds",
+        &[("function", "arrives")],
+    );
+    // The first call has nothing to measure against.
+    assert_eq!(gaps.len(), 2, "{gaps:?}");
+    assert!((10.0..30.0).contains(&gaps[0]), "{gaps:?}");
+    assert!((30.0..1000.0).contains(&gaps[1]), "{gaps:?}");
+}
+
+#[measured_function(interarrival)]
+fn arrives_concurrently() {}
+
+#[test]
+fn interarrival_counts_every_gap_between_concurrent_callers() {
+    let recorder = DebuggingRecorder::new();
+    let snapshotter = recorder.snapshotter();
+    std::thread::scope(|scope| {
+        for _ in 0..4 {
+            scope.spawn(|| {
+                metrics::with_local_recorder(&recorder, || {
+                    for _ in 0..100 {
+                        arrives_concurrently();
+                    }
+                })
+            });
+        }
+    });
+    let gaps = Recorded::from(&snapshotter).histogram(
+        "function_interarrival_milliseconds",
+        &[("function", "arrives_concurrently")],
+    );
+    // Every call but the very first has a predecessor, whichever thread made it.
+    assert_eq!(gaps.len(), 399);
+    assert!(gaps.iter().all(|gap| *gap >= 0.0), "{gaps:?}");
+}
+
+#[measured_function(timestamps)]
+fn stamped() {
+    std::thread::sleep(Duration::from_millis(5));
+}
+
+#[test]
+fn timestamps_set_the_start_and_end_of_the_last_call() {
+    let before = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs_f64();
+    let recorded = capture(stamped);
+    let labels = [("function", "stamped")];
+    let start = recorded
+        .gauge("function_last_start_seconds", &labels)
+        .unwrap();
+    let end = recorded
+        .gauge("function_last_end_seconds", &labels)
+        .unwrap();
+    assert!(start >= before, "{start} < {before}");
+    assert!(end - start >= 0.005, "{start}..{end}");
+}
+
+static ENCODE_DURATION: OnceLock<Histogram> = OnceLock::new();
+
+#[measured_function(handle = ENCODE_DURATION)]
+fn encode() {
+    std::thread::sleep(Duration::from_millis(2));
+}
+
+#[test]
+fn handle_records_into_the_pre_resolved_histogram() {
+    let recorded = capture(|| {
+        let resolved = metrics::histogram!("encode_duration_milliseconds", "codec" => "json");
+        assert!(ENCODE_DURATION.set(resolved).is_ok());
+        encode();
+        encode();
+    });
+    // The handle keeps the name and labels it was resolved with.
+    let samples = recorded.histogram("encode_duration_milliseconds", &[("codec", "json")]);
+    assert_eq!(samples.len(), 2);
+    assert!(samples.iter().all(|sample| *sample >= 2.0), "{samples:?}");
+    assert!(recorded
+        .label_sets("function_duration_milliseconds")
+        .is_empty());
+}
+
+static UNSET_HANDLE: OnceLock<Histogram> = OnceLock::new();
+
+#[measured_function(handle = UNSET_HANDLE)]
+fn unset_handle() {}
+
+#[test]
+fn an_unset_handle_falls_back_to_the_lookup() {
+    let recorded = capture(unset_handle);
+    let samples = recorded.histogram(
+        "function_duration_milliseconds",
+        &[("function", "unset_handle"), ("status", "ok")],
+    );
+    assert_eq!(samples.len(), 1);
+}
+
+fn json_len(value: &serde_json::Value) -> usize {
+    serde_json::to_vec(value).map_or(0, |bytes| bytes.len())
+}
+
+#[measured_function(record_serialized_len = json_len)]
+fn respond(id: u32) -> serde_json::Value {
+    serde_json::json!({ "id": id, "name": "widget" })
+}
+
+#[test]
+fn record_serialized_len_records_the_size_of_the_response() {
+    let recorded = capture(|| {
+        respond(7);
+    });
+    let expected = json_len(&serde_json::json!({ "id": 7, "name": "widget" }));
+    let sizes = recorded.histogram("function_response_bytes", &[("function", "respond")]);
+    assert_eq!(sizes, [expected as f64]);
+    // The latency histogram is recorded as usual.
+    let samples = recorded.histogram(
+        "function_duration_milliseconds",
+        &[("function", "respond"), ("status", "ok")],
+    );
+    assert_eq!(samples.len(), 1);
+}
+
+#[measured_async_function(ringbuffer = 3, clock = "tokio")]
+async fn buffered_recently(ms: u64) {
+    tokio::time::sleep(Duration::from_millis(ms)).await;
+}
+
+#[measured_function(ringbuffer = 8)]
+fn buffered_from_threads() {}
+
+#[test]
+fn ringbuffer_keeps_the_last_n_durations() {
+    assert!(metrics_utils::ring::recent_durations("buffered_recently").is_empty());
+    let recorded = capture_paused(async {
+        for ms in 1..=5 {
+            buffered_recently(ms).await;
+        }
+    });
+    assert_eq!(
+        metrics_utils::ring::recent_durations("buffered_recently"),
+        [3.0, 4.0, 5.0]
+    );
+    // The histogram still gets every call.
+    let samples = recorded.histogram(
+        "async_function_duration_milliseconds",
+        &[
+            ("function", "buffered_recently"),
+            ("status", "ok"),
+            ("completion", "completed"),
+        ],
+    );
+    assert_eq!(samples.len(), 5);
+}
+
+#[test]
+fn ringbuffer_takes_concurrent_writers() {
+    let threads: Vec<_> = (0..4)
+        .map(|_| std::thread::spawn(|| (0..100).for_each(|_| buffered_from_threads())))
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+    assert_eq!(
+        metrics_utils::ring::recent_durations("buffered_from_threads").len(),
+        8
+    );
+}
@@ -0,0 +1,615 @@
+#![cfg(not(any(
+    feature = "disabled",
+    feature = "otel",
+    feature = "buffered",
+    feature = "global_sink",
+    feature = "strict"
+)))]
+
+mod common;
+
+use common::{capture, capture_async};
+use metrics_utils::{measured_async_function, measured_function};
+
+#[measured_function(label_key = "handler")]
+fn process() {}
+
+#[test]
+fn label_key_renames_the_function_label() {
+    let recorded = capture(process);
+    assert_eq!(
+        recorded.label_sets("function_duration_milliseconds"),
+        [[
+            ("handler".to_owned(), "process".to_owned()),
+            ("status".to_owned(), "ok".to_owned()),
+        ]]
+    );
+}
+
+#[measured_function(location)]
+fn located() {}
+
+#[test]
+fn location_labels_the_call_site() {
+    let line = line!() + 3;
+    // Called here rather than passed to `capture`, which would make it the caller.
+    let recorded = capture(|| {
+        located();
+    });
+    let expected = format!("{}:{line}", file!());
+    assert_eq!(
+        recorded.label_sets("function_duration_milliseconds"),
+        [[
+            ("function".to_owned(), "located".to_owned()),
+            ("location".to_owned(), expected),
+            ("status".to_owned(), "ok".to_owned()),
+        ]]
+    );
+}
+
+mod worker {
+    use metrics_utils::measured_function;
+
+    #[measured_function(qualified)]
+    pub fn run() {}
+
+    #[measured_function("custom_run", qualified)]
+    pub fn run_named() {}
+}
+
+#[test]
+fn qualified_prefixes_the_module_path() {
+    let recorded = capture(worker::run);
+    let samples = recorded.histogram(
+        "function_duration_milliseconds",
+        &[("function", "labels::worker::run"), ("status", "ok")],
+    );
+    assert_eq!(samples.len(), 1);
+}
+
+#[test]
+fn a_custom_name_overrides_the_qualified_path() {
+    let recorded = capture(worker::run_named);
+    let samples = recorded.histogram(
+        "function_duration_milliseconds",
+        &[("function", "custom_run"), ("status", "ok")],
+    );
+    assert_eq!(samples.len(), 1);
+}
+
+#[measured_function(monomorphized)]
+fn parse<T: std::str::FromStr>(input: &str) -> Option<T> {
+    input.parse().ok()
+}
+
+#[measured_function(type_param = "U")]
+fn convert<T: Copy, U: From<T>>(value: T) -> U {
+    U::from(value)
+}
+
+#[test]
+fn monomorphized_labels_each_instantiation_with_its_type() {
+    let recorded = capture(|| {
+        assert_eq!(parse::<u32>("1"), Some(1));
+        assert_eq!(parse::<String>("a"), Some("a".to_owned()));
+    });
+    let types: Vec<_> = recorded
+        .label_sets("function_duration_milliseconds")
+        .into_iter()
+        .map(|labels| labels.into_iter().find(|(key, _)| key == "type").unwrap().1)
+        .collect();
+    assert_eq!(types, ["alloc::string::String", "u32"]);
+}
+
+#[test]
+fn type_param_picks_the_labelled_parameter() {
+    let recorded = capture(|| assert_eq!(convert::<u8, u64>(3), 3));
+    let samples = recorded.histogram(
+        "function_duration_milliseconds",
+        &[("function", "convert"), ("status", "ok"), ("type", "u64")],
+    );
+    assert_eq!(samples.len(), 1);
+}
+
+#[measured_function(label_from = route)]
+fn handle(route: String) -> usize {
+    // Moves the parameter, after the label has been read.
+    let owned = route;
+    owned.len()
+}
+
+#[test]
+fn label_from_labels_each_call_with_the_argument() {
+    let recorded = capture(|| {
+        assert_eq!(handle("/users".to_owned()), 6);
+        assert_eq!(handle("/orders".to_owned()), 7);
+        assert_eq!(handle("/users".to_owned()), 6);
+    });
+    let users = recorded.histogram(
+        "function_duration_milliseconds",
+        &[("function", "/users"), ("status", "ok")],
+    );
+    let orders = recorded.histogram(
+        "function_duration_milliseconds",
+        &[("function", "/orders"), ("status", "ok")],
+    );
+    assert_eq!((users.len(), orders.len()), (2, 1));
+}
+
+#[measured_function(label_from_present = filter, label_key = "mode")]
+fn search(query: &str, filter: Option<&str>) -> usize {
+    query.len() + filter.map_or(0, str::len)
+}
+
+#[test]
+fn label_from_present_labels_calls_with_and_without_the_option() {
+    let recorded = capture(|| {
+        assert_eq!(search("shoes", Some("red")), 8);
+        assert_eq!(search("shoes", None), 5);
+        assert_eq!(search("hats", None), 4);
+    });
+    let some = recorded.histogram(
+        "function_duration_milliseconds",
+        &[("mode", "some"), ("status", "ok")],
+    );
+    let none = recorded.histogram(
+        "function_duration_milliseconds",
+        &[("mode", "none"), ("status", "ok")],
+    );
+    assert_eq!((some.len(), none.len()), (1, 2));
+}
+
+#[measured_function(label_from = region)]
+fn deploy(region: Option<String>) {
+    drop(region);
+}
+
+#[test]
+fn label_from_an_option_labels_with_the_value_or_none() {
+    let recorded = capture(|| {
+        deploy(Some("eu-west-1".to_owned()));
+        deploy(None);
+    });
+    let held = recorded.histogram(
+        "function_duration_milliseconds",
+        &[("function", "eu-west-1"), ("status", "ok")],
+    );
+    let none = recorded.histogram(
+        "function_duration_milliseconds",
+        &[("function", "none"), ("status", "ok")],
+    );
+    assert_eq!((held.len(), none.len()), (1, 1));
+}
+
+const CHECKOUT: &str = "checkout";
+
+mod names {
+    pub const REFUND: &str = "refund";
+
+    const SERVICE: &str = "payments";
+
+    /// Picks the name from structured constants, as a `const fn` has to.
+    pub const fn payment_capture() -> &'static str {
+        match SERVICE.len() {
+            0 => "capture",
+            _ => "payments_capture",
+        }
+    }
+}
+
+#[measured_function(name = CHECKOUT)]
+fn named_by_const() {}
+
+#[measured_function(name = names::REFUND)]
+fn named_by_path() {}
+
+#[test]
+fn name_can_be_a_constant() {
+    let recorded = capture(|| {
+        named_by_const();
+        named_by_path();
+    });
+    for function in [CHECKOUT, names::REFUND] {
+        let samples = recorded.histogram(
+            "function_duration_milliseconds",
+            &[("function", function), ("status", "ok")],
+        );
+        assert_eq!(samples.len(), 1, "{function}");
+    }
+}
+
+#[measured_function(name_fn = names::payment_capture, count)]
+fn named_by_const_fn() {}
+
+#[test]
+fn name_fn_names_the_function_with_the_computed_name() {
+    let recorded = capture(named_by_const_fn);
+    let samples = recorded.histogram(
+        "function_duration_milliseconds",
+        &[("function", "payments_capture"), ("status", "ok")],
+    );
+    assert_eq!(samples.len(), 1);
+    assert_eq!(
+        recorded.counter("function_calls_total", &[("function", "payments_capture")]),
+        Some(1)
+    );
+}
+
+#[measured_function("select_users", metric = "db_query_duration")]
+fn select_users() {}
+
+#[measured_function(metric = "db_query_duration")]
+fn insert_order() {}
+
+#[test]
+fn functions_sharing_a_metric_are_told_apart_by_label() {
+    let recorded = capture(|| {
+        select_users();
+        select_users();
+        insert_order();
+    });
+    assert_eq!(recorded.names(), ["db_query_duration"]);
+    let select = recorded.histogram(
+        "db_query_duration",
+        &[("function", "select_users"), ("status", "ok")],
+    );
+    let insert = recorded.histogram(
+        "db_query_duration",
+        &[("function", "insert_order"), ("status", "ok")],
+    );
+    assert_eq!((select.len(), insert.len()), (2, 1));
+    assert_eq!(recorded.label_sets("db_query_duration").len(), 2);
+}
+
+#[measured_function(namespace = "db.queries", count)]
+fn namespaced() {}
+
+#[measured_function(namespace = "db.queries", namespace_separator = ".")]
+fn dotted() {}
+
+#[measured_function(namespace = "db", metric = "lookup_duration")]
+fn namespaced_metric() {}
+
+#[test]
+fn namespace_joins_its_components_with_underscores_by_default() {
+    let recorded = capture(namespaced);
+    assert_eq!(
+        recorded.names(),
+        [
+            "db_queries_function_calls_total",
+            "db_queries_function_duration_milliseconds",
+        ]
+    );
+}
+
+#[test]
+fn namespace_separator_keeps_the_dots() {
+    let recorded = capture(|| {
+        dotted();
+        namespaced_metric();
+    });
+    assert_eq!(
+        recorded.names(),
+        [
+            "db.queries.function_duration_milliseconds",
+            "db_lookup_duration",
+        ]
+    );
+}
+
+#[measured_function("listed", labels(tier = "critical", region = "eu",))]
+fn multi_line_arguments() {}
+
+#[test]
+fn multi_line_arguments_with_trailing_commas_apply() {
+    let recorded = capture(multi_line_arguments);
+    let samples = recorded.histogram(
+        "function_duration_milliseconds",
+        &[
+            ("function", "listed"),
+            ("status", "ok"),
+            ("tier", "critical"),
+            ("region", "eu"),
+        ],
+    );
+    assert_eq!(samples.len(), 1);
+}
+
+macro_rules! handler {
+    ($name:ident) => {
+        #[measured_function]
+        fn $name() {}
+    };
+    ($name:ident, $label:literal) => {
+        #[measured_function($label)]
+        fn $name() {}
+    };
+}
+
+handler!(get_user);
+handler!(r#match);
+handler!(list_orders, "orders::list");
+
+#[test]
+fn macro_generated_functions_are_labelled_with_the_substituted_name() {
+    let recorded = capture(|| {
+        get_user();
+        r#match();
+        list_orders();
+    });
+    for function in ["get_user", "match", "orders::list"] {
+        let samples = recorded.histogram(
+            "function_duration_milliseconds",
+            &[("function", function), ("status", "ok")],
+        );
+        assert_eq!(samples.len(), 1, "{function}");
+    }
+}
+
+#[measured_function(count)]
+#[metric(name = "checkout_submit", metric = "checkout_duration")]
+#[inline]
+fn from_marker() {}
+
+#[test]
+fn the_metric_marker_sets_the_name_and_metric() {
+    // The marker is consumed; left on the function it would fail to compile as an unknown
+    // attribute.
+    let recorded = capture(from_marker);
+    assert_eq!(
+        recorded.label_sets("checkout_duration"),
+        [[
+            ("function".to_owned(), "checkout_submit".to_owned()),
+            ("status".to_owned(), "ok".to_owned()),
+        ]]
+    );
+    assert_eq!(
+        recorded.counter("function_calls_total", &[("function", "checkout_submit")]),
+        Some(1)
+    );
+}
+
+#[measured_function(also_record = "legacy_latency", also_record = "migration_latency")]
+fn dual_emitted() {
+    std::thread::sleep(std::time::Duration::from_millis(1));
+}
+
+#[test]
+fn also_record_sends_the_same_sample_to_every_name() {
+    let recorded = capture(dual_emitted);
+    let labels = [("function", "dual_emitted"), ("status", "ok")];
+    let primary = recorded.histogram("function_duration_milliseconds", &labels);
+    assert_eq!(primary.len(), 1);
+    for name in ["legacy_latency", "migration_latency"] {
+        assert_eq!(recorded.histogram(name, &labels), primary, "{name}");
+    }
+}
+
+#[test]
+fn nested_functions_are_labelled_with_their_own_name() {
+    #[measured_function]
+    fn helper(value: u32) -> u32 {
+        value + 1
+    }
+
+    #[measured_function(qualified)]
+    fn qualified_helper() {}
+
+    let recorded = capture(|| {
+        assert_eq!(helper(1), 2);
+        qualified_helper();
+    });
+    assert_eq!(
+        recorded.label_sets("function_duration_milliseconds"),
+        [
+            [
+                ("function".to_owned(), "helper".to_owned()),
+                ("status".to_owned(), "ok".to_owned()),
+            ],
+            // The enclosing function is not part of the module path.
+            [
+                ("function".to_owned(), "labels::qualified_helper".to_owned()),
+                ("status".to_owned(), "ok".to_owned()),
+            ],
+        ]
+    );
+}
+
+tokio::task_local! {
+    static TENANT: String;
+}
+
+fn tenant() -> Option<String> {
+    TENANT.try_with(String::clone).ok()
+}
+
+#[measured_async_function(label_from_context = tenant, context_key = "tenant")]
+async fn tenant_request() {
+    tokio::task::yield_now().await;
+}
+
+#[test]
+fn label_from_context_labels_calls_made_with_a_context() {
+    let recorded = capture_async(async {
+        TENANT.scope("acme".to_owned(), tenant_request()).await;
+        tenant_request().await;
+    });
+    let with_tenant = recorded.histogram(
+        "async_function_duration_milliseconds",
+        &[
+            ("function", "tenant_request"),
+            ("status", "ok"),
+            ("completion", "completed"),
+            ("tenant", "acme"),
+        ],
+    );
+    let without = recorded.histogram(
+        "async_function_duration_milliseconds",
+        &[
+            ("function", "tenant_request"),
+            ("status", "ok"),
+            ("completion", "completed"),
+        ],
+    );
+    assert_eq!((with_tenant.len(), without.len()), (1, 1));
+}
+
+fn request_labels() -> Vec<(&'static str, String)> {
+    vec![
+        ("region", "eu-west".to_owned()),
+        ("shard", 7.to_string()),
+        // Already set by the macro, so left out.
+        ("function", "spoofed".to_owned()),
+    ]
+}
+
+fn label_values() -> Vec<metrics::Label> {
+    vec![metrics::Label::new("plan", "pro")]
+}
+
+#[measured_function(labels_from = request_labels)]
+fn dynamic_labels() {}
+
+#[measured_function(labels_from = label_values)]
+fn dynamic_label_values() {}
+
+#[test]
+fn labels_from_adds_the_returned_labels_without_duplicating_function() {
+    let recorded = capture(|| {
+        dynamic_labels();
+        dynamic_label_values();
+    });
+    let plain = recorded.histogram(
+        "function_duration_milliseconds",
+        &[
+            ("function", "dynamic_labels"),
+            ("status", "ok"),
+            ("region", "eu-west"),
+            ("shard", "7"),
+        ],
+    );
+    let label_type = recorded.histogram(
+        "function_duration_milliseconds",
+        &[
+            ("function", "dynamic_label_values"),
+            ("status", "ok"),
+            ("plan", "pro"),
+        ],
+    );
+    assert_eq!((plain.len(), label_type.len()), (1, 1));
+}
+
+#[measured_function(label_from = user, max_cardinality = 2)]
+fn per_user(user: u32) -> u32 {
+    user
+}
+
+#[test]
+fn max_cardinality_collapses_new_values_past_the_cap() {
+    let recorded = capture(|| {
+        for user in [1, 2, 3, 1, 4, 2, 5] {
+            assert_eq!(per_user(user), user);
+        }
+    });
+    let samples = |function| {
+        recorded
+            .histogram(
+                "function_duration_milliseconds",
+                &[("function", function), ("status", "ok")],
+            )
+            .len()
+    };
+    // The first two values keep their label, later ones share the sentinel.
+    assert_eq!((samples("1"), samples("2")), (2, 2));
+    assert_eq!(samples("__overflow__"), 3);
+    assert_eq!(
+        recorded.label_sets("function_duration_milliseconds").len(),
+        3
+    );
+}
+
+#[measured_function(correlation_from = pipeline_id, count)]
+fn transform_stage(pipeline_id: String, rows: Vec<u32>) -> usize {
+    // Moves the parameter, after the label has been read.
+    drop(pipeline_id);
+    rows.len()
+}
+
+#[test]
+fn correlation_from_labels_each_call_next_to_the_function() {
+    let recorded = capture(|| {
+        assert_eq!(transform_stage("41".to_owned(), vec![1, 2]), 2);
+        assert_eq!(transform_stage("42".to_owned(), vec![3]), 1);
+        assert_eq!(transform_stage("41".to_owned(), vec![]), 0);
+    });
+    let samples = |correlation| {
+        recorded
+            .histogram(
+                "function_duration_milliseconds",
+                &[
+                    ("function", "transform_stage"),
+                    ("correlation", correlation),
+                    ("status", "ok"),
+                ],
+            )
+            .len()
+    };
+    assert_eq!((samples("41"), samples("42")), (2, 1));
+    assert_eq!(
+        recorded.counter(
+            "function_calls_total",
+            &[("function", "transform_stage"), ("correlation", "42")]
+        ),
+        Some(1)
+    );
+}
+
+#[measured_function(time_bucket, count)]
+fn bucketed_by_hour() {}
+
+#[measured_function(time_bucket = "local")]
+fn bucketed_by_local_hour() {}
+
+/// The current hour of day in UTC.
+fn utc_hour() -> String {
+    let seconds = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    (seconds / 3600 % 24).to_string()
+}
+
+#[test]
+fn time_bucket_labels_calls_with_the_utc_hour() {
+    let before = utc_hour();
+    let recorded = capture(bucketed_by_hour);
+    let after = utc_hour();
+    let sets = recorded.label_sets("function_duration_milliseconds");
+    assert_eq!(sets.len(), 1);
+    let hour = sets[0]
+        .iter()
+        .find_map(|(key, value)| (key == "hour").then_some(value))
+        .unwrap();
+    // The hour may have turned during the call.
+    assert!(*hour == before || *hour == after, "{hour}");
+    assert_eq!(
+        recorded.counter(
+            "function_calls_total",
+            &[("function", "bucketed_by_hour"), ("hour", hour)]
+        ),
+        Some(1)
+    );
+}
+
+#[test]
+fn time_bucket_local_labels_calls_with_an_hour_of_day() {
+    let recorded = capture(bucketed_by_local_hour);
+    let sets = recorded.label_sets("function_duration_milliseconds");
+    let hour: u32 = sets[0]
+        .iter()
+        .find_map(|(key, value)| (key == "hour").then_some(value))
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!(hour < 24, "{hour}");
+}
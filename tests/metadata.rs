@@ -0,0 +1,163 @@
+#![cfg(not(any(
+    feature = "disabled",
+    feature = "otel",
+    feature = "buffered",
+    feature = "global_sink",
+    feature = "strict"
+)))]
+
+mod common;
+
+use common::{capture, capture_async};
+use metrics::Unit;
+use metrics_utils::catalog::{collected_metrics, MetricDescriptor};
+use metrics_utils::{measured_async_function, measured_function};
+
+#[measured_function(describe = "Time spent parsing a request")]
+fn described() {}
+
+#[measured_function(metric = "first_duration_seconds", unit = "seconds", describe)]
+fn first() {}
+
+#[measured_function(metric = "second_duration_milliseconds", describe)]
+fn second() {}
+
+#[test]
+fn describe_registers_the_unit_and_text() {
+    let recorded = capture(|| {
+        described();
+        described();
+    });
+    assert_eq!(
+        recorded.description("function_duration_milliseconds"),
+        Some((
+            Some(Unit::Milliseconds),
+            "Time spent parsing a request".to_owned()
+        ))
+    );
+}
+
+#[test]
+fn every_described_function_registers_its_own_metric() {
+    let recorded = capture(|| {
+        first();
+        second();
+    });
+    assert_eq!(
+        recorded.description("first_duration_seconds"),
+        Some((Some(Unit::Seconds), "Execution time of first".to_owned()))
+    );
+    assert_eq!(
+        recorded.description("second_duration_milliseconds"),
+        Some((
+            Some(Unit::Milliseconds),
+            "Execution time of second".to_owned()
+        ))
+    );
+}
+
+#[measured_function(metric = "bucketed_duration_milliseconds", buckets = [0.001, 0.01, 0.1, 1.0])]
+fn bucketed() {}
+
+#[test]
+fn buckets_are_registered_on_the_first_call() {
+    let registered = || {
+        metrics_utils::buckets::registered()
+            .into_iter()
+            .find(|(name, _)| *name == "bucketed_duration_milliseconds")
+    };
+    assert_eq!(registered(), None);
+    capture(bucketed);
+    assert_eq!(
+        registered(),
+        Some((
+            "bucketed_duration_milliseconds",
+            &[0.001, 0.01, 0.1, 1.0][..]
+        ))
+    );
+}
+
+#[test]
+fn default_buckets_cover_the_standard_duration_histograms() {
+    let buckets = metrics_utils::buckets::default_buckets();
+    let bounds = |name: &str| {
+        buckets
+            .iter()
+            .find(|(key, _)| key.as_str() == name)
+            .map(|(_, bounds)| bounds.clone())
+            .unwrap_or_else(|| panic!("no buckets for {name}"))
+    };
+    for name in [
+        "function_duration_milliseconds",
+        "async_function_duration_milliseconds",
+        "function_duration_seconds",
+        "closure_duration_milliseconds",
+        "block_duration_milliseconds",
+    ] {
+        let bounds = bounds(name);
+        assert!(!bounds.is_empty(), "{name}");
+        assert!(
+            bounds.windows(2).all(|pair| pair[0] < pair[1]),
+            "{name}: {bounds:?}"
+        );
+        assert!(
+            bounds.iter().all(|bound| *bound > 0.0),
+            "{name}: {bounds:?}"
+        );
+    }
+    // The same span of time, scaled to the unit in the name.
+    let milliseconds = bounds("function_duration_milliseconds");
+    assert_eq!(milliseconds.first(), Some(&0.1));
+    assert_eq!(milliseconds.last(), Some(&10000.0));
+    let seconds = bounds("function_duration_seconds");
+    assert_eq!(seconds.first(), Some(&0.0001));
+    assert_eq!(seconds.last(), Some(&10.0));
+    assert_eq!(
+        bounds("function_duration_microseconds").first(),
+        Some(&100.0)
+    );
+}
+
+#[measured_function]
+fn catalogued() {}
+
+#[measured_async_function("catalogued_task", unit = "seconds", label_key = "task")]
+async fn catalogued_async() {}
+
+#[test]
+fn instrumented_functions_show_up_in_the_catalog_once_called() {
+    let descriptor = |function: &str| {
+        collected_metrics()
+            .into_iter()
+            .filter(|descriptor| descriptor.function == function)
+            .collect::<Vec<_>>()
+    };
+    assert!(descriptor("catalogued").is_empty());
+    capture(|| {
+        catalogued();
+        catalogued();
+    });
+    capture_async(catalogued_async());
+    assert_eq!(
+        descriptor("catalogued"),
+        [MetricDescriptor {
+            name: "function_duration_milliseconds",
+            unit: "milliseconds",
+            label_key: "function",
+            label: Some("catalogued"),
+            function: "catalogued",
+            module_path: module_path!(),
+        }]
+    );
+    assert_eq!(
+        descriptor("catalogued_async"),
+        [MetricDescriptor {
+            name: "async_function_duration_seconds",
+            unit: "seconds",
+            label_key: "task",
+            label: Some("catalogued_task"),
+            function: "catalogued_async",
+            module_path: module_path!(),
+        }]
+    );
+}
@@ -0,0 +1,102 @@
+//! Run with `cargo test --features otel,metrics-utils/otel --test otel`.
+
+#![cfg(feature = "otel")]
+
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use metrics_utils::{measured_async_function, measured_function};
+use opentelemetry::metrics::MeterProvider;
+use opentelemetry_sdk::metrics::data::{AggregatedMetrics, MetricData};
+use opentelemetry_sdk::metrics::{InMemoryMetricExporter, PeriodicReader, SdkMeterProvider};
+
+type Exported = (u64, f64, Vec<(String, String)>);
+
+/// Exports to memory through a provider whose meter is set once for the whole test binary.
+fn provider() -> &'static (SdkMeterProvider, InMemoryMetricExporter) {
+    static PROVIDER: OnceLock<(SdkMeterProvider, InMemoryMetricExporter)> = OnceLock::new();
+    PROVIDER.get_or_init(|| {
+        let exporter = InMemoryMetricExporter::default();
+        let provider = SdkMeterProvider::builder()
+            .with_reader(PeriodicReader::builder(exporter.clone()).build())
+            .build();
+        metrics_utils::otel::set_meter(provider.meter("tests"))
+            .unwrap_or_else(|_| panic!("already set"));
+        (provider, exporter)
+    })
+}
+
+/// Flushes and returns the count and sum of the `metric` data point with
+/// `function="<function>"`, and its attributes sorted by key.
+fn exported(metric: &str, function: &str) -> Option<Exported> {
+    let (provider, exporter) = provider();
+    provider.force_flush().unwrap();
+    let mut found = None;
+    for resource in exporter.get_finished_metrics().unwrap() {
+        for scope in resource.scope_metrics() {
+            for exported in scope.metrics().filter(|exported| exported.name() == metric) {
+                let AggregatedMetrics::F64(MetricData::Histogram(histogram)) = exported.data()
+                else {
+                    panic!("`{metric}` is not an f64 histogram");
+                };
+                for point in histogram.data_points() {
+                    let mut attributes: Vec<_> = point
+                        .attributes()
+                        .map(|kv| (kv.key.to_string(), kv.value.to_string()))
+                        .collect();
+                    attributes.sort_unstable();
+                    if attributes.contains(&("function".to_owned(), function.to_owned())) {
+                        // Later exports hold the cumulative totals.
+                        found = Some((point.count(), point.sum(), attributes));
+                    }
+                }
+            }
+        }
+    }
+    found
+}
+
+#[measured_function(labels(tier = "critical"))]
+fn exported_sync() {
+    std::thread::sleep(Duration::from_millis(5));
+}
+
+#[test]
+fn sync_durations_are_exported_with_their_labels_as_attributes() {
+    provider();
+    exported_sync();
+    let (count, sum, attributes) =
+        exported("function_duration_milliseconds", "exported_sync").unwrap();
+    assert_eq!(count, 1);
+    assert!(sum >= 5.0, "{sum}");
+    assert_eq!(
+        attributes,
+        [
+            ("function".to_owned(), "exported_sync".to_owned()),
+            ("status".to_owned(), "ok".to_owned()),
+            ("tier".to_owned(), "critical".to_owned()),
+        ]
+    );
+}
+
+#[measured_async_function]
+async fn exported_async() {
+    tokio::time::sleep(Duration::from_millis(5)).await;
+}
+
+#[test]
+fn async_durations_are_exported() {
+    provider();
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    runtime.block_on(async {
+        exported_async().await;
+        exported_async().await;
+    });
+    let (count, sum, _) =
+        exported("async_function_duration_milliseconds", "exported_async").unwrap();
+    assert_eq!(count, 2);
+    assert!(sum >= 10.0, "{sum}");
+}
@@ -0,0 +1,52 @@
+#![cfg(not(any(
+    feature = "disabled",
+    feature = "otel",
+    feature = "buffered",
+    feature = "global_sink",
+    feature = "strict"
+)))]
+
+//! `METRICS_MACROS_PREFIX`, read while the attributes expand. Without the variable this checks
+//! the names are left alone; run it with the variable set to check they are prefixed, e.g.
+//! `METRICS_MACROS_PREFIX=myapp cargo test --test prefix`.
+
+mod common;
+
+use common::{capture, capture_async};
+use metrics_utils::{measured_async_function, measured_function};
+
+/// `name` as the macros emit it in this build.
+fn prefixed(name: &str) -> String {
+    match option_env!("METRICS_MACROS_PREFIX") {
+        Some("") | None => name.to_owned(),
+        Some(prefix) if prefix.ends_with('_') => format!("{prefix}{name}"),
+        Some(prefix) => format!("{prefix}_{name}"),
+    }
+}
+
+#[measured_function(count)]
+fn prefixed_sync() {}
+
+#[measured_async_function]
+async fn prefixed_async() {}
+
+#[test]
+fn sync_metric_names_carry_the_prefix() {
+    let recorded = capture(prefixed_sync);
+    assert_eq!(
+        recorded.names(),
+        [
+            prefixed("function_calls_total"),
+            prefixed("function_duration_milliseconds"),
+        ]
+    );
+}
+
+#[test]
+fn async_metric_names_carry_the_prefix() {
+    let recorded = capture_async(prefixed_async());
+    assert_eq!(
+        recorded.names(),
+        [prefixed("async_function_duration_milliseconds")]
+    );
+}
@@ -0,0 +1,50 @@
+//! `reset_all_state` resets every instrumented function of the process, so its test lives in
+//! a file of its own, away from the tests relying on that state.
+
+#![cfg(not(any(
+    feature = "disabled",
+    feature = "otel",
+    feature = "buffered",
+    feature = "global_sink",
+    feature = "strict"
+)))]
+
+mod common;
+
+use common::capture;
+use metrics_utils::measured_function;
+use metrics_utils::reset::reset_all_state;
+
+const SYNC: &str = "function_duration_milliseconds";
+
+#[measured_function(warmup = 2)]
+fn warms_up() {}
+
+#[measured_function(sample = 10)]
+fn sampled() {}
+
+#[test]
+fn reset_all_state_starts_the_counters_afresh() {
+    let calls = || {
+        capture(|| {
+            (0..3).for_each(|_| warms_up());
+            (0..2).for_each(|_| sampled());
+        })
+    };
+    let counts = |recorded: &common::Recorded| {
+        (
+            recorded
+                .histogram(SYNC, &[("function", "warms_up"), ("status", "ok")])
+                .len(),
+            recorded
+                .histogram(SYNC, &[("function", "sampled"), ("status", "ok")])
+                .len(),
+        )
+    };
+    // The first three calls include the warm-up, and the first sampled call is recorded.
+    assert_eq!(counts(&calls()), (1, 1));
+    // Without a reset the warm-up is over and the sample counter has moved on.
+    assert_eq!(counts(&calls()), (3, 0));
+    reset_all_state();
+    assert_eq!(counts(&calls()), (1, 1));
+}
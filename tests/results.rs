@@ -0,0 +1,332 @@
+#![cfg(not(any(
+    feature = "disabled",
+    feature = "otel",
+    feature = "buffered",
+    feature = "global_sink",
+    feature = "strict"
+)))]
+
+mod common;
+
+use common::{capture, capture_async, capture_with, Recorded};
+use metrics_utils::{
+    measured_async_function, measured_function, measured_try_function, VariantName,
+};
+
+#[measured_function(result)]
+fn check(valid: bool) -> Result<(), String> {
+    if valid {
+        Ok(())
+    } else {
+        Err("invalid".to_owned())
+    }
+}
+
+#[measured_async_function(result)]
+async fn check_async(valid: bool) -> Result<(), String> {
+    check(valid)
+}
+
+#[test]
+fn ok_and_err_returns_carry_distinct_status_labels() {
+    let recorded = capture(|| {
+        assert!(check(true).is_ok());
+        assert!(check(false).is_err());
+    });
+    let samples = |status| {
+        recorded.histogram(
+            "function_duration_milliseconds",
+            &[("function", "check"), ("status", status)],
+        )
+    };
+    assert_eq!((samples("ok").len(), samples("err").len()), (1, 1));
+}
+
+#[test]
+fn async_results_are_labelled_the_same_way() {
+    let recorded = capture_async(async {
+        assert!(check_async(true).await.is_ok());
+        assert!(check_async(false).await.is_err());
+    });
+    let samples = |status| {
+        recorded.histogram(
+            "async_function_duration_milliseconds",
+            &[
+                ("function", "check_async"),
+                ("status", status),
+                ("completion", "completed"),
+            ],
+        )
+    };
+    assert_eq!((samples("ok").len(), samples("err").len()), (1, 1));
+}
+
+#[derive(Debug)]
+struct TimeoutError;
+
+impl std::fmt::Display for TimeoutError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("timed out")
+    }
+}
+
+#[measured_function(error_kind)]
+fn by_type_name(fail: bool) -> Result<(), TimeoutError> {
+    if fail {
+        Err(TimeoutError)
+    } else {
+        Ok(())
+    }
+}
+
+#[measured_function(error_kind = display)]
+fn by_display() -> Result<(), TimeoutError> {
+    Err(TimeoutError)
+}
+
+#[test]
+fn error_kind_labels_errors_with_their_type_name() {
+    let recorded = capture(|| {
+        assert!(by_type_name(true).is_err());
+        assert!(by_type_name(false).is_ok());
+    });
+    let err = recorded.histogram(
+        "function_duration_milliseconds",
+        &[
+            ("function", "by_type_name"),
+            ("status", "err"),
+            ("error_kind", "TimeoutError"),
+        ],
+    );
+    let ok = recorded.histogram(
+        "function_duration_milliseconds",
+        &[("function", "by_type_name"), ("status", "ok")],
+    );
+    assert_eq!((err.len(), ok.len()), (1, 1));
+}
+
+#[test]
+fn error_kind_display_labels_errors_with_their_message() {
+    let recorded = capture(|| assert!(by_display().is_err()));
+    let samples = recorded.histogram(
+        "function_duration_milliseconds",
+        &[
+            ("function", "by_display"),
+            ("status", "err"),
+            ("error_kind", "timed out"),
+        ],
+    );
+    assert_eq!(samples.len(), 1);
+}
+
+#[measured_try_function]
+fn try_parse(input: &str) -> Result<u32, std::num::ParseIntError> {
+    let value = input.parse::<u32>()?;
+    Ok(value * 2)
+}
+
+#[test]
+fn try_function_records_ok_calls_and_counts_errors() {
+    let recorded = capture(|| {
+        assert_eq!(try_parse("4"), Ok(8));
+        assert!(try_parse("four").is_err());
+        assert!(try_parse("five").is_err());
+    });
+    assert_eq!(
+        recorded.label_sets("function_duration_milliseconds"),
+        [[
+            ("function".to_owned(), "try_parse".to_owned()),
+            ("status".to_owned(), "ok".to_owned()),
+        ]]
+    );
+    let ok = recorded.histogram(
+        "function_duration_milliseconds",
+        &[("function", "try_parse"), ("status", "ok")],
+    );
+    assert_eq!(ok.len(), 1);
+    assert_eq!(
+        recorded.counter("function_errors_total", &[("function", "try_parse")]),
+        Some(2)
+    );
+}
+
+#[measured_try_function]
+async fn try_load(id: u32) -> Result<u32, String> {
+    if id == 0 {
+        return Err("missing".to_owned());
+    }
+    Ok(id)
+}
+
+#[test]
+fn try_function_leaves_async_errors_out_of_the_histogram() {
+    let recorded = capture_async(async {
+        assert!(try_load(0).await.is_err());
+    });
+    assert!(recorded
+        .label_sets("async_function_duration_milliseconds")
+        .is_empty());
+    assert_eq!(
+        recorded.counter("function_errors_total", &[("function", "try_load")]),
+        Some(1)
+    );
+}
+
+#[derive(Debug, VariantName)]
+enum Outcome {
+    Hit,
+    Miss(String),
+    Expired { age: u64 },
+}
+
+#[measured_function(label_variant)]
+fn classify(key: u32) -> Outcome {
+    match key % 3 {
+        0 => Outcome::Hit,
+        1 => Outcome::Miss(format!("key {key}")),
+        _ => Outcome::Expired {
+            age: u64::from(key),
+        },
+    }
+}
+
+#[test]
+fn label_variant_labels_each_call_with_the_returned_variant() {
+    let recorded = capture(|| {
+        assert!(matches!(classify(0), Outcome::Hit));
+        assert!(matches!(classify(3), Outcome::Hit));
+        // The value is returned intact, not moved out by the labelling.
+        assert!(matches!(classify(1), Outcome::Miss(message) if message == "key 1"));
+        assert!(matches!(classify(2), Outcome::Expired { age: 2 }));
+    });
+    for (variant, calls) in [("Hit", 2), ("Miss", 1), ("Expired", 1)] {
+        let samples = recorded.histogram(
+            "function_duration_milliseconds",
+            &[
+                ("function", "classify"),
+                ("status", "ok"),
+                ("variant", variant),
+            ],
+        );
+        assert_eq!(samples.len(), calls, "{variant}");
+    }
+}
+
+#[measured_function(split_result)]
+fn split(fail: bool) -> Result<(), String> {
+    if fail {
+        Err("nope".to_owned())
+    } else {
+        Ok(())
+    }
+}
+
+#[test]
+fn split_result_records_each_branch_to_its_own_metric() {
+    let recorded = capture(|| {
+        assert!(split(false).is_ok());
+        assert!(split(true).is_err());
+        assert!(split(true).is_err());
+    });
+    let labels = [("function", "split")];
+    assert_eq!(
+        recorded
+            .histogram("function_duration_ok_milliseconds", &labels)
+            .len(),
+        1
+    );
+    assert_eq!(
+        recorded
+            .histogram("function_duration_err_milliseconds", &labels)
+            .len(),
+        2
+    );
+    assert!(recorded
+        .label_sets("function_duration_milliseconds")
+        .is_empty());
+}
+
+#[measured_function(success_ratio)]
+fn mostly_succeeds(call: u32) -> Result<u32, String> {
+    if call % 4 == 3 {
+        Err(format!("call {call} failed"))
+    } else {
+        Ok(call)
+    }
+}
+
+#[test]
+fn success_ratio_publishes_the_share_of_ok_calls() {
+    let mut ratios = Vec::new();
+    let mut samples = 0;
+    capture_with(|snapshotter| {
+        for call in 0..8 {
+            let _ = mostly_succeeds(call);
+            let recorded = Recorded::from(snapshotter);
+            ratios.extend(
+                recorded.gauge("function_success_ratio", &[("function", "mostly_succeeds")]),
+            );
+            samples += recorded
+                .histogram(
+                    "function_duration_milliseconds",
+                    &[("function", "mostly_succeeds"), ("status", "ok")],
+                )
+                .len();
+        }
+    });
+    // Each call sets the gauge to the ratio so far, converging on three in four.
+    assert_eq!(
+        ratios,
+        [1.0, 1.0, 1.0, 0.75, 0.8, 5.0 / 6.0, 6.0 / 7.0, 0.75]
+    );
+    // The duration histogram is recorded as usual.
+    assert_eq!(samples, 8);
+}
+
+#[measured_function(windowed_error_rate = "10s")]
+fn fails_sometimes(fail: bool) -> Result<(), String> {
+    if fail {
+        Err("failed".to_owned())
+    } else {
+        Ok(())
+    }
+}
+
+#[measured_function(windowed_error_rate = "100ms")]
+fn failed_a_while_ago(fail: bool) -> Result<(), String> {
+    if fail {
+        Err("failed".to_owned())
+    } else {
+        Ok(())
+    }
+}
+
+#[test]
+fn windowed_error_rate_publishes_the_share_of_errors_in_the_window() {
+    let recorded = capture(|| {
+        for fail in [false, true, false, false, true, false, false, false] {
+            let _ = fails_sometimes(fail);
+        }
+    });
+    assert_eq!(
+        recorded.gauge("function_error_rate", &[("function", "fails_sometimes")]),
+        Some(0.25)
+    );
+}
+
+#[test]
+fn windowed_error_rate_forgets_calls_older_than_the_window() {
+    let recorded = capture_with(|snapshotter| {
+        let _ = failed_a_while_ago(true);
+        let _ = failed_a_while_ago(false);
+        let rate = Recorded::from(snapshotter)
+            .gauge("function_error_rate", &[("function", "failed_a_while_ago")]);
+        assert_eq!(rate, Some(0.5));
+        std::thread::sleep(std::time::Duration::from_millis(150));
+        let _ = failed_a_while_ago(false);
+    });
+    assert_eq!(
+        recorded.gauge("function_error_rate", &[("function", "failed_a_while_ago")]),
+        Some(0.0)
+    );
+}
@@ -0,0 +1,142 @@
+#![cfg(not(any(
+    feature = "disabled",
+    feature = "otel",
+    feature = "buffered",
+    feature = "global_sink",
+    feature = "strict"
+)))]
+
+mod common;
+
+use std::panic::{self, AssertUnwindSafe};
+use std::time::Duration;
+
+use common::{capture, capture_async};
+use metrics_utils::timing::{measure_fn, measure_future};
+use metrics_utils::MeasureGuard;
+
+const METRIC: &str = "function_duration_milliseconds";
+
+#[test]
+fn guard_records_when_dropped() {
+    let recorded = capture(|| {
+        let _guard = MeasureGuard::new(METRIC, "dropped");
+        std::thread::sleep(Duration::from_millis(5));
+    });
+    let samples = recorded.histogram(METRIC, &[("function", "dropped"), ("status", "ok")]);
+    assert_eq!(samples.len(), 1);
+    assert!((5.0..1000.0).contains(&samples[0]), "{samples:?}");
+}
+
+#[test]
+fn guard_records_once_when_finished() {
+    let mut finished = Duration::ZERO;
+    let recorded = capture(|| {
+        let guard = MeasureGuard::new(METRIC, "finished");
+        std::thread::sleep(Duration::from_millis(5));
+        finished = guard.finish();
+        std::thread::sleep(Duration::from_millis(20));
+    });
+    let samples = recorded.histogram(METRIC, &[("function", "finished"), ("status", "ok")]);
+    assert_eq!(samples, [finished.as_secs_f64() * 1000.0]);
+    assert!(finished < Duration::from_millis(20), "{finished:?}");
+}
+
+#[test]
+fn guard_dropped_by_a_panic_records_panic() {
+    let recorded = capture(|| {
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let _guard = MeasureGuard::new(METRIC, "unwound");
+            panic!("dropping the guard while unwinding");
+        }));
+        assert!(result.is_err());
+    });
+    let samples = recorded.histogram(METRIC, &[("function", "unwound"), ("status", "panic")]);
+    assert_eq!(samples.len(), 1);
+}
+
+#[test]
+fn measure_future_records_the_awaited_time() {
+    let recorded = capture_async(async {
+        let value = measure_future(
+            "async_function_duration_milliseconds",
+            "helper_sleep",
+            async {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                5
+            },
+        )
+        .await;
+        assert_eq!(value, 5);
+    });
+    let samples = recorded.histogram(
+        "async_function_duration_milliseconds",
+        &[
+            ("function", "helper_sleep"),
+            ("status", "ok"),
+            ("completion", "completed"),
+        ],
+    );
+    assert!(
+        samples.len() == 1 && (20.0..1000.0).contains(&samples[0]),
+        "{samples:?}"
+    );
+}
+
+#[test]
+fn measure_future_records_a_dropped_future_as_cancelled() {
+    let recorded = capture_async(async {
+        let pending = measure_future(
+            "async_function_duration_milliseconds",
+            "helper_pending",
+            std::future::pending::<()>(),
+        );
+        let timed_out = tokio::time::timeout(Duration::from_millis(1), pending).await;
+        assert!(timed_out.is_err());
+    });
+    let samples = recorded.histogram(
+        "async_function_duration_milliseconds",
+        &[
+            ("function", "helper_pending"),
+            ("status", "cancelled"),
+            ("completion", "cancelled"),
+        ],
+    );
+    assert_eq!(samples.len(), 1);
+}
+
+#[test]
+fn measure_fn_records_and_returns_the_value() {
+    let recorded = capture(|| {
+        let value = measure_fn("function_duration_milliseconds", "helper_sum", || {
+            std::thread::sleep(Duration::from_millis(2));
+            1 + 2
+        });
+        assert_eq!(value, 3);
+    });
+    let samples = recorded.histogram(
+        "function_duration_milliseconds",
+        &[("function", "helper_sum"), ("status", "ok")],
+    );
+    assert!(
+        samples.len() == 1 && (2.0..1000.0).contains(&samples[0]),
+        "{samples:?}"
+    );
+}
+
+#[test]
+fn measure_fn_records_a_panicking_closure() {
+    let recorded = capture(|| {
+        let result = panic::catch_unwind(|| {
+            measure_fn("function_duration_milliseconds", "helper_panics", || {
+                panic!("boom")
+            })
+        });
+        assert!(result.is_err());
+    });
+    let samples = recorded.histogram(
+        "function_duration_milliseconds",
+        &[("function", "helper_panics"), ("status", "panic")],
+    );
+    assert_eq!(samples.len(), 1);
+}
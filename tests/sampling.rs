@@ -0,0 +1,191 @@
+#![cfg(not(any(
+    feature = "disabled",
+    feature = "otel",
+    feature = "buffered",
+    feature = "global_sink",
+    feature = "strict"
+)))]
+
+mod common;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use common::capture;
+use metrics_utils::measured_function;
+
+const SYNC: &str = "function_duration_milliseconds";
+
+#[measured_function(sample = 100)]
+fn sampled() {}
+
+#[measured_function(sample = 1000)]
+fn other_sampled() {}
+
+#[test]
+fn sample_records_one_call_in_n() {
+    let recorded = capture(|| (0..1000).for_each(|_| sampled()));
+    let samples = recorded.histogram(SYNC, &[("function", "sampled"), ("status", "ok")]);
+    assert!((9..=11).contains(&samples.len()), "{}", samples.len());
+}
+
+#[test]
+fn every_function_has_its_own_sample_counter() {
+    // A counter shared with `sampled` would be at 500, and the call would be skipped.
+    let recorded = capture(|| {
+        (0..500).for_each(|_| sampled());
+        other_sampled();
+    });
+    let others = recorded.histogram(SYNC, &[("function", "other_sampled"), ("status", "ok")]);
+    assert_eq!(others.len(), 1);
+}
+
+fn off() -> bool {
+    false
+}
+
+fn on() -> bool {
+    true
+}
+
+#[measured_function(count, enabled_if = off)]
+fn dark() -> u32 {
+    1
+}
+
+#[measured_function(count, enabled_if = on)]
+fn lit() -> u32 {
+    2
+}
+
+#[test]
+fn enabled_if_false_records_nothing() {
+    let recorded = capture(|| {
+        assert_eq!(dark(), 1);
+        assert_eq!(dark(), 1);
+    });
+    assert!(recorded.names().is_empty(), "{:?}", recorded.names());
+}
+
+#[test]
+fn enabled_if_true_records_every_call() {
+    let recorded = capture(|| {
+        assert_eq!(lit(), 2);
+        assert_eq!(lit(), 2);
+    });
+    let samples = recorded.histogram(SYNC, &[("function", "lit"), ("status", "ok")]);
+    assert_eq!(samples.len(), 2);
+}
+
+static ASKED: AtomicUsize = AtomicUsize::new(0);
+
+fn counted_predicate() -> bool {
+    ASKED.fetch_add(1, Ordering::Relaxed);
+    true
+}
+
+#[measured_function(enabled_if = counted_predicate)]
+fn asks_once() {}
+
+#[test]
+fn enabled_if_asks_the_predicate_once() {
+    capture(|| {
+        for _ in 0..5 {
+            asks_once();
+        }
+    });
+    assert_eq!(ASKED.load(Ordering::Relaxed), 1);
+}
+
+#[measured_function(skip_if = cached)]
+fn lookup(key: u32, cached: bool) -> u32 {
+    key
+}
+
+#[test]
+fn skip_if_leaves_out_calls_with_the_flag_set() {
+    let recorded = capture(|| {
+        assert_eq!(lookup(1, true), 1);
+        assert_eq!(lookup(2, false), 2);
+    });
+    let samples = recorded.histogram(SYNC, &[("function", "lookup"), ("status", "ok")]);
+    assert_eq!(samples.len(), 1);
+}
+
+static SKIPPED_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+#[measured_function(skip_in_test, count)]
+fn skipped_in_tests(value: u32) -> u32 {
+    SKIPPED_CALLS.fetch_add(1, Ordering::Relaxed);
+    value * 2
+}
+
+#[test]
+fn skip_in_test_runs_the_body_without_recording() {
+    // Defined in the test crate itself, so it is compiled with `cfg(test)` like a unit test.
+    let recorded = capture(|| assert_eq!(skipped_in_tests(21), 42));
+    assert_eq!(SKIPPED_CALLS.load(Ordering::Relaxed), 1);
+    assert!(recorded.names().is_empty(), "{:?}", recorded.names());
+}
+
+#[measured_function(tail_sample(threshold_ms = 5, sample = 10))]
+fn tail_sampled(slow: bool) {
+    if slow {
+        std::thread::sleep(Duration::from_millis(6));
+    }
+}
+
+#[test]
+fn tail_sample_keeps_every_slow_call_and_thins_out_the_fast_ones() {
+    let recorded = capture(|| {
+        for call in 0..100 {
+            tail_sampled(call % 20 == 0);
+        }
+    });
+    let samples = recorded.histogram(SYNC, &[("function", "tail_sampled"), ("status", "ok")]);
+    let slow = samples.iter().filter(|sample| **sample >= 5.0).count();
+    let fast = samples.len() - slow;
+    assert_eq!(slow, 5, "{samples:?}");
+    // One in ten of the 95 fast calls.
+    assert!((9..=10).contains(&fast), "{samples:?}");
+}
+
+#[measured_function(warmup = 3, count)]
+fn warms_up() {}
+
+#[test]
+fn warmup_leaves_out_the_first_calls() {
+    let recorded = capture(|| {
+        for _ in 0..5 {
+            warms_up();
+        }
+    });
+    let samples = recorded.histogram(SYNC, &[("function", "warms_up"), ("status", "ok")]);
+    assert_eq!(samples.len(), 2);
+    // Only the histogram skips the warm-up.
+    assert_eq!(
+        recorded.counter("function_calls_total", &[("function", "warms_up")]),
+        Some(5)
+    );
+}
+
+#[measured_function(min_duration_ms = 5, count)]
+fn above_the_floor(ms: u64) {
+    std::thread::sleep(Duration::from_millis(ms));
+}
+
+#[test]
+fn min_duration_ms_leaves_out_calls_below_the_floor() {
+    let recorded = capture(|| {
+        above_the_floor(0);
+        above_the_floor(10);
+        above_the_floor(0);
+    });
+    let samples = recorded.histogram(SYNC, &[("function", "above_the_floor"), ("status", "ok")]);
+    assert!(samples.len() == 1 && samples[0] >= 10.0, "{samples:?}");
+    // Only the histogram applies the floor.
+    assert_eq!(
+        recorded.counter("function_calls_total", &[("function", "above_the_floor")]),
+        Some(3)
+    );
+}
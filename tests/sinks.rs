@@ -0,0 +1,191 @@
+#![cfg(not(any(
+    feature = "disabled",
+    feature = "otel",
+    feature = "buffered",
+    feature = "global_sink",
+    feature = "strict"
+)))]
+
+mod common;
+
+use std::cell::RefCell;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use common::{capture, capture_async};
+use metrics::Label;
+use metrics_utils::{measured_async_function, measured_function, DurationSink};
+
+type Sample = (&'static str, Vec<(String, String)>, f64);
+
+thread_local! {
+    static SAMPLES: RefCell<Vec<Sample>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Keeps the durations recorded on the current thread.
+struct Capture;
+
+impl DurationSink for Capture {
+    fn record_duration(&self, metric: &'static str, labels: &[Label], value: f64) {
+        let labels = labels
+            .iter()
+            .map(|label| (label.key().to_owned(), label.value().to_owned()))
+            .collect();
+        SAMPLES.with(|samples| samples.borrow_mut().push((metric, labels, value)));
+    }
+}
+
+fn take_samples() -> Vec<Sample> {
+    SAMPLES.with(|samples| samples.take())
+}
+
+#[measured_function(sink = Capture, count)]
+fn work() {
+    std::thread::sleep(Duration::from_millis(2));
+}
+
+#[test]
+fn durations_go_to_the_sink_instead_of_the_recorder() {
+    let recorded = capture(work);
+    let samples = take_samples();
+    assert_eq!(samples.len(), 1);
+    let (metric, labels, value) = &samples[0];
+    assert_eq!(*metric, "function_duration_milliseconds");
+    assert_eq!(
+        *labels,
+        [
+            ("function".to_owned(), "work".to_owned()),
+            ("status".to_owned(), "ok".to_owned()),
+        ]
+    );
+    assert!((2.0..1000.0).contains(value), "{value}");
+    // Companion metrics still use the recorder.
+    assert!(recorded
+        .histogram(
+            "function_duration_milliseconds",
+            &[("function", "work"), ("status", "ok")]
+        )
+        .is_empty());
+    assert_eq!(
+        recorded.counter("function_calls_total", &[("function", "work")]),
+        Some(1)
+    );
+}
+
+thread_local! {
+    static EXEMPLARS: RefCell<Vec<Option<String>>> = const { RefCell::new(Vec::new()) };
+    static TRACE_ID: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Keeps the exemplar of every duration recorded on the current thread.
+struct ExemplarCapture;
+
+impl DurationSink for ExemplarCapture {
+    fn record_duration(&self, _metric: &'static str, _labels: &[Label], _value: f64) {
+        panic!("exemplar_from must record through record_duration_with_exemplar");
+    }
+
+    fn record_duration_with_exemplar(
+        &self,
+        _metric: &'static str,
+        _labels: &[Label],
+        _value: f64,
+        exemplar: Option<&str>,
+    ) {
+        EXEMPLARS.with(|exemplars| exemplars.borrow_mut().push(exemplar.map(str::to_owned)));
+    }
+}
+
+fn trace_id() -> Option<String> {
+    TRACE_ID.with(|id| id.borrow().clone())
+}
+
+#[measured_function(sink = ExemplarCapture, exemplar_from = trace_id)]
+fn traced() {}
+
+#[test]
+fn exemplar_from_passes_the_trace_id_to_the_sink() {
+    traced();
+    TRACE_ID.with(|id| *id.borrow_mut() = Some("4bf92f3577b34da6".to_owned()));
+    traced();
+    let exemplars = EXEMPLARS.with(|exemplars| exemplars.take());
+    assert_eq!(exemplars, [None, Some("4bf92f3577b34da6".to_owned())]);
+}
+
+static OBSERVED: Mutex<Vec<(String, f64)>> = Mutex::new(Vec::new());
+
+fn observe(label: &str, duration: f64) {
+    OBSERVED.lock().unwrap().push((label.to_owned(), duration));
+}
+
+/// What `observe` received for `label`.
+fn observed(label: &str) -> Vec<f64> {
+    OBSERVED
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(observed, _)| observed == label)
+        .map(|(_, duration)| *duration)
+        .collect()
+}
+
+#[measured_function(on_record = observe)]
+fn observed_sync() {
+    std::thread::sleep(Duration::from_millis(2));
+}
+
+#[measured_async_function("observed_task", on_record = observe)]
+async fn observed_async() {
+    tokio::time::sleep(Duration::from_millis(2)).await;
+}
+
+#[test]
+fn on_record_receives_the_label_and_duration_of_sync_calls() {
+    let recorded = capture(observed_sync);
+    let durations = observed("observed_sync");
+    assert!(
+        durations.len() == 1 && (2.0..1000.0).contains(&durations[0]),
+        "{durations:?}"
+    );
+    // The histogram is still recorded, with the same value.
+    let samples = recorded.histogram(
+        "function_duration_milliseconds",
+        &[("function", "observed_sync"), ("status", "ok")],
+    );
+    assert_eq!(samples, durations);
+}
+
+#[test]
+fn on_record_receives_the_label_and_duration_of_async_calls() {
+    capture_async(observed_async());
+    let durations = observed("observed_task");
+    assert!(
+        durations.len() == 1 && (2.0..1000.0).contains(&durations[0]),
+        "{durations:?}"
+    );
+}
+
+thread_local! {
+    static TOOK: RefCell<Vec<f64>> = const { RefCell::new(Vec::new()) };
+}
+
+#[measured_function(expose_as = took, after = { TOOK.with(|took_all| took_all.borrow_mut().push(took)) })]
+fn reacts_to_its_duration(input: String) -> usize {
+    std::thread::sleep(Duration::from_millis(3));
+    input.len()
+}
+
+#[test]
+fn after_runs_with_the_recorded_duration_bound() {
+    let recorded = capture(|| assert_eq!(reacts_to_its_duration("abc".to_owned()), 3));
+    let took = TOOK.with(|took| took.take());
+    assert!(
+        took.len() == 1 && (3.0..1000.0).contains(&took[0]),
+        "{took:?}"
+    );
+    let samples = recorded.histogram(
+        "function_duration_milliseconds",
+        &[("function", "reacts_to_its_duration"), ("status", "ok")],
+    );
+    assert_eq!(samples, took);
+}
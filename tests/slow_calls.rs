@@ -0,0 +1,96 @@
+#![cfg(not(any(
+    feature = "disabled",
+    feature = "otel",
+    feature = "buffered",
+    feature = "global_sink",
+    feature = "strict"
+)))]
+
+mod common;
+
+use std::panic::{self, AssertUnwindSafe};
+use std::time::Duration;
+
+use common::{capture, capture_logs};
+use metrics_utils::measured_function;
+
+#[measured_function(slow_threshold_ms = 5)]
+fn sometimes_slow(slow: bool) {
+    if slow {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn slow_calls_log_a_warning() {
+    let logs = capture_logs(|| sometimes_slow(true));
+    assert!(logs.contains("WARN"), "{logs}");
+    assert!(logs.contains("slow call"), "{logs}");
+    assert!(logs.contains("function=\"sometimes_slow\""), "{logs}");
+    assert!(logs.contains("threshold_ms=5"), "{logs}");
+}
+
+#[test]
+fn fast_calls_log_nothing() {
+    let logs = capture_logs(|| sometimes_slow(false));
+    assert_eq!(logs, "");
+}
+
+#[measured_function(slow_threshold_ms = 5)]
+fn counted_when_slow(slow: bool) {
+    if slow {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+#[test]
+fn slow_calls_are_counted_once_each() {
+    let recorded = capture(|| {
+        counted_when_slow(true);
+        counted_when_slow(false);
+    });
+    assert_eq!(
+        recorded.counter(
+            "function_slow_calls_total",
+            &[("function", "counted_when_slow")]
+        ),
+        Some(1)
+    );
+}
+
+#[measured_function(max_duration_ms = 5)]
+fn budgeted(ms: u64) -> u64 {
+    std::thread::sleep(Duration::from_millis(ms));
+    ms
+}
+
+#[test]
+fn max_duration_ms_panics_when_a_call_is_over_budget() {
+    let mut outcome = None;
+    let recorded = capture(|| {
+        outcome = Some(panic::catch_unwind(AssertUnwindSafe(|| budgeted(20))));
+    });
+    let message = outcome.unwrap().unwrap_err();
+    let message = message.downcast_ref::<String>().unwrap();
+    assert!(message.starts_with("`budgeted` took "), "{message}");
+    assert!(
+        message.ends_with("over its latency budget of 5 ms"),
+        "{message}"
+    );
+    // The call was recorded before the panic.
+    let samples = recorded.histogram(
+        "function_duration_milliseconds",
+        &[("function", "budgeted"), ("status", "ok")],
+    );
+    assert!(samples.len() == 1 && samples[0] >= 20.0, "{samples:?}");
+}
+
+#[test]
+fn max_duration_ms_lets_calls_within_budget_return() {
+    let recorded = capture(|| assert_eq!(budgeted(0), 0));
+    let samples = recorded.histogram(
+        "function_duration_milliseconds",
+        &[("function", "budgeted"), ("status", "ok")],
+    );
+    assert_eq!(samples.len(), 1);
+}
@@ -0,0 +1,149 @@
+#![cfg(not(any(
+    feature = "disabled",
+    feature = "otel",
+    feature = "buffered",
+    feature = "global_sink",
+    feature = "strict"
+)))]
+
+mod common;
+
+use std::time::Duration;
+
+use common::{capture, capture_logs};
+use metrics_utils::{measured_async_function, measured_function};
+
+/// The `elapsed_ms` recorded on the closed span named `name` in `logs`.
+fn elapsed_ms(logs: &str, name: &str) -> f64 {
+    span_field(logs, name, "elapsed_ms")
+}
+
+/// The value of `field` on the closed span named `name` in `logs`.
+fn span_field(logs: &str, name: &str, field: &str) -> f64 {
+    let prefix = format!("{name}{{");
+    let line = logs
+        .lines()
+        .find(|line| line.contains(&prefix) && line.contains("close"))
+        .unwrap_or_else(|| panic!("no closed `{name}` span in {logs}"));
+    let start = line.find(&prefix).unwrap() + prefix.len();
+    let end = start + line[start..].find('}').unwrap();
+    line[start..end]
+        .split(' ')
+        .find_map(|pair| pair.strip_prefix(field)?.strip_prefix('='))
+        .unwrap_or_else(|| panic!("no `{field}` on the `{name}` span in {logs}"))
+        .parse()
+        .unwrap()
+}
+
+#[measured_function(span)]
+fn spanned() {
+    std::thread::sleep(Duration::from_millis(5));
+    tracing::info!("inside");
+}
+
+#[test]
+fn span_wraps_the_call_and_records_the_elapsed_time() {
+    let logs = capture_logs(spanned);
+    assert!(logs.contains("INFO spanned: spans: inside"), "{logs}");
+    assert!(elapsed_ms(&logs, "spanned") >= 5.0, "{logs}");
+}
+
+#[measured_async_function(span)]
+async fn spanned_async() {
+    tokio::time::sleep(Duration::from_millis(5)).await;
+    tracing::info!("resumed");
+}
+
+#[test]
+fn async_spans_are_entered_across_awaits() {
+    let logs = capture_logs(|| {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(spanned_async());
+    });
+    assert!(
+        logs.contains("INFO spanned_async: spans: resumed"),
+        "{logs}"
+    );
+    assert!(elapsed_ms(&logs, "spanned_async") >= 5.0, "{logs}");
+}
+
+#[test]
+fn span_still_records_the_histogram() {
+    let recorded = capture(spanned);
+    let samples = recorded.histogram(
+        "function_duration_milliseconds",
+        &[("function", "spanned"), ("status", "ok")],
+    );
+    assert_eq!(samples.len(), 1);
+}
+
+#[measured_function(record = "span", span)]
+fn recorded_on_its_span() {
+    std::thread::sleep(Duration::from_millis(5));
+}
+
+#[measured_function(record = "span")]
+fn recorded_on_the_callers_span() {
+    std::thread::sleep(Duration::from_millis(5));
+}
+
+#[test]
+fn record_span_sets_the_duration_field_instead_of_the_histogram() {
+    let mut recorded = None;
+    let logs = capture_logs(|| {
+        recorded = Some(capture(|| {
+            recorded_on_its_span();
+            let caller = tracing::info_span!("caller", duration_ms = tracing::field::Empty);
+            caller.in_scope(recorded_on_the_callers_span);
+        }));
+    });
+    assert!(
+        span_field(&logs, "recorded_on_its_span", "duration_ms") >= 5.0,
+        "{logs}"
+    );
+    assert!(span_field(&logs, "caller", "duration_ms") >= 5.0, "{logs}");
+    assert!(recorded.unwrap().names().is_empty());
+}
+
+#[measured_function(log_level = "warn")]
+fn audited(amount: u32) -> u32 {
+    amount
+}
+
+#[measured_async_function(log_level = "error")]
+async fn audited_async() {}
+
+#[test]
+fn log_level_logs_every_call_at_the_level() {
+    let logs = capture_logs(|| {
+        audited(1);
+        audited(2);
+    });
+    let events: Vec<_> = logs
+        .lines()
+        .filter(|line| line.contains("audited"))
+        .collect();
+    assert_eq!(events.len(), 2, "{logs}");
+    for event in events {
+        assert!(event.contains(" WARN "), "{event}");
+        assert!(event.contains(r#"function="audited""#), "{event}");
+        assert!(event.contains("elapsed_ms="), "{event}");
+    }
+}
+
+#[test]
+fn log_level_logs_async_calls() {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let logs = capture_logs(|| runtime.block_on(audited_async()));
+    let event = logs
+        .lines()
+        .find(|line| line.contains(r#"function="audited_async""#))
+        .unwrap_or_else(|| panic!("no event in {logs}"));
+    assert!(event.contains(" ERROR "), "{event}");
+}
@@ -0,0 +1,176 @@
+#![cfg(not(any(
+    feature = "disabled",
+    feature = "otel",
+    feature = "buffered",
+    feature = "global_sink",
+    feature = "strict"
+)))]
+
+mod common;
+
+use common::capture;
+use metrics_utils::measured_function;
+
+#[measured_function("login", component = "auth", tier = "critical")]
+fn login() {}
+
+#[measured_function("logout", labels(component = "auth"), tier = "critical")]
+fn logout() {}
+
+#[measured_function(unit = "seconds", component = "auth")]
+fn refresh() {}
+
+#[test]
+fn bare_key_value_pairs_are_static_labels() {
+    let recorded = capture(login);
+    let samples = recorded.histogram(
+        "function_duration_milliseconds",
+        &[
+            ("function", "login"),
+            ("status", "ok"),
+            ("component", "auth"),
+            ("tier", "critical"),
+        ],
+    );
+    assert_eq!(samples.len(), 1);
+}
+
+#[test]
+fn bare_labels_combine_with_the_labels_list() {
+    let recorded = capture(logout);
+    let samples = recorded.histogram(
+        "function_duration_milliseconds",
+        &[
+            ("function", "logout"),
+            ("status", "ok"),
+            ("component", "auth"),
+            ("tier", "critical"),
+        ],
+    );
+    assert_eq!(samples.len(), 1);
+}
+
+#[test]
+fn options_are_not_taken_for_labels() {
+    let recorded = capture(refresh);
+    let samples = recorded.histogram(
+        "function_duration_seconds",
+        &[
+            ("function", "refresh"),
+            ("status", "ok"),
+            ("component", "auth"),
+        ],
+    );
+    assert_eq!(samples.len(), 1);
+}
+
+#[measured_function(with_pkg_labels)]
+fn built() {}
+
+#[test]
+fn with_pkg_labels_adds_the_package_name_and_version() {
+    let recorded = capture(built);
+    let samples = recorded.histogram(
+        "function_duration_milliseconds",
+        &[
+            ("function", "built"),
+            ("status", "ok"),
+            ("service", env!("CARGO_PKG_NAME")),
+            ("version", env!("CARGO_PKG_VERSION")),
+        ],
+    );
+    assert_eq!(
+        samples.len(),
+        1,
+        "{:?}",
+        recorded.label_sets("function_duration_milliseconds")
+    );
+}
+
+#[measured_function(with_profile_label, count)]
+fn profiled() {}
+
+#[test]
+fn with_profile_label_reflects_the_build_profile() {
+    // `cargo test` builds the debug profile, and `cargo test --release` the release one.
+    let profile = if cfg!(debug_assertions) {
+        "debug"
+    } else {
+        "release"
+    };
+    let recorded = capture(profiled);
+    let samples = recorded.histogram(
+        "function_duration_milliseconds",
+        &[
+            ("function", "profiled"),
+            ("status", "ok"),
+            ("profile", profile),
+        ],
+    );
+    assert_eq!(samples.len(), 1);
+    assert_eq!(
+        recorded.counter(
+            "function_calls_total",
+            &[("function", "profiled"), ("profile", profile)]
+        ),
+        Some(1)
+    );
+}
+
+#[measured_function(count, path_label_from_cfg = "buffered")]
+fn on_the_buffered_path() {}
+
+#[test]
+fn path_label_from_cfg_is_off_without_the_feature() {
+    // `tests/buffered.rs` checks the same function with the feature on.
+    let recorded = capture(on_the_buffered_path);
+    let samples = recorded.histogram(
+        "function_duration_milliseconds",
+        &[
+            ("function", "on_the_buffered_path"),
+            ("status", "ok"),
+            ("path", "off"),
+        ],
+    );
+    assert_eq!(samples.len(), 1);
+    assert_eq!(
+        recorded.counter(
+            "function_calls_total",
+            &[("function", "on_the_buffered_path"), ("path", "off")]
+        ),
+        Some(1)
+    );
+}
+
+#[measured_function(arity)]
+fn nullary() {}
+
+#[measured_function(arity)]
+fn binary(a: u32, b: u32) -> u32 {
+    a + b
+}
+
+struct Adder(u32);
+
+impl Adder {
+    #[measured_function(arity)]
+    fn add(&self, value: u32) -> u32 {
+        self.0 + value
+    }
+}
+
+#[test]
+fn arity_labels_the_parameter_count_without_self() {
+    let recorded = capture(|| {
+        nullary();
+        assert_eq!(binary(1, 2), 3);
+        assert_eq!(Adder(1).add(2), 3);
+    });
+    for (function, arity) in [("nullary", "0"), ("binary", "2"), ("add", "1")] {
+        let samples = recorded.histogram(
+            "function_duration_milliseconds",
+            &[("function", function), ("status", "ok"), ("arity", arity)],
+        );
+        assert_eq!(samples.len(), 1, "{function}");
+    }
+}
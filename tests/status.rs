@@ -0,0 +1,86 @@
+#![cfg(not(any(
+    feature = "disabled",
+    feature = "otel",
+    feature = "buffered",
+    feature = "global_sink",
+    feature = "strict"
+)))]
+
+mod common;
+
+use std::panic::{self, AssertUnwindSafe};
+
+use common::{capture, capture_async};
+use metrics_utils::{measured_async_function, measured_function};
+
+#[measured_function]
+fn sync_panics() {
+    panic!("sync failure");
+}
+
+#[measured_function]
+fn sync_returns() -> u32 {
+    1
+}
+
+#[measured_async_function]
+async fn async_panics() {
+    tokio::task::yield_now().await;
+    panic!("async failure");
+}
+
+#[test]
+fn sync_panics_are_recorded_with_status_panic() {
+    let recorded = capture(|| {
+        assert!(panic::catch_unwind(AssertUnwindSafe(sync_panics)).is_err());
+    });
+    let samples = recorded.histogram(
+        "function_duration_milliseconds",
+        &[("function", "sync_panics"), ("status", "panic")],
+    );
+    assert_eq!(samples.len(), 1);
+    assert_eq!(
+        recorded.label_sets("function_duration_milliseconds").len(),
+        1
+    );
+}
+
+#[test]
+fn returning_normally_records_once_with_status_ok() {
+    let recorded = capture(|| assert_eq!(sync_returns(), 1));
+    assert_eq!(
+        recorded.label_sets("function_duration_milliseconds"),
+        [[
+            ("function".to_owned(), "sync_returns".to_owned()),
+            ("status".to_owned(), "ok".to_owned()),
+        ]]
+    );
+    let samples = recorded.histogram(
+        "function_duration_milliseconds",
+        &[("function", "sync_returns"), ("status", "ok")],
+    );
+    assert_eq!(samples.len(), 1);
+}
+
+#[test]
+fn async_panics_are_recorded_with_status_panic() {
+    let recorded = capture_async(async {
+        let error = tokio::spawn(async_panics()).await.unwrap_err();
+        assert!(error.is_panic());
+    });
+    let samples = recorded.histogram(
+        "async_function_duration_milliseconds",
+        &[
+            ("function", "async_panics"),
+            ("status", "panic"),
+            ("completion", "completed"),
+        ],
+    );
+    assert_eq!(samples.len(), 1);
+    assert_eq!(
+        recorded
+            .label_sets("async_function_duration_milliseconds")
+            .len(),
+        1
+    );
+}
@@ -0,0 +1,11 @@
+//! Run with `cargo test --features strict,metrics-utils/strict --test strict`.
+
+#![cfg(feature = "strict")]
+
+/// With the feature on, every function is checked as if it had the `strict` flag.
+#[test]
+fn strict_feature() {
+    let cases = trybuild::TestCases::new();
+    cases.compile_fail("tests/ui/strict/*.rs");
+    cases.pass("tests/ui/pass/strict_conforming.rs");
+}
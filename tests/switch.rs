@@ -0,0 +1,51 @@
+//! The runtime switch is process-wide, so its test lives in a file of its own, away from the
+//! tests expecting every call to be recorded.
+
+#![cfg(not(any(
+    feature = "disabled",
+    feature = "otel",
+    feature = "buffered",
+    feature = "global_sink",
+    feature = "strict"
+)))]
+
+mod common;
+
+use std::cell::Cell;
+
+use common::capture;
+use metrics_utils::{measure, measured_function, switch};
+
+#[measured_function(count)]
+fn switched(calls: &Cell<u32>) {
+    calls.set(calls.get() + 1);
+}
+
+#[test]
+fn the_switch_stops_and_restarts_recording() {
+    let calls = Cell::new(0);
+    let recorded = |calls: &Cell<u32>| {
+        let recorded = capture(|| {
+            switched(calls);
+            measure!("switched_block", { switched(calls) });
+        });
+        (
+            recorded.counter("function_calls_total", &[("function", "switched")]),
+            recorded
+                .histogram(
+                    "block_duration_milliseconds",
+                    &[("block", "switched_block")],
+                )
+                .len(),
+        )
+    };
+    assert!(switch::is_enabled());
+    assert_eq!(recorded(&calls), (Some(2), 1));
+    switch::disable();
+    assert!(!switch::is_enabled());
+    assert_eq!(recorded(&calls), (None, 0));
+    switch::enable();
+    assert_eq!(recorded(&calls), (Some(2), 1));
+    // The body ran on every call, switched off or not.
+    assert_eq!(calls.get(), 6);
+}
@@ -0,0 +1,273 @@
+#![cfg(not(any(
+    feature = "disabled",
+    feature = "otel",
+    feature = "buffered",
+    feature = "global_sink",
+    feature = "strict"
+)))]
+
+mod common;
+
+use std::num::ParseIntError;
+use std::panic::{self, AssertUnwindSafe};
+
+use common::{capture, capture_async};
+use metrics_utils::{measured_async_function, measured_function};
+
+#[measured_function]
+fn parse(input: &str) -> Result<u32, ParseIntError> {
+    let value: u32 = input.parse()?;
+    Ok(value + 1)
+}
+
+#[measured_function]
+fn first_even(values: &[u32]) -> Option<u32> {
+    for value in values {
+        if value % 2 == 0 {
+            return Some(*value);
+        }
+    }
+    None
+}
+
+#[measured_function]
+fn cleanup() {}
+
+struct CleansUp;
+
+impl Drop for CleansUp {
+    fn drop(&mut self) {
+        cleanup();
+    }
+}
+
+#[test]
+fn question_mark_returns_from_the_function() {
+    let recorded = capture(|| {
+        assert_eq!(parse("41"), Ok(42));
+        assert!(parse("forty-one").is_err());
+    });
+    let samples = recorded.histogram(
+        "function_duration_milliseconds",
+        &[("function", "parse"), ("status", "ok")],
+    );
+    assert_eq!(samples.len(), 2);
+}
+
+#[test]
+fn early_return_returns_from_the_function() {
+    let recorded = capture(|| {
+        assert_eq!(first_even(&[1, 4, 6]), Some(4));
+        assert_eq!(first_even(&[1, 3]), None);
+    });
+    let samples = recorded.histogram(
+        "function_duration_milliseconds",
+        &[("function", "first_even"), ("status", "ok")],
+    );
+    assert_eq!(samples.len(), 2);
+}
+
+#[test]
+fn calls_made_while_unwinding_still_return_ok() {
+    let recorded = capture(|| {
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let _guard = CleansUp;
+            panic!("unwinding through a drop");
+        }));
+        assert!(result.is_err());
+    });
+    let labels = |status| [("function", "cleanup"), ("status", status)];
+    let ok = recorded.histogram("function_duration_milliseconds", &labels("ok"));
+    let panicked = recorded.histogram("function_duration_milliseconds", &labels("panic"));
+    assert_eq!((ok.len(), panicked.len()), (1, 0));
+}
+
+#[measured_function]
+fn shadows_common_names(input: u32) -> u32 {
+    let result = input * 2;
+    let duration = result + 1;
+    let start = duration + 1;
+    result + duration + start
+}
+
+#[test]
+fn body_bindings_named_like_the_timing_state_are_left_alone() {
+    let recorded = capture(|| assert_eq!(shadows_common_names(1), 9));
+    let samples = recorded.histogram(
+        "function_duration_milliseconds",
+        &[("function", "shadows_common_names"), ("status", "ok")],
+    );
+    assert_eq!(samples.len(), 1);
+}
+
+#[measured_function]
+fn parse_any<T: std::str::FromStr>(input: &str) -> Option<T> {
+    input.parse().ok()
+}
+
+#[test]
+fn every_instantiation_records_under_the_generic_name() {
+    let recorded = capture(|| {
+        assert_eq!(parse_any::<u8>("1"), Some(1));
+        assert_eq!(parse_any::<i64>("-1"), Some(-1));
+        assert_eq!(parse_any::<f64>("0.5"), Some(0.5));
+    });
+    let samples = recorded.histogram(
+        "function_duration_milliseconds",
+        &[("function", "parse_any"), ("status", "ok")],
+    );
+    assert_eq!(samples.len(), 3);
+}
+
+#[measured_function(strip_const)]
+const fn stripped(value: u32) -> u32 {
+    value * 2
+}
+
+#[test]
+fn strip_const_keeps_a_const_fn_working_at_runtime() {
+    let recorded = capture(|| assert_eq!(stripped(2), 4));
+    let samples = recorded.histogram(
+        "function_duration_milliseconds",
+        &[("function", "stripped"), ("status", "ok")],
+    );
+    assert_eq!(samples.len(), 1);
+}
+
+#[measured_function]
+unsafe fn unsafe_read(pointer: *const u32) -> u32 {
+    *pointer
+}
+
+#[measured_function]
+extern "C" fn c_double(value: u32) -> u32 {
+    value * 2
+}
+
+#[measured_function]
+unsafe extern "C" fn unsafe_c_read(pointer: *const u32) -> u32 {
+    *pointer
+}
+
+#[test]
+fn unsafe_and_extern_functions_still_record() {
+    let value = 21;
+    let recorded = capture(|| unsafe {
+        assert_eq!(unsafe_read(&value), 21);
+        assert_eq!(c_double(value), 42);
+        assert_eq!(unsafe_c_read(&value), 21);
+    });
+    for function in ["unsafe_read", "c_double", "unsafe_c_read"] {
+        let samples = recorded.histogram(
+            "function_duration_milliseconds",
+            &[("function", function), ("status", "ok")],
+        );
+        assert_eq!(samples.len(), 1, "{function}");
+    }
+}
+
+#[measured_function]
+fn literal() -> &'static str {
+    "value"
+}
+
+#[measured_function]
+fn doubled_parse(input: &str) -> Result<u32, ParseIntError> {
+    Ok(input.parse::<u32>()? * 2)
+}
+
+#[measured_function]
+fn incremented(value: Option<u32>) -> Option<u32> {
+    Some(value? + 1)
+}
+
+#[measured_function]
+fn pair() -> (u32, u32) {
+    (1, 2)
+}
+
+#[measured_function]
+fn sign(value: i32) -> &'static str {
+    match value {
+        0 => "zero",
+        v if v < 0 => "negative",
+        _ => "positive",
+    }
+}
+
+#[measured_async_function]
+async fn async_doubled_parse(input: &str) -> Result<u32, ParseIntError> {
+    Ok(input.parse::<u32>()? * 2)
+}
+
+#[test]
+fn single_expression_bodies_keep_their_value_and_question_marks() {
+    let recorded = capture(|| {
+        assert_eq!(literal(), "value");
+        assert_eq!(doubled_parse("4"), Ok(8));
+        assert!(doubled_parse("x").is_err());
+        assert_eq!(incremented(Some(1)), Some(2));
+        assert_eq!(incremented(None), None);
+        assert_eq!(pair(), (1, 2));
+        assert_eq!(sign(-4), "negative");
+    });
+    for (function, calls) in [
+        ("literal", 1),
+        ("doubled_parse", 2),
+        ("incremented", 2),
+        ("pair", 1),
+        ("sign", 1),
+    ] {
+        let samples = recorded.histogram(
+            "function_duration_milliseconds",
+            &[("function", function), ("status", "ok")],
+        );
+        assert_eq!(samples.len(), calls, "{function}");
+    }
+}
+
+#[test]
+fn single_expression_async_bodies_keep_their_question_marks() {
+    let recorded = capture_async(async {
+        assert_eq!(async_doubled_parse("4").await, Ok(8));
+        assert!(async_doubled_parse("x").await.is_err());
+    });
+    let samples = recorded.histogram(
+        "async_function_duration_milliseconds",
+        &[
+            ("function", "async_doubled_parse"),
+            ("status", "ok"),
+            ("completion", "completed"),
+        ],
+    );
+    assert_eq!(samples.len(), 2);
+}
+
+#[measured_function(named_inner)]
+fn parse_list<T: std::str::FromStr>(input: &str, separator: char) -> (Vec<T>, String) {
+    let parsed = input
+        .split(separator)
+        .filter_map(|item| item.trim().parse().ok())
+        .collect();
+    let backtrace = std::backtrace::Backtrace::force_capture().to_string();
+    (parsed, backtrace)
+}
+
+#[test]
+fn named_inner_runs_the_body_under_its_own_symbol() {
+    let mut backtrace = String::new();
+    let recorded = capture(|| {
+        let (parsed, trace) = parse_list::<u32>("1, 2, x, 3", ',');
+        assert_eq!(parsed, [1, 2, 3]);
+        backtrace = trace;
+    });
+    assert!(
+        backtrace.contains("__measured_inner_parse_list"),
+        "{backtrace}"
+    );
+    let samples = recorded.histogram(
+        "function_duration_milliseconds",
+        &[("function", "parse_list"), ("status", "ok")],
+    );
+    assert_eq!(samples.len(), 1);
+}
@@ -0,0 +1,129 @@
+#![cfg(not(any(
+    feature = "disabled",
+    feature = "otel",
+    feature = "buffered",
+    feature = "global_sink",
+    feature = "strict"
+)))]
+
+mod common;
+
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use common::{capture, capture_async};
+use metrics_utils::{measured_async_function, measured_boxed_future, measured_function};
+
+#[async_trait]
+trait Store {
+    async fn get(&self, key: u32) -> Option<u32>;
+}
+
+struct Db;
+
+#[async_trait]
+impl Store for Db {
+    #[measured_async_function]
+    async fn get(&self, key: u32) -> Option<u32> {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        Some(key)
+    }
+}
+
+#[measured_boxed_future]
+fn boxed() -> Pin<Box<dyn Future<Output = u32> + Send>> {
+    Box::pin(async {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        5
+    })
+}
+
+fn completed(recorded: &common::Recorded, function: &str) -> Vec<f64> {
+    recorded.histogram(
+        "async_function_duration_milliseconds",
+        &[
+            ("function", function),
+            ("status", "ok"),
+            ("completion", "completed"),
+        ],
+    )
+}
+
+#[test]
+fn async_trait_methods_are_timed_until_their_future_completes() {
+    let recorded = capture_async(async {
+        let store: &dyn Store = &Db;
+        assert_eq!(store.get(2).await, Some(2));
+    });
+    let samples = completed(&recorded, "get");
+    assert!(
+        samples.len() == 1 && (20.0..1000.0).contains(&samples[0]),
+        "{samples:?}"
+    );
+}
+
+#[test]
+fn boxed_futures_are_timed_until_they_complete() {
+    let recorded = capture_async(async { assert_eq!(boxed().await, 5) });
+    let samples = completed(&recorded, "boxed");
+    assert!(
+        samples.len() == 1 && (20.0..1000.0).contains(&samples[0]),
+        "{samples:?}"
+    );
+}
+
+trait Greeter {
+    #[measured_function]
+    fn greet(&self) -> &'static str {
+        "hello"
+    }
+}
+
+struct English;
+
+impl Greeter for English {}
+
+#[test]
+fn trait_default_methods_are_measured() {
+    let recorded = capture(|| assert_eq!(English.greet(), "hello"));
+    let samples = recorded.histogram(
+        "function_duration_milliseconds",
+        &[("function", "greet"), ("status", "ok")],
+    );
+    assert_eq!(samples.len(), 1);
+}
+
+#[measured_boxed_future]
+fn unpinned() -> Box<dyn Future<Output = u32> + Send> {
+    Box::new(async {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        6
+    })
+}
+
+#[measured_async_function]
+fn detected_boxed() -> Pin<Box<dyn Future<Output = u32> + Send>> {
+    Box::pin(async {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        7
+    })
+}
+
+#[test]
+fn unpinned_and_detected_boxed_futures_are_timed_and_stay_send() {
+    let recorded = capture_async(async {
+        // Spawning requires the wrapped futures to still be `Send`.
+        let detected = tokio::spawn(detected_boxed());
+        assert_eq!(Box::into_pin(unpinned()).await, 6);
+        assert_eq!(detected.await.unwrap(), 7);
+    });
+    for function in ["unpinned", "detected_boxed"] {
+        let samples = completed(&recorded, function);
+        assert!(
+            samples.len() == 1 && (20.0..1000.0).contains(&samples[0]),
+            "{function}: {samples:?}"
+        );
+    }
+}
@@ -0,0 +1,8 @@
+use metrics_utils::measured_function;
+
+#[measured_function]
+fn blocking() -> u32 {
+    metrics_utils::await_point!("never", std::future::ready(1).await)
+}
+
+fn main() {}
@@ -0,0 +1,6 @@
+use metrics_utils::measured_function;
+
+#[measured_function(buckets = [0.5, "1", 2.0])]
+fn bucketed() {}
+
+fn main() {}
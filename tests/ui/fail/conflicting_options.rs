@@ -0,0 +1,11 @@
+use metrics_utils::measured_function;
+
+#[measured_function(as = "counter", buckets = [1.0, 5.0])]
+fn counter_with_buckets() {}
+
+#[measured_function(split_result, result)]
+fn split_with_status() -> Result<(), ()> {
+    Ok(())
+}
+
+fn main() {}
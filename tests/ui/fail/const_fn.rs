@@ -0,0 +1,8 @@
+use metrics_utils::measured_function;
+
+#[measured_function]
+const fn double(value: u32) -> u32 {
+    value * 2
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use metrics_utils::measured_function;
+
+#[measured_function(correlation_from = pipeline_id)]
+fn stage(run_id: u64) -> u64 {
+    run_id
+}
+
+fn main() {}
@@ -0,0 +1,6 @@
+use metrics_utils::measured_function;
+
+#[measured_function(tier = "critical", labels(tier = "low"))]
+fn login() {}
+
+fn main() {}
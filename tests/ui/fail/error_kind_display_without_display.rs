@@ -0,0 +1,11 @@
+use metrics_utils::measured_function;
+
+#[derive(Debug)]
+struct Opaque;
+
+#[measured_function(error_kind = display)]
+fn fails() -> Result<(), Opaque> {
+    Err(Opaque)
+}
+
+fn main() {}
@@ -0,0 +1,13 @@
+use metrics_utils::measured_function;
+
+#[measured_function(ewma, label_from = route)]
+fn by_route(route: &str) -> usize {
+    route.len()
+}
+
+#[measured_function(ewma = 0.2, label_from_present = filter)]
+fn by_filter(filter: Option<&str>) -> bool {
+    filter.is_some()
+}
+
+fn main() {}
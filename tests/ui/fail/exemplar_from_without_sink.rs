@@ -0,0 +1,10 @@
+use metrics_utils::measured_function;
+
+fn trace_id() -> Option<String> {
+    None
+}
+
+#[measured_function(exemplar_from = trace_id)]
+fn traced() {}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use metrics_utils::measured_function;
+
+#[measured_function(expected = "microseconds", unit = "seconds")]
+fn both() {}
+
+#[measured_function(expected = "fortnights")]
+fn unknown() {}
+
+fn main() {}
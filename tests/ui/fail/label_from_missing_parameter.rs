@@ -0,0 +1,8 @@
+use metrics_utils::measured_function;
+
+#[measured_function(label_from = route)]
+fn handle(path: &str) -> usize {
+    path.len()
+}
+
+fn main() {}
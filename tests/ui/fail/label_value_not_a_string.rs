@@ -0,0 +1,6 @@
+use metrics_utils::measured_function;
+
+#[measured_function("login", component = auth, tier = 1)]
+fn login() {}
+
+fn main() {}
@@ -0,0 +1,6 @@
+use metrics_utils::measured_async_function;
+
+#[measured_async_function(location)]
+async fn located() {}
+
+fn main() {}
@@ -0,0 +1,6 @@
+use metrics_utils::measured_function;
+
+#[measured_function(log_level = "verbose")]
+fn chatty() {}
+
+fn main() {}
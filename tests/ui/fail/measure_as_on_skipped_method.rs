@@ -0,0 +1,12 @@
+use metrics_utils::measured_impl;
+
+struct Service;
+
+#[measured_impl]
+impl Service {
+    #[skip_measure]
+    #[measure_as("service_lookup")]
+    fn lookup(&self) {}
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use metrics_utils::measured_async_function;
+
+#[measured_async_function]
+fn lookup() -> u32 {
+    1
+}
+
+fn main() {}
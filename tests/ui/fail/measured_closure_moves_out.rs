@@ -0,0 +1,12 @@
+use metrics_utils::measured_closure;
+
+fn call_twice(f: impl Fn() -> String) {
+    f();
+    f();
+}
+
+fn main() {
+    let name = String::from("job");
+    // Moving the capture out makes the closure `FnOnce`, wrapped or not.
+    call_twice(measured_closure!("consume", move || name));
+}
@@ -0,0 +1,8 @@
+use metrics_utils::measured_function;
+
+#[measured_function]
+async fn fetch() -> u32 {
+    1
+}
+
+fn main() {}
@@ -0,0 +1,15 @@
+use metrics_utils::measured_function;
+
+#[measured_function]
+#[metric(name = 5)]
+fn wrong_type() {}
+
+#[measured_function]
+#[metric(label = "x")]
+fn unknown_key() {}
+
+#[measured_function("checkout")]
+#[metric(name = "checkout_submit")]
+fn repeated() {}
+
+fn main() {}
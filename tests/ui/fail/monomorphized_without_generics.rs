@@ -0,0 +1,8 @@
+use metrics_utils::measured_function;
+
+#[measured_function(monomorphized)]
+fn parse(input: &str) -> Option<u32> {
+    input.parse().ok()
+}
+
+fn main() {}
@@ -0,0 +1,32 @@
+#![deny(unused_must_use)]
+
+use metrics_utils::{measured_async_function, measured_function};
+
+#[must_use]
+struct Token;
+
+#[measured_async_function]
+async fn acquire() -> Token {
+    Token
+}
+
+#[measured_function]
+#[must_use = "the count is the only output"]
+fn count() -> usize {
+    3
+}
+
+#[measured_function]
+fn try_acquire() -> Result<Token, ()> {
+    Ok(Token)
+}
+
+async fn caller() {
+    acquire().await;
+    count();
+    try_acquire();
+}
+
+fn main() {
+    let _ = caller;
+}
@@ -0,0 +1,10 @@
+use metrics_utils::measured_function;
+
+fn runtime_name() -> &'static str {
+    "runtime"
+}
+
+#[measured_function(name_fn = runtime_name)]
+fn named_at_runtime() {}
+
+fn main() {}
@@ -0,0 +1,6 @@
+use metrics_utils::measured_function;
+
+#[measured_function(units = "seconds")]
+fn login() {}
+
+fn main() {}
@@ -0,0 +1,6 @@
+use metrics_utils::measured_function;
+
+#[measured_function(bucket = [1.0, 5.0, 25.0])]
+fn login() {}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use metrics_utils::measured_function;
+
+#[measured_function(no_histogram)]
+fn records_nothing() {}
+
+#[measured_function(count, no_histogram, buckets = [1.0, 10.0])]
+fn buckets_for_nothing() {}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use metrics_utils::measured_async_function;
+
+#[measured_async_function]
+mod handlers {
+    pub async fn handle() {}
+}
+
+fn main() {}
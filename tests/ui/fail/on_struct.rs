@@ -0,0 +1,8 @@
+use metrics_utils::measured_function;
+
+#[measured_function]
+struct Config {
+    retries: u32,
+}
+
+fn main() {}
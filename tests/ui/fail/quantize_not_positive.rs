@@ -0,0 +1,9 @@
+use metrics_utils::measured_function;
+
+#[measured_function(quantize = 0.0)]
+fn zero() {}
+
+#[measured_function(quantize = -1.0)]
+fn negative() {}
+
+fn main() {}
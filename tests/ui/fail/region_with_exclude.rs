@@ -0,0 +1,9 @@
+use metrics_utils::measured_function;
+
+#[measured_function]
+fn both() {
+    metrics_utils::exclude!({ std::thread::yield_now() });
+    metrics_utils::region!({ std::thread::yield_now() });
+}
+
+fn main() {}
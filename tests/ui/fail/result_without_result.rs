@@ -0,0 +1,8 @@
+use metrics_utils::measured_function;
+
+#[measured_function(result)]
+fn lookup() -> Option<u32> {
+    None
+}
+
+fn main() {}
@@ -0,0 +1,6 @@
+use metrics_utils::measured_function;
+
+#[measured_function("login", "signin")]
+fn login() {}
+
+fn main() {}
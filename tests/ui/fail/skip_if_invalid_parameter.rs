@@ -0,0 +1,13 @@
+use metrics_utils::measured_function;
+
+#[measured_function(skip_if = cached)]
+fn lookup(key: u32, cached: u8) -> u32 {
+    key + u32::from(cached)
+}
+
+#[measured_function(skip_if = cached)]
+fn missing(key: u32) -> u32 {
+    key
+}
+
+fn main() {}
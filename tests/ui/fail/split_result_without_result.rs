@@ -0,0 +1,8 @@
+use metrics_utils::measured_function;
+
+#[measured_function(split_result)]
+fn total() -> u32 {
+    3
+}
+
+fn main() {}
@@ -0,0 +1,12 @@
+use metrics_utils::measured_function;
+
+#[measured_function(strict, metric = "dbQueryDuration")]
+fn camel_case() {}
+
+#[measured_function(strict, metric = "db_query_duration")]
+fn missing_suffix() {}
+
+#[measured_function(strict, label_key = "Handler")]
+fn label_key() {}
+
+fn main() {}
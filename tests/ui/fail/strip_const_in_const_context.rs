@@ -0,0 +1,12 @@
+use metrics_utils::measured_function;
+
+#[measured_function(strip_const)]
+const fn double(value: u32) -> u32 {
+    value * 2
+}
+
+const FOUR: u32 = double(2);
+
+fn main() {
+    let _ = FOUR;
+}
@@ -0,0 +1,6 @@
+use metrics_utils::measured_function;
+
+#[measured_function(task_id_label)]
+fn blocking() {}
+
+fn main() {}
@@ -0,0 +1,8 @@
+use metrics_utils::measured_try_function;
+
+#[measured_try_function]
+fn total(values: &[u32]) -> u32 {
+    values.iter().sum()
+}
+
+fn main() {}
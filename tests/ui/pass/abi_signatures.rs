@@ -0,0 +1,27 @@
+use metrics_utils::measured_function;
+
+#[measured_function]
+unsafe fn read(pointer: *const u32) -> u32 {
+    *pointer
+}
+
+#[measured_function]
+extern "C" fn double(value: u32) -> u32 {
+    value * 2
+}
+
+#[measured_function]
+unsafe extern "C" fn read_c(pointer: *const u32) -> u32 {
+    *pointer
+}
+
+fn main() {
+    // The qualifiers are kept, so the functions still coerce to pointers of the same type.
+    let read: unsafe fn(*const u32) -> u32 = read;
+    let double: extern "C" fn(u32) -> u32 = double;
+    let read_c: unsafe extern "C" fn(*const u32) -> u32 = read_c;
+    let value = 21;
+    assert_eq!(unsafe { read(&value) }, 21);
+    assert_eq!(double(21), 42);
+    assert_eq!(unsafe { read_c(&value) }, 21);
+}
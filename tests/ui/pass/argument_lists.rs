@@ -0,0 +1,30 @@
+use metrics_utils::{measured_async_function, measured_function};
+
+#[measured_function("name",)]
+fn trailing_comma_after_the_name() {}
+
+#[measured_function(count,)]
+fn trailing_comma_after_a_flag() {}
+
+#[measured_function(
+    "multi_line",
+    count,
+    labels(
+        tier = "critical",
+        region = "eu",
+    ),
+    buckets = [1.0, 5.0, 10.0,],
+)]
+fn multi_line() {}
+
+#[measured_async_function(
+    "spaced"   ,   unit   =   "seconds"  ,
+)]
+async fn odd_whitespace() {}
+
+fn main() {
+    trailing_comma_after_the_name();
+    trailing_comma_after_a_flag();
+    multi_line();
+    let _ = odd_whitespace;
+}
@@ -0,0 +1,24 @@
+use std::time::Instant;
+
+use metrics_utils::{measured_async_function, measured_function};
+
+#[measured_function]
+fn sync_bindings() -> f64 {
+    let start = Instant::now();
+    let result = 1.0;
+    let duration = start.elapsed().as_secs_f64();
+    result + duration
+}
+
+#[measured_async_function]
+async fn async_bindings() -> f64 {
+    let start = Instant::now();
+    let result = 1.0;
+    let duration = start.elapsed().as_secs_f64();
+    result + duration
+}
+
+fn main() {
+    assert!(sync_bindings() >= 1.0);
+    drop(async_bindings());
+}
@@ -0,0 +1,52 @@
+use metrics_utils::{measured_async_function, measured_function};
+
+#[measured_function]
+fn first<T>(values: &[T]) -> &T {
+    &values[0]
+}
+
+#[measured_function]
+fn longest<'a>(a: &'a str, b: &'a str) -> &'a str {
+    if a.len() >= b.len() {
+        a
+    } else {
+        b
+    }
+}
+
+#[measured_function]
+fn first_mut<T>(values: &mut [T]) -> Option<&mut T> {
+    values.first_mut()
+}
+
+struct Config {
+    name: String,
+}
+
+impl Config {
+    #[measured_function]
+    fn name(&self) -> &str {
+        &self.name
+    }
+}
+
+#[measured_async_function]
+async fn async_first(values: &[u32]) -> &u32 {
+    &values[0]
+}
+
+fn main() {
+    let values = vec![1, 2, 3];
+    assert_eq!(*first(&values), 1);
+    assert_eq!(longest("ab", "abc"), "abc");
+    let mut values = values;
+    if let Some(value) = first_mut(&mut values) {
+        *value = 10;
+    }
+    assert_eq!(values[0], 10);
+    let config = Config {
+        name: "svc".to_owned(),
+    };
+    assert_eq!(config.name(), "svc");
+    let _ = async_first;
+}
@@ -0,0 +1,46 @@
+#![deny(unreachable_code)]
+
+use std::convert::Infallible;
+use std::time::Duration;
+
+use metrics_utils::{measured_async_function, measured_function};
+
+#[measured_function]
+fn worker_loop() -> ! {
+    loop {
+        std::thread::sleep(Duration::from_secs(1));
+    }
+}
+
+#[measured_function(result)]
+fn serve() -> Result<Infallible, String> {
+    loop {
+        std::thread::sleep(Duration::from_secs(1));
+    }
+}
+
+#[measured_function]
+fn never_returns() -> Infallible {
+    loop {
+        std::thread::sleep(Duration::from_secs(1));
+    }
+}
+
+#[measured_function(result)]
+fn sync_todo() -> Result<(), String> {
+    todo!()
+}
+
+#[measured_async_function]
+async fn async_panics() {
+    panic!("not yet")
+}
+
+fn main() {
+    // Compiling without warnings is the point, calling them would only panic or loop.
+    let _ = worker_loop;
+    let _ = serve;
+    let _ = never_returns;
+    let _ = sync_todo;
+    let _ = async_panics;
+}
@@ -0,0 +1,47 @@
+#![deny(warnings)]
+
+use metrics_utils::measured_function;
+
+#[measured_function]
+#[inline]
+fn inlined(value: u32) -> u32 {
+    value + 1
+}
+
+#[measured_function]
+#[inline(always)]
+fn always_inlined(value: u32) -> u32 {
+    value + 2
+}
+
+#[measured_function]
+#[cold]
+fn rarely_called() -> u32 {
+    3
+}
+
+#[measured_function]
+#[no_mangle]
+pub extern "C" fn metrics_utils_exported(value: u32) -> u32 {
+    value * 2
+}
+
+#[measured_function]
+#[cfg(any())]
+fn compiled_out() -> u32 {
+    does_not_exist()
+}
+
+extern "C" {
+    // Resolves to the instrumented function through its unmangled symbol.
+    #[link_name = "metrics_utils_exported"]
+    fn exported_by_symbol(value: u32) -> u32;
+}
+
+fn main() {
+    assert_eq!(inlined(1), 2);
+    assert_eq!(always_inlined(1), 3);
+    assert_eq!(rarely_called(), 3);
+    assert_eq!(metrics_utils_exported(2), 4);
+    assert_eq!(unsafe { exported_by_symbol(2) }, 4);
+}
@@ -0,0 +1,64 @@
+use std::fmt::Debug;
+use std::str::FromStr;
+
+use metrics_utils::{measured, measured_async_function, measured_function};
+
+#[measured_function]
+fn parse<T: FromStr>(s: &str) -> Result<T, T::Err>
+where
+    T: Debug,
+{
+    s.parse()
+}
+
+#[measured_function]
+fn longest<'a, 'b: 'a>(a: &'a str, b: &'b str) -> &'a str {
+    if a.len() >= b.len() {
+        a
+    } else {
+        b
+    }
+}
+
+#[measured_function]
+fn first<const N: usize, T: Copy + Default>(values: [T; N]) -> T {
+    values.first().copied().unwrap_or_default()
+}
+
+#[measured_function(result)]
+fn convert<'a, T, E, const STRICT: bool>(input: &'a str) -> Result<T, E>
+where
+    T: FromStr<Err = E> + Debug + 'a,
+    E: Debug,
+    for<'x> &'x T: Debug,
+{
+    if STRICT {
+        input.parse()
+    } else {
+        input.trim().parse()
+    }
+}
+
+#[measured_async_function]
+async fn echo<'a, T>(value: &'a T) -> &'a T
+where
+    T: Sync + ?Sized,
+{
+    value
+}
+
+#[measured]
+fn boxed<T: Into<Box<dyn std::error::Error>>>(value: T) -> Box<dyn std::error::Error> {
+    value.into()
+}
+
+fn main() {
+    assert_eq!(parse::<u8>("3"), Ok(3));
+    assert_eq!(longest("ab", "c"), "ab");
+    assert_eq!(first([4u8, 5]), 4);
+    assert_eq!(first::<0, u8>([]), 0);
+    assert_eq!(convert::<u16, _, false>(" 7 "), Ok(7));
+    assert!(convert::<u16, _, true>(" 7 ").is_err());
+    drop(echo("text"));
+    drop(boxed(String::from("x")));
+}
@@ -0,0 +1,12 @@
+use metrics_utils::{measured_async_function, measured_function};
+
+#[measured_function(strict, count, metric = "db_query_duration_milliseconds")]
+fn query() {}
+
+#[measured_async_function(strict, unit = "seconds", label_key = "handler")]
+async fn fetch() {}
+
+fn main() {
+    query();
+    let _ = fetch;
+}
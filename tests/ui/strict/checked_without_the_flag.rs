@@ -0,0 +1,9 @@
+use metrics_utils::measured_function;
+
+#[measured_function(metric = "db_query_duration")]
+fn query() {}
+
+#[measured_function(label_key = "Handler")]
+fn handle() {}
+
+fn main() {}
@@ -0,0 +1,283 @@
+#![cfg(not(any(
+    feature = "disabled",
+    feature = "otel",
+    feature = "buffered",
+    feature = "global_sink",
+    feature = "strict"
+)))]
+
+mod common;
+
+use std::time::Duration;
+
+use common::{capture, capture_async, capture_paused};
+use metrics_utils::{measured_async_function, measured_function};
+
+#[measured_function]
+fn sleep_default() {
+    std::thread::sleep(Duration::from_millis(20));
+}
+
+#[measured_function(unit = "seconds")]
+fn sleep_seconds() {
+    std::thread::sleep(Duration::from_millis(20));
+}
+
+#[measured_async_function]
+async fn async_sleep_default() {
+    tokio::time::sleep(Duration::from_millis(20)).await;
+}
+
+#[measured_async_function(unit = "seconds")]
+async fn async_sleep_seconds() {
+    tokio::time::sleep(Duration::from_millis(20)).await;
+}
+
+#[measured_function]
+fn fast() {}
+
+#[test]
+fn milliseconds_are_the_default() {
+    let recorded = capture(sleep_default);
+    let samples = recorded.histogram(
+        "function_duration_milliseconds",
+        &[("function", "sleep_default"), ("status", "ok")],
+    );
+    assert_eq!(samples.len(), 1);
+    assert!((20.0..1000.0).contains(&samples[0]), "{samples:?}");
+}
+
+#[test]
+fn seconds_pick_the_seconds_name_and_value() {
+    let recorded = capture(sleep_seconds);
+    let samples = recorded.histogram(
+        "function_duration_seconds",
+        &[("function", "sleep_seconds"), ("status", "ok")],
+    );
+    assert_eq!(samples.len(), 1);
+    assert!((0.02..1.0).contains(&samples[0]), "{samples:?}");
+}
+
+#[test]
+fn async_functions_convert_the_same_way() {
+    let recorded = capture_async(async {
+        async_sleep_default().await;
+        async_sleep_seconds().await;
+    });
+    let labels = |function| {
+        [
+            ("function", function),
+            ("status", "ok"),
+            ("completion", "completed"),
+        ]
+    };
+    let millis = recorded.histogram(
+        "async_function_duration_milliseconds",
+        &labels("async_sleep_default"),
+    );
+    let seconds = recorded.histogram(
+        "async_function_duration_seconds",
+        &labels("async_sleep_seconds"),
+    );
+    assert!(
+        millis.len() == 1 && (20.0..1000.0).contains(&millis[0]),
+        "{millis:?}"
+    );
+    assert!(
+        seconds.len() == 1 && (0.02..1.0).contains(&seconds[0]),
+        "{seconds:?}"
+    );
+}
+
+#[test]
+fn sub_millisecond_calls_are_not_truncated_to_zero() {
+    let recorded = capture(fast);
+    let samples = recorded.histogram(
+        "function_duration_milliseconds",
+        &[("function", "fast"), ("status", "ok")],
+    );
+    assert_eq!(samples.len(), 1);
+    assert!(samples[0] > 0.0 && samples[0] < 1.0, "{samples:?}");
+}
+
+#[measured_function(unit = "nanoseconds")]
+fn busy_nanos() -> u64 {
+    (0..1_000u64).map(std::hint::black_box).sum()
+}
+
+#[measured_function(unit = "microseconds")]
+fn sleep_micros() {
+    std::thread::sleep(Duration::from_millis(2));
+}
+
+#[test]
+fn nanoseconds_keep_the_resolution_of_fast_calls() {
+    let recorded = capture(|| assert_eq!(busy_nanos(), 499_500));
+    let samples = recorded.histogram(
+        "function_duration_nanoseconds",
+        &[("function", "busy_nanos"), ("status", "ok")],
+    );
+    assert_eq!(samples.len(), 1);
+    // Well under a millisecond, and not rounded to one.
+    assert!(samples[0] > 0.0 && samples[0] < 1_000_000.0, "{samples:?}");
+}
+
+#[test]
+fn microseconds_pick_the_microseconds_name_and_value() {
+    let recorded = capture(sleep_micros);
+    let samples = recorded.histogram(
+        "function_duration_microseconds",
+        &[("function", "sleep_micros"), ("status", "ok")],
+    );
+    assert_eq!(samples.len(), 1);
+    assert!((2_000.0..1_000_000.0).contains(&samples[0]), "{samples:?}");
+}
+
+/// Microseconds on a log10 scale.
+fn log_micros(duration: Duration) -> f64 {
+    (duration.as_secs_f64() * 1e6).log10()
+}
+
+#[measured_function(convert = log_micros)]
+fn converted() {
+    std::thread::sleep(Duration::from_millis(10));
+}
+
+#[test]
+fn convert_records_the_value_of_the_converter() {
+    let recorded = capture(converted);
+    let samples = recorded.histogram(
+        "function_duration_milliseconds",
+        &[("function", "converted"), ("status", "ok")],
+    );
+    assert_eq!(samples.len(), 1);
+    // 10ms is 10^4 microseconds; a second would be 10^6.
+    assert!((4.0..6.0).contains(&samples[0]), "{samples:?}");
+}
+
+#[measured_function(quantize = 0.5)]
+fn quantized() {
+    std::thread::sleep(Duration::from_millis(3));
+}
+
+#[test]
+fn quantize_snaps_the_value_to_the_granularity() {
+    let recorded = capture(quantized);
+    let samples = recorded.histogram(
+        "function_duration_milliseconds",
+        &[("function", "quantized"), ("status", "ok")],
+    );
+    assert_eq!(samples.len(), 1);
+    assert!(samples[0] >= 3.0, "{samples:?}");
+    assert_eq!((samples[0] * 2.0).fract(), 0.0, "{samples:?}");
+}
+
+#[measured_function(expected = "microseconds")]
+fn expected_micros() {
+    std::thread::sleep(Duration::from_millis(2));
+}
+
+#[measured_function(expected = "seconds")]
+fn expected_seconds() {
+    std::thread::sleep(Duration::from_millis(20));
+}
+
+#[test]
+fn expected_picks_the_suffix_and_conversion() {
+    let recorded = capture(|| {
+        expected_micros();
+        expected_seconds();
+    });
+    let micros = recorded.histogram(
+        "function_duration_microseconds",
+        &[("function", "expected_micros"), ("status", "ok")],
+    );
+    let seconds = recorded.histogram(
+        "function_duration_seconds",
+        &[("function", "expected_seconds"), ("status", "ok")],
+    );
+    assert!(
+        micros.len() == 1 && (2_000.0..1_000_000.0).contains(&micros[0]),
+        "{micros:?}"
+    );
+    assert!(
+        seconds.len() == 1 && (0.02..1.0).contains(&seconds[0]),
+        "{seconds:?}"
+    );
+}
+
+#[measured_async_function(value_type = "u64", clock = "tokio")]
+async fn whole_nanoseconds() {
+    tokio::time::sleep(Duration::from_millis(50)).await;
+}
+
+#[measured_async_function(value_type = "u64", as = "counter", clock = "tokio")]
+async fn counted_nanoseconds() {
+    tokio::time::sleep(Duration::from_millis(3)).await;
+}
+
+#[test]
+fn value_type_u64_records_whole_nanoseconds() {
+    let recorded = capture_paused(async {
+        whole_nanoseconds().await;
+        counted_nanoseconds().await;
+        counted_nanoseconds().await;
+    });
+    let samples = recorded.histogram(
+        "async_function_duration_nanoseconds",
+        &[
+            ("function", "whole_nanoseconds"),
+            ("status", "ok"),
+            ("completion", "completed"),
+        ],
+    );
+    assert_eq!(samples, [50_000_000.0]);
+    let total = recorded.counter(
+        "async_function_duration_nanoseconds_total",
+        &[
+            ("function", "counted_nanoseconds"),
+            ("status", "ok"),
+            ("completion", "completed"),
+        ],
+    );
+    assert_eq!(total, Some(6_000_000));
+}
+
+#[measured_async_function(dual_unit, clock = "tokio")]
+async fn migrating() {
+    tokio::time::sleep(Duration::from_millis(250)).await;
+}
+
+#[measured_function(unit = "seconds", dual_unit)]
+fn migrating_from_seconds() {}
+
+#[test]
+fn dual_unit_records_milliseconds_and_seconds_from_one_measurement() {
+    let recorded = capture_paused(migrating());
+    let labels = [
+        ("function", "migrating"),
+        ("status", "ok"),
+        ("completion", "completed"),
+    ];
+    assert_eq!(
+        recorded.histogram("async_function_duration_milliseconds", &labels),
+        [250.0]
+    );
+    assert_eq!(
+        recorded.histogram("async_function_duration_seconds", &labels),
+        [0.25]
+    );
+}
+
+#[test]
+fn dual_unit_works_from_seconds_too() {
+    let recorded = capture(migrating_from_seconds);
+    let labels = [("function", "migrating_from_seconds"), ("status", "ok")];
+    let seconds = recorded.histogram("function_duration_seconds", &labels);
+    let milliseconds = recorded.histogram("function_duration_milliseconds", &labels);
+    assert_eq!((seconds.len(), milliseconds.len()), (1, 1));
+    assert!(
+        (milliseconds[0] - seconds[0] * 1000.0).abs() < 1e-9,
+        "{milliseconds:?} {seconds:?}"
+    );
+}
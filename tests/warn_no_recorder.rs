@@ -0,0 +1,26 @@
+//! Run with `cargo test --features warn_no_recorder,metrics-utils/warn_no_recorder --test
+//! warn_no_recorder`. The check runs once per process, so this file holds a single test.
+
+#![cfg(feature = "warn_no_recorder")]
+
+mod common;
+
+use common::capture_logs;
+use metrics_utils::measured_function;
+
+#[measured_function]
+fn unrecorded() {}
+
+#[test]
+fn a_missing_recorder_is_reported_once() {
+    let logs = capture_logs(|| {
+        unrecorded();
+        unrecorded();
+    });
+    assert_eq!(
+        logs.matches("no `metrics` recorder is installed").count(),
+        1,
+        "{logs}"
+    );
+    assert!(logs.contains("WARN"), "{logs}");
+}